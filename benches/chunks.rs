@@ -0,0 +1,205 @@
+// cpu side of the voxel world, none of these need a gpu
+// run with `cargo bench`, or `cargo bench -- meshing` for one group
+use cgmath::Vector3;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use teal_mountain::{
+    rendering::mesh_buffer_pool::MeshBufferPool,
+    voxel_tools::{
+        block::BlockType,
+        chunk::{Chunk, LocalCoordinate, SIZE},
+        chunks::Chunks,
+        generator::WorldGenerator,
+        mesh_builder,
+        rendering::voxel_vertex::VoxelVertex,
+        voxel::Voxel,
+    },
+};
+
+// block at a world voxel coordinate
+type Pattern<'a> = &'a dyn Fn(Vector3<i32>) -> Voxel;
+
+// chunk filled by a pattern over world voxel coordinates
+fn pattern_chunk(chunk_pos: Vector3<i32>, pattern: Pattern) -> Chunk {
+    let mut chunk = Chunk::new();
+    for index in 0..SIZE * SIZE * SIZE {
+        let local = Chunk::get_local_coordinate(index as i32);
+        let world = chunk_pos * SIZE as i32 + Vector3::new(local.0, local.1, local.2);
+        if let Some(voxel) = chunk.get_voxel_from_index_mut(index) {
+            *voxel = pattern(world);
+        }
+    }
+    chunk
+}
+
+// the chunk at the origin and all chunks around it, everything meshing and lookups touch
+fn pattern_chunks(pattern: Pattern) -> Chunks {
+    let mut chunks = Chunks::new();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let chunk_pos = Vector3::new(x, y, z);
+                chunks.insert_chunk_data(chunk_pos, pattern_chunk(chunk_pos, pattern));
+            }
+        }
+    }
+    chunks
+}
+
+fn dense(_: Vector3<i32>) -> Voxel {
+    Voxel::new_block(BlockType::Grass)
+}
+
+fn empty(_: Vector3<i32>) -> Voxel {
+    Voxel::new_empty()
+}
+
+// every other voxel solid, the most faces a chunk can have
+fn checkerboard(pos: Vector3<i32>) -> Voxel {
+    if (pos.x + pos.y + pos.z).rem_euclid(2) == 0 {
+        Voxel::new_block(BlockType::Grass)
+    } else {
+        Voxel::new_empty()
+    }
+}
+
+// random mix of all block types, seeded so every run meshes the same chunk
+fn noisy() -> impl Fn(Vector3<i32>) -> Voxel {
+    let mut rng = StdRng::seed_from_u64(484);
+    let blocks = (0..(SIZE * 3).pow(3))
+        .map(|_| BlockType::ALL[rng.gen_range(0..BlockType::COUNT)])
+        .collect::<Vec<_>>();
+    move |pos: Vector3<i32>| {
+        let size = SIZE as i32 * 3;
+        let p = pos + Vector3::new(SIZE as i32, SIZE as i32, SIZE as i32);
+        Voxel::new_block(blocks[(p.x * size * size + p.y * size + p.z) as usize])
+    }
+}
+
+fn generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generation");
+    let generator = WorldGenerator::default();
+    // chunk positions in the air, through the terrain surface and below the water level
+    for (name, chunk_pos) in [
+        ("sky", Vector3::new(0, 8, 0)),
+        ("surface", Vector3::new(0, 0, 0)),
+        ("underwater", Vector3::new(3, -2, 5)),
+    ]
+    .iter()
+    {
+        let chunk_world_pos = Chunks::chunk_to_world(*chunk_pos);
+        let mut chunk = Chunk::new();
+        group.bench_function(*name, |b| {
+            b.iter(|| chunk.build_voxel_data(&generator, black_box(&chunk_world_pos)))
+        });
+    }
+    group.finish();
+}
+
+fn meshing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("meshing");
+    let noisy = noisy();
+    let patterns: [(&str, Pattern); 4] = [
+        ("dense", &dense),
+        ("empty", &empty),
+        ("noisy", &noisy),
+        ("checkerboard", &checkerboard),
+    ];
+    let chunk_pos = Vector3::new(0, 0, 0);
+    let chunk_world_pos = Chunks::chunk_to_world(chunk_pos);
+    for (name, pattern) in patterns.iter() {
+        let chunks = pattern_chunks(*pattern);
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                mesh_builder::build_chunk_mesh_data(
+                    &chunks,
+                    &chunk_pos,
+                    black_box(&chunk_world_pos),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn neighbour_lookups(c: &mut Criterion) {
+    let chunks = pattern_chunks(&noisy());
+    let chunk_pos = Vector3::new(0, 0, 0);
+    let size = SIZE as i32;
+    // every voxel of the chunk and the layer of its neighbours below, left and behind it,
+    // the same lookups meshing does
+    c.bench_function("try_get_voxel", |b| {
+        b.iter(|| {
+            let mut solid = 0u32;
+            for x in -1..size {
+                for y in -1..size {
+                    for z in -1..size {
+                        let local_pos = LocalCoordinate(x, y, z);
+                        if let Ok(voxel) = chunks.try_get_voxel(&chunk_pos, black_box(&local_pos)) {
+                            solid += voxel.is_solid() as u32;
+                        }
+                    }
+                }
+            }
+            solid
+        })
+    });
+}
+
+// frames of a camera flying in a straight line, with the budgets of the game
+const FLIGHT_FRAMES: usize = 60;
+// chunks per frame
+const FLIGHT_SPEED: f32 = 0.25;
+const FLIGHT_RENDER_DISTANCE: i32 = 3;
+
+fn fly(chunks: &mut Chunks, chunk_buffers: &mut MeshBufferPool) -> u32 {
+    let mut vertices = 0;
+    let direction = Vector3::new(1f32, 0f32, 0.5);
+    for frame in 0..FLIGHT_FRAMES {
+        chunks.position = direction * frame as f32 * FLIGHT_SPEED * SIZE as f32;
+        chunks.direction = direction;
+        chunks.update_streaming();
+        chunks.build_chunk_data_in_queue();
+        let mut budget = chunks.mesh_budget.start();
+        while budget.has_budget() {
+            let chunk_pos = match chunks.next_mesh_in_queue() {
+                Some(chunk_pos) => chunk_pos,
+                None => break,
+            };
+            let chunk_world_pos = Chunks::chunk_to_world(chunk_pos);
+            let mesh_data =
+                mesh_builder::build_chunk_mesh_data(chunks, &chunk_pos, &chunk_world_pos);
+            vertices += mesh_data.num_vertices();
+            budget.spend();
+        }
+        chunks.unload_data_queue();
+        chunks.unload_mesh_queue(chunk_buffers);
+    }
+    vertices
+}
+
+fn streaming(c: &mut Criterion) {
+    let mut group = c.benchmark_group("streaming");
+    // every flight starts with nothing loaded
+    group.sample_size(10);
+    group.bench_function("camera_flight", |b| {
+        b.iter_batched(
+            || {
+                let mut chunks = Chunks::new();
+                chunks.set_render_distance(FLIGHT_RENDER_DISTANCE);
+                let chunk_buffers = MeshBufferPool::new(
+                    "bench_chunk_buffers",
+                    std::mem::size_of::<VoxelVertex>() as u64,
+                );
+                (chunks, chunk_buffers)
+            },
+            |(mut chunks, mut chunk_buffers)| fly(&mut chunks, &mut chunk_buffers),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, generation, meshing, neighbour_lookups, streaming);
+criterion_main!(benches);
@@ -64,14 +64,19 @@ impl Camera {
             .update(&self.position, self.build_view_projection_matrix());
     }
 
-    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.sin()).normalize(),
-            Vector3::unit_y(),
-        );
+    // normalized direction the camera is looking
+    pub fn direction(&self) -> Vector3<f32> {
+        Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.sin()).normalize()
+    }
+
+    pub fn build_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let proj =
             cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.z_near, self.z_far);
-        OPENGL_TO_WGPU_MATRIX * proj * view
+        OPENGL_TO_WGPU_MATRIX * proj
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let view = cgmath::Matrix4::look_to_rh(self.position, self.direction(), Vector3::unit_y());
+        self.build_projection_matrix() * view
     }
 }
@@ -0,0 +1,150 @@
+use anyhow::{bail, Context, Result};
+use clap::{App, Arg, ArgMatches};
+use std::path::PathBuf;
+
+use crate::voxel_tools::{
+    chunks::{MAX_RENDER_DISTANCE, RENDER_DIST_RADIUS},
+    generator::GeneratorKind,
+    world_settings::{WorldSettings, MESH_CACHE_DIR},
+};
+
+// command line flags, None for everything not given
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    pub seed: Option<u32>,
+    // holds the world settings and the mesh cache
+    pub world_dir: Option<PathBuf>,
+    pub generator: Option<GeneratorKind>,
+    pub render_distance: Option<i32>,
+    // the camera position, instead of searching for a spawn point
+    pub start_position: Option<cgmath::Vector3<f32>>,
+}
+
+impl Args {
+    // prints the usage and exits on --help and malformed flags
+    pub fn from_env() -> Result<Self> {
+        Self::parse_from(std::env::args_os())
+    }
+
+    pub fn parse_from<I, T>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let matches = app().get_matches_from(args);
+        Self::from_matches(&matches)
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let seed = matches
+            .value_of("seed")
+            .map(|seed| {
+                seed.parse::<u32>()
+                    .with_context(|| format!("invalid --seed {:?}", seed))
+            })
+            .transpose()?;
+        let generator = matches
+            .value_of("generator")
+            .map(GeneratorKind::parse)
+            .transpose()?;
+        let render_distance = matches
+            .value_of("render-distance")
+            .map(parse_render_distance)
+            .transpose()?;
+        let start_position = matches.value_of("pos").map(parse_position).transpose()?;
+        Ok(Self {
+            seed,
+            world_dir: matches.value_of_os("world").map(PathBuf::from),
+            generator,
+            render_distance,
+            start_position,
+        })
+    }
+
+    // the settings saved in the world directory or the defaults, overridden by the flags,
+    // saved back so the world is the same next time
+    pub fn world_settings(&self) -> Result<WorldSettings> {
+        let saved = match &self.world_dir {
+            Some(dir) => WorldSettings::load(dir)?,
+            None => None,
+        };
+        let mut settings = saved.unwrap_or_default();
+        settings.seed = self.seed.unwrap_or(settings.seed);
+        settings.generator = self.generator.unwrap_or(settings.generator);
+        if let Some(dir) = &self.world_dir {
+            if saved.is_some() && saved != Some(settings) {
+                log::warn!("the world in {:?} is regenerated with {:?}", dir, settings);
+            }
+            settings.save(dir)?;
+        }
+        Ok(settings)
+    }
+
+    pub fn mesh_cache_dir(&self) -> Option<PathBuf> {
+        self.world_dir.as_ref().map(|dir| dir.join(MESH_CACHE_DIR))
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    App::new(env!("CARGO_PKG_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("voxel world renderer")
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("world seed, a number up to 4294967295"),
+        )
+        .arg(
+            Arg::with_name("world")
+                .long("world")
+                .value_name("DIR")
+                .help("keeps the seed, the generator and cached chunk meshes in DIR"),
+        )
+        .arg(
+            Arg::with_name("generator")
+                .long("generator")
+                .value_name("KIND")
+                .possible_values(&["caves", "crystals", "hills", "flat"])
+                .help("how the terrain is shaped"),
+        )
+        .arg(
+            Arg::with_name("render-distance")
+                .long("render-distance")
+                .value_name("CHUNKS")
+                .help("chunks loaded around the camera"),
+        )
+        .arg(
+            Arg::with_name("pos")
+                .long("pos")
+                .value_name("X,Y,Z")
+                .allow_hyphen_values(true)
+                .help("start position, otherwise open ground near the origin is searched"),
+        )
+}
+
+pub fn parse_render_distance(value: &str) -> Result<i32> {
+    match value.trim().parse::<i32>() {
+        Ok(distance) if (1..=MAX_RENDER_DISTANCE).contains(&distance) => Ok(distance),
+        _ => bail!(
+            "invalid --render-distance {:?}, expected 1 to {} (default {})",
+            value,
+            MAX_RENDER_DISTANCE,
+            RENDER_DIST_RADIUS
+        ),
+    }
+}
+
+// three comma separated numbers
+pub fn parse_position(value: &str) -> Result<cgmath::Vector3<f32>> {
+    let coordinates = value
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse::<f32>())
+        .collect::<std::result::Result<Vec<_>, _>>();
+    match coordinates.as_deref() {
+        Ok([x, y, z]) if x.is_finite() && y.is_finite() && z.is_finite() => {
+            Ok(cgmath::Vector3::new(*x, *y, *z))
+        }
+        _ => bail!("invalid --pos {:?}, expected x,y,z", value),
+    }
+}
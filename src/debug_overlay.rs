@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+
+use crate::{rendering::overlay::OverlayRenderer, voxel_tools::chunks::ChunkStats};
+
+// frames kept for the graph and the averages
+pub const FRAME_HISTORY: usize = 120;
+// frame time at the top of the graph, in milliseconds
+pub const GRAPH_MAX_MS: f32 = 50f32;
+pub const GRAPH_HEIGHT: f32 = 60f32;
+// pixels per frame in the graph
+pub const GRAPH_BAR_WIDTH: f32 = 2f32;
+// distance from the window edge
+const MARGIN: f32 = 8f32;
+
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const GOOD_FRAME_COLOR: [f32; 4] = [0.2, 0.9, 0.3, 0.9];
+const SLOW_FRAME_COLOR: [f32; 4] = [0.95, 0.8, 0.2, 0.9];
+const BAD_FRAME_COLOR: [f32; 4] = [0.95, 0.25, 0.2, 0.9];
+const TARGET_LINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.4];
+// 60 fps
+const TARGET_FRAME_MS: f32 = 1000f32 / 60f32;
+
+// durations of the last frames in milliseconds, oldest first
+#[derive(Clone, Debug, Default)]
+pub struct FrameTimes {
+    times: VecDeque<f32>,
+}
+
+impl FrameTimes {
+    pub fn push(&mut self, dt: std::time::Duration) {
+        if self.times.len() == FRAME_HISTORY {
+            self.times.pop_front();
+        }
+        self.times.push_back(dt.as_secs_f32() * 1000f32);
+    }
+
+    pub fn average_ms(&self) -> f32 {
+        if self.times.is_empty() {
+            return 0f32;
+        }
+        self.times.iter().sum::<f32>() / self.times.len() as f32
+    }
+
+    pub fn max_ms(&self) -> f32 {
+        self.times.iter().copied().fold(0f32, f32::max)
+    }
+
+    pub fn fps(&self) -> f32 {
+        let average = self.average_ms();
+        if average > 0f32 {
+            1000f32 / average
+        } else {
+            0f32
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &f32> {
+        self.times.iter()
+    }
+}
+
+// everything the overlay shows besides the frame times, gathered by the caller
+#[derive(Copy, Clone, Debug)]
+pub struct DebugStats {
+    pub camera_position: cgmath::Point3<f32>,
+    pub chunk_position: cgmath::Vector3<i32>,
+    pub chunks: ChunkStats,
+    pub vertex_count: u32,
+    // bytes allocated and in use by the chunk mesh buffers
+    pub chunk_memory: (u64, u64),
+    // name of the active debug view
+    pub debug_view: &'static str,
+}
+
+impl DebugStats {
+    pub fn lines(&self, frame_times: &FrameTimes) -> Vec<String> {
+        let megabytes = |bytes: u64| bytes as f64 / (1024f64 * 1024f64);
+        let p = self.camera_position;
+        let c = self.chunk_position;
+        vec![
+            format!(
+                "FPS {:.0} ({:.2} MS, MAX {:.2})",
+                frame_times.fps(),
+                frame_times.average_ms(),
+                frame_times.max_ms()
+            ),
+            format!("POS {:.1} {:.1} {:.1}", p.x, p.y, p.z),
+            format!("CHUNK {} {} {}", c.x, c.y, c.z),
+            format!(
+                "LOADED DATA {} MESH {}",
+                self.chunks.data_count, self.chunks.mesh_count
+            ),
+            format!(
+                "LOAD QUEUE DATA {} MESH {}",
+                self.chunks.data_load_queue, self.chunks.mesh_load_queue
+            ),
+            format!(
+                "UNLOAD QUEUE DATA {} MESH {}",
+                self.chunks.data_unload_queue, self.chunks.mesh_unload_queue
+            ),
+            format!("VERTICES {}", self.vertex_count),
+            format!(
+                "CHUNK BUFFERS {:.1}/{:.1} MB",
+                megabytes(self.chunk_memory.1),
+                megabytes(self.chunk_memory.0)
+            ),
+            format!("VIEW {}", self.debug_view),
+        ]
+    }
+}
+
+// F3 text in the top left corner with a frame time graph below it
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    // recorded while hidden too, so the graph is filled when it is opened
+    frame_times: FrameTimes,
+}
+
+impl DebugOverlay {
+    pub fn update(&mut self, dt: std::time::Duration) {
+        self.frame_times.push(dt);
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn build(&self, renderer: &mut OverlayRenderer, stats: &DebugStats) {
+        if !self.visible {
+            return;
+        }
+        let lines = stats.lines(&self.frame_times);
+        let line_height = OverlayRenderer::line_height();
+        let graph_width = FRAME_HISTORY as f32 * GRAPH_BAR_WIDTH;
+        let text_width = lines
+            .iter()
+            .map(|line| OverlayRenderer::text_width(line))
+            .fold(graph_width, f32::max);
+        let text_height = lines.len() as f32 * line_height;
+        renderer.rect(
+            MARGIN,
+            MARGIN,
+            text_width + MARGIN * 2f32,
+            text_height + GRAPH_HEIGHT + MARGIN * 3f32,
+            BACKGROUND_COLOR,
+        );
+        for (row, line) in lines.iter().enumerate() {
+            let y = MARGIN * 2f32 + row as f32 * line_height;
+            renderer.text(MARGIN * 2f32, y, line, TEXT_COLOR);
+        }
+
+        // newest frame on the right
+        let graph_bottom = MARGIN * 3f32 + text_height + GRAPH_HEIGHT;
+        for (index, ms) in self.frame_times.iter().enumerate() {
+            let height = (ms / GRAPH_MAX_MS).min(1f32) * GRAPH_HEIGHT;
+            let color = if *ms <= TARGET_FRAME_MS * 1.05 {
+                GOOD_FRAME_COLOR
+            } else if *ms <= TARGET_FRAME_MS * 2f32 {
+                SLOW_FRAME_COLOR
+            } else {
+                BAD_FRAME_COLOR
+            };
+            let x = MARGIN * 2f32 + index as f32 * GRAPH_BAR_WIDTH;
+            renderer.rect(x, graph_bottom - height, GRAPH_BAR_WIDTH, height, color);
+        }
+        let target_y = graph_bottom - TARGET_FRAME_MS / GRAPH_MAX_MS * GRAPH_HEIGHT;
+        renderer.rect(
+            MARGIN * 2f32,
+            target_y,
+            graph_width,
+            1f32,
+            TARGET_LINE_COLOR,
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    rendering::{
+        capabilities::Capabilities, debug_lines::DebugLines, frustum::Frustum,
+        render_utils::PipelineOptions,
+    },
+    voxel_tools::chunks::Chunks,
+};
+
+// chunk with the camera in it
+const CURRENT_CHUNK_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+const CHUNK_BOUNDS_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 0.6];
+
+// how the voxels are shaded, cycled with F4
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DebugView {
+    Shaded,
+    Wireframe,
+    // world space normals as colors
+    Normals,
+    // a random color per chunk mesh
+    ChunkColors,
+    // the linearized depth buffer over the whole frame
+    Depth,
+    // the light reaching every surface, from blue (dark) to red
+    LightLevel,
+}
+
+impl DebugView {
+    pub fn next(&self) -> Self {
+        match self {
+            DebugView::Shaded => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::Normals,
+            DebugView::Normals => DebugView::ChunkColors,
+            DebugView::ChunkColors => DebugView::Depth,
+            DebugView::Depth => DebugView::LightLevel,
+            DebugView::LightLevel => DebugView::Shaded,
+        }
+    }
+
+    // upper case, for the debug overlay
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugView::Shaded => "SHADED",
+            DebugView::Wireframe => "WIREFRAME",
+            DebugView::Normals => "NORMALS",
+            DebugView::ChunkColors => "CHUNK COLORS",
+            DebugView::Depth => "DEPTH",
+            DebugView::LightLevel => "LIGHT LEVEL",
+        }
+    }
+
+    // views that need a missing feature are skipped when cycling
+    pub fn is_supported(&self, capabilities: &Capabilities) -> bool {
+        match self {
+            DebugView::Wireframe => capabilities.wireframe(),
+            _ => true,
+        }
+    }
+
+    // the variant of the voxel pipeline drawing the view, None for the regular pipelines
+    pub fn voxel_pipeline_options(&self) -> Option<PipelineOptions> {
+        let fragment_entry = match self {
+            DebugView::Shaded | DebugView::Depth => return None,
+            DebugView::Wireframe => {
+                return Some(PipelineOptions {
+                    polygon_mode: wgpu::PolygonMode::Line,
+                    ..PipelineOptions::default()
+                })
+            }
+            DebugView::Normals => "fs_normals",
+            DebugView::ChunkColors => "fs_chunk_colors",
+            DebugView::LightLevel => "fs_light_level",
+        };
+        Some(PipelineOptions {
+            fragment_entry,
+            ..PipelineOptions::default()
+        })
+    }
+}
+
+// the debug view and its voxel pipelines, all created up front so switching is instant
+pub struct DebugViews {
+    pub view: DebugView,
+    // lines around every loaded chunk mesh on top of any view, toggled with F5
+    pub chunk_bounds: bool,
+    voxel_pipelines: Vec<(DebugView, wgpu::RenderPipeline)>,
+    // cycle order, without the views the device can't draw
+    supported: Vec<DebugView>,
+}
+
+impl DebugViews {
+    // create_pipeline builds a voxel pipeline with the given options
+    pub fn new(
+        capabilities: &Capabilities,
+        mut create_pipeline: impl FnMut(PipelineOptions) -> wgpu::RenderPipeline,
+    ) -> Self {
+        let mut voxel_pipelines = Vec::new();
+        let mut supported = Vec::new();
+        let mut view = DebugView::Shaded;
+        loop {
+            if view.is_supported(capabilities) {
+                if let Some(options) = view.voxel_pipeline_options() {
+                    voxel_pipelines.push((view, create_pipeline(options)));
+                }
+                supported.push(view);
+            } else {
+                log::info!("debug view {:?} is not supported by the device", view);
+            }
+            view = view.next();
+            if view == DebugView::Shaded {
+                break;
+            }
+        }
+        Self {
+            view: DebugView::Shaded,
+            chunk_bounds: false,
+            voxel_pipelines,
+            supported,
+        }
+    }
+
+    pub fn cycle(&mut self) {
+        self.view = self.view.next();
+        while !self.supported.contains(&self.view) {
+            self.view = self.view.next();
+        }
+        log::info!("debug view: {:?}", self.view);
+    }
+
+    // draws opaque and translucent voxels alike, None when the view keeps the regular shading
+    pub fn voxel_pipeline(&self) -> Option<&wgpu::RenderPipeline> {
+        self.voxel_pipelines
+            .iter()
+            .find(|(view, _)| *view == self.view)
+            .map(|(_, pipeline)| pipeline)
+    }
+
+    // ambient occlusion would darken the debug colors
+    pub fn is_shaded(&self) -> bool {
+        self.view == DebugView::Shaded
+    }
+
+    pub fn build_chunk_bounds(&self, lines: &mut DebugLines, chunks: &Chunks, frustum: &Frustum) {
+        if !self.chunk_bounds {
+            return;
+        }
+        let current = chunks.current_chunk_pos();
+        for chunk_pos in chunks.mesh_positions() {
+            let (min, max) = Chunks::chunk_bounds(*chunk_pos);
+            if !frustum.intersects_aabb(min, max) {
+                continue;
+            }
+            let color = if *chunk_pos == current {
+                CURRENT_CHUNK_COLOR
+            } else {
+                CHUNK_BOUNDS_COLOR
+            };
+            lines.aabb(min, max, color);
+        }
+    }
+}
@@ -1,186 +1,111 @@
-use wgpu::util::DeviceExt;
+use crate::{
+    camera::Camera,
+    rendering::post_process::{self, PostProcessPass},
+    texture::Texture,
+};
 
-use crate::{model::ModelVertex, rendering::vertex_desc::VertexDesc, texture::Texture, Vertex};
+// linear depth shown as white, closer is darker
+pub const DEPTH_VISIBLE_RANGE: f32 = 100f32;
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DepthUniform {
+    // x: near, y: far, z: visible range
+    params: [f32; 4],
+}
+
+// owns the depth buffer of the main pass and shows it in the top right quarter of the frame,
+// or over the whole frame as a debug view
 pub struct DepthPass {
-    pipeline: wgpu::RenderPipeline,
-    bind_group_layout: wgpu::BindGroupLayout,
+    pass: PostProcessPass,
     bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    // multisampled like the color target of the main pass
     pub texture: Texture,
-    index_buffer: wgpu::Buffer,
-    vertex_buffer: wgpu::Buffer,
-    num_indices: u32,
+    sample_count: u32,
+    // top right quarter of the frame
+    viewport: [f32; 4],
 }
 
-pub const DEPTH_VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [0.0, 0.0, 0.0],
-        tex_coords: [0.0, 1.0],
-    },
-    Vertex {
-        position: [1.0, 0.0, 0.0],
-        tex_coords: [1.0, 1.0],
-    },
-    Vertex {
-        position: [1.0, 1.0, 0.0],
-        tex_coords: [1.0, 0.0],
-    },
-    Vertex {
-        position: [0.0, 1.0, 0.0],
-        tex_coords: [0.0, 0.0],
-    },
-];
-
-pub const DEPTH_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
-
 impl DepthPass {
-    pub fn new(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) -> Self {
-        let texture = Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
-        let shader_module = crate::rendering::render_utils::create_shader_module(
+    pub fn new(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        sample_count: u32,
+    ) -> Self {
+        let texture = Self::create_texture(device, sc_desc, sample_count);
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("depth_pass_uniform_buffer"),
+            size: std::mem::size_of::<DepthUniform>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (depth_input, depth_source) = post_process::depth_input(sample_count);
+        let pass = PostProcessPass::new(
             device,
-            include_str!("depth_pass.wgsl"),
-            "depth pass",
+            "depth_pass",
+            &format!("{}\n{}", depth_source, include_str!("depth_pass.wgsl")),
+            &[depth_input],
+            true,
+            sc_desc.format,
         );
-
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("depth_pass.bind_group_layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStage::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Depth,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStage::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler {
-                        filtering: true,
-                        comparison: true,
-                    },
-                    count: None,
-                },
-            ],
-        });
-
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("depth_pass.bind_group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                },
-            ],
-        });
-
-        let depth_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("depth_pass.layout"),
-                bind_group_layouts: &[&bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("depth_pass.pipeline"),
-            layout: Some(&depth_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader_module,
-                entry_point: "vs_main",
-                buffers: &[ModelVertex::desc()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                clamp_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
-                entry_point: "fs_main",
-                targets: &[wgpu::ColorTargetState {
-                    format: sc_desc.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-            }),
-        });
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("depth_pass.vertex_buffer"),
-            contents: bytemuck::cast_slice(DEPTH_VERTICES),
-            usage: wgpu::BufferUsage::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("depth_pass.index_buffer"),
-            contents: bytemuck::cast_slice(DEPTH_INDICES),
-            usage: wgpu::BufferUsage::INDEX,
-        });
-        let num_indices = DEPTH_INDICES.len() as u32;
+        let bind_group = pass.create_bind_group(device, &[&texture.view], Some(&uniform_buffer));
 
         Self {
-            pipeline,
-            texture,
+            pass,
             bind_group,
-            bind_group_layout,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            uniform_buffer,
+            texture,
+            sample_count,
+            viewport: Self::viewport(sc_desc),
         }
     }
 
+    fn create_texture(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        sample_count: u32,
+    ) -> Texture {
+        Texture::create_depth_texture_with_samples(device, sc_desc, sample_count, "depth_texture")
+    }
+
+    fn viewport(sc_desc: &wgpu::SwapChainDescriptor) -> [f32; 4] {
+        let (width, height) = (sc_desc.width as f32, sc_desc.height as f32);
+        [width / 2f32, 0f32, width / 2f32, height / 2f32]
+    }
+
     pub fn resize(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
-        self.texture = Texture::create_depth_texture(device, sc_desc, "depth_texture");
-        self.bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("depth_pass.bind_group"),
-            layout: &self.bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&self.texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&self.texture.sampler),
-                },
-            ],
-        });
+        self.texture = Self::create_texture(device, sc_desc, self.sample_count);
+        self.bind_group =
+            self.pass
+                .create_bind_group(device, &[&self.texture.view], Some(&self.uniform_buffer));
+        self.viewport = Self::viewport(sc_desc);
     }
 
-    pub fn render(&self, frame: &wgpu::SwapChainTexture, encoder: &mut wgpu::CommandEncoder) {
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("render_pass.depth_visual"),
-            color_attachments: &[wgpu::RenderPassColorAttachment {
-                view: &frame.view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
-                    store: true,
-                },
-            }],
-            depth_stencil_attachment: None,
-        });
-        render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    // the depth is linearized with the clip planes of the camera
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        let uniform = DepthUniform {
+            params: [camera.z_near, camera.z_far, DEPTH_VISIBLE_RANGE, 0f32],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn render(
+        &self,
+        frame: &wgpu::SwapChainTexture,
+        encoder: &mut wgpu::CommandEncoder,
+        fullscreen: bool,
+    ) {
+        self.pass.render(
+            encoder,
+            &frame.view,
+            &self.bind_group,
+            wgpu::LoadOp::Load,
+            if fullscreen {
+                None
+            } else {
+                Some(self.viewport)
+            },
+        );
     }
 }
@@ -1,32 +1,25 @@
-struct VertexOutput {
-    [[builtin(position)]] builtin_position: vec4<f32>;
-    [[location(0)]] tex_coords: vec2<f32>;
-};
-
-[[stage(vertex)]]
-fn vs_main(
-    [[location(0)]] position: vec3<f32>,
-    [[location(1)]] tex_coords: vec2<f32>,
-) -> VertexOutput {
-    var out: VertexOutput;
-    out.tex_coords = tex_coords;
-    out.builtin_position = vec4<f32>(position, 1.0);
-    return out;
-}
+// t_depth, depth_size and load_depth are prepended, see post_process::depth_input
+[[group(0), binding(1)]]
+var s_post: sampler;
 
-[[group(0), binding(0)]]
-var t_depth: texture_depth_2d;
+[[block]]
+struct DepthUniform {
+    // x: near, y: far, z: linear depth shown as white
+    params: vec4<f32>;
+};
 
-[[group(0), binding(1)]]
-var s_depth: sampler_comparison;
+[[group(0), binding(2)]]
+var<uniform> u_depth: DepthUniform;
 
-[[stage(fragment), early_depth_test]]
+[[stage(fragment)]]
 fn fs_main(
     in: VertexOutput,
 ) -> [[location(0)]] vec4<f32> {
-    let near = 0.1;
-    let far = 100.0;
-    let depth = textureSampleCompare(t_depth, s_depth, in.tex_coords, 1.0);
-    let r = (2.0 * near * far) / (far + near - depth * (far - near));
-    return vec4<f32>(vec3<f32>(r), 1.0);
-}
\ No newline at end of file
+    let near = u_depth.params.x;
+    let far = u_depth.params.y;
+    let size = depth_size();
+    let texel = min(vec2<i32>(in.uv * vec2<f32>(size)), size - vec2<i32>(1));
+    let depth = load_depth(texel);
+    let linear_depth = near * far / (far - depth * (far - near));
+    return vec4<f32>(vec3<f32>(min(linear_depth / u_depth.params.z, 1.0)), 1.0);
+}
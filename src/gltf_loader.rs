@@ -0,0 +1,251 @@
+use anyhow::*;
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Vector3, Vector4};
+use std::path::Path;
+
+use crate::{
+    color::Color,
+    model::{generate_normals, Material, Mesh, Model, ModelVertex},
+    texture::{Texture, TextureOptions},
+};
+
+impl Model {
+    // meshes of the default scene with node transforms baked into the vertices,
+    // textures embedded in the file or next to it are both supported
+    pub fn load_gltf<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        path: P,
+    ) -> Result<Self> {
+        let (document, buffers, images) = gltf::import(path.as_ref())
+            .with_context(|| format!("loading gltf {:?}", path.as_ref()))?;
+
+        let mut materials = Vec::new();
+        for material in document.materials() {
+            let pbr = material.pbr_metallic_roughness();
+            let name = material.name().unwrap_or("gltf_material");
+            let diffuse_texture = match pbr.base_color_texture() {
+                Some(info) => {
+                    let image_index = info.texture().source().index();
+                    let image = images.get(image_index).with_context(|| {
+                        format!("material {} has no image {}", name, image_index)
+                    })?;
+                    let rgba =
+                        to_rgba8(image).with_context(|| format!("material {} texture", name))?;
+                    Texture::from_bytes_with_options(
+                        device,
+                        queue,
+                        &rgba,
+                        image.width,
+                        image.height,
+                        Some(name),
+                        &TextureOptions::mipmapped(),
+                    )?
+                }
+                None => {
+                    use crate::color::colors::*;
+                    Texture::from_color(device, queue, WHITE, 128, 128)?
+                }
+            };
+            let [r, g, b, a] = pbr.base_color_factor();
+            materials.push(Material::new(
+                device,
+                layout,
+                name,
+                diffuse_texture,
+                Color::new(r, g, b, a),
+            ));
+        }
+
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .context("gltf has no scene")?;
+        let mut meshes = Vec::new();
+        for node in scene.nodes() {
+            load_node(
+                device,
+                queue,
+                layout,
+                &node,
+                Matrix4::identity(),
+                &buffers,
+                &mut materials,
+                &mut meshes,
+            )?;
+        }
+        Ok(Self { meshes, materials })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_node(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    node: &gltf::Node,
+    parent_transform: Matrix4<f32>,
+    buffers: &[gltf::buffer::Data],
+    materials: &mut Vec<Material>,
+    meshes: &mut Vec<Mesh>,
+) -> Result<()> {
+    let transform = parent_transform * Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        let mesh_name = mesh.name().unwrap_or("gltf_mesh");
+        for primitive in mesh.primitives() {
+            let name = format!("{} {}", mesh_name, primitive.index());
+            let material_id = match primitive.material().index() {
+                Some(material_id) => material_id,
+                None => {
+                    materials.push(Material::white(device, queue, layout)?);
+                    materials.len() - 1
+                }
+            };
+            let textured = primitive
+                .material()
+                .pbr_metallic_roughness()
+                .base_color_texture()
+                .is_some();
+            let (vertices, indices) = load_primitive(
+                &primitive,
+                &name,
+                transform,
+                buffers,
+                materials[material_id].diffuse_color,
+                textured,
+            )?;
+            meshes.push(Mesh::new(device, &name, &vertices, &indices, material_id));
+        }
+    }
+    for child in node.children() {
+        load_node(
+            device, queue, layout, &child, transform, buffers, materials, meshes,
+        )?;
+    }
+    Ok(())
+}
+
+// vertices in model space, positions and normals transformed by the node
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    name: &str,
+    transform: Matrix4<f32>,
+    buffers: &[gltf::buffer::Data],
+    diffuse_color: Color,
+    textured: bool,
+) -> Result<(Vec<ModelVertex>, Vec<u32>)> {
+    if primitive.mode() != gltf::mesh::Mode::Triangles {
+        bail!(
+            "{}: only triangle meshes are supported, got {:?}",
+            name,
+            primitive.mode()
+        );
+    }
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let positions = reader
+        .read_positions()
+        .with_context(|| format!("{}: mesh has no positions", name))?
+        .map(|position| {
+            let position = transform * Vector4::new(position[0], position[1], position[2], 1f32);
+            [position.x, position.y, position.z]
+        })
+        .collect::<Vec<_>>();
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    if let Some(index) = indices
+        .iter()
+        .find(|index| **index as usize >= positions.len())
+    {
+        bail!("{}: index {} out of range", name, index);
+    }
+
+    let normals = match reader.read_normals() {
+        Some(normals) => {
+            // inverse transpose keeps normals perpendicular under non uniform scaling
+            let normal_matrix = Matrix3::from_cols(
+                transform.x.truncate(),
+                transform.y.truncate(),
+                transform.z.truncate(),
+            )
+            .invert()
+            .context("node transform can't be inverted")?
+            .transpose();
+            normals
+                .map(|normal| (normal_matrix * Vector3::from(normal)).normalize().into())
+                .collect::<Vec<[f32; 3]>>()
+        }
+        None => {
+            log::debug!("generating normals for mesh: {}", name);
+            generate_normals(&positions, &indices)
+        }
+    };
+
+    let tex_coords = match reader.read_tex_coords(0) {
+        Some(tex_coords) => tex_coords.into_f32().collect::<Vec<_>>(),
+        None if textured => bail!("{}: textured mesh has no texture coordinates", name),
+        None => vec![[0f32, 0f32]; positions.len()],
+    };
+    let diffuse_color: [f32; 3] = diffuse_color.into();
+    let colors = match reader.read_colors(0) {
+        Some(colors) => colors
+            .into_rgb_f32()
+            .map(|color| {
+                [
+                    color[0] * diffuse_color[0],
+                    color[1] * diffuse_color[1],
+                    color[2] * diffuse_color[2],
+                ]
+            })
+            .collect::<Vec<_>>(),
+        None => vec![diffuse_color; positions.len()],
+    };
+    if normals.len() != positions.len()
+        || tex_coords.len() != positions.len()
+        || colors.len() != positions.len()
+    {
+        bail!("{}: vertex attributes have different lengths", name);
+    }
+
+    let vertices = (0..positions.len())
+        .map(|i| ModelVertex {
+            position: positions[i],
+            tex_coords: tex_coords[i],
+            normal: normals[i],
+            color_diffuse: colors[i],
+        })
+        .collect();
+    Ok((vertices, indices))
+}
+
+fn to_rgba8(image: &gltf::image::Data) -> Result<Vec<u8>> {
+    use gltf::image::Format;
+    let pixels = &image.pixels;
+    let rgba = match image.format {
+        Format::R8G8B8A8 => pixels.clone(),
+        Format::R8G8B8 => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255u8].to_vec())
+            .collect(),
+        Format::B8G8R8A8 => pixels
+            .chunks_exact(4)
+            .flat_map(|p| [p[2], p[1], p[0], p[3]].to_vec())
+            .collect(),
+        Format::B8G8R8 => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[2], p[1], p[0], 255u8].to_vec())
+            .collect(),
+        Format::R8G8 => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[1], 0u8, 255u8].to_vec())
+            .collect(),
+        Format::R8 => pixels
+            .iter()
+            .flat_map(|p| [*p, *p, *p, 255u8].to_vec())
+            .collect(),
+        format => bail!("unsupported image format {:?}", format),
+    };
+    Ok(rgba)
+}
@@ -0,0 +1,18 @@
+// the engine as a library, used by the game binary in main.rs and by the benches
+pub mod camera;
+pub mod camera_controller;
+pub mod cli;
+pub mod color;
+pub mod debug_overlay;
+pub mod debug_view;
+pub mod depth_pass;
+pub mod gltf_loader;
+pub mod light;
+pub mod model;
+pub mod point_lights;
+pub mod props;
+pub mod rendering;
+pub mod texture;
+pub mod time_of_day;
+pub mod timing;
+pub mod voxel_tools;
@@ -1,24 +1,74 @@
+use crate::time_of_day::Sky;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Light {
+    // where the light marker model is drawn, point lights are shaded from point_lights
     pub position: [f32; 3],
     // due to uniforms requireing 16 byte (4 float) spacing, we need to use a padding field here
     pub _padding: u32,
     pub color: [f32; 3],
+    pub _padding2: u32,
+    // normalized, points from the surface towards the sun
+    pub sun_direction: [f32; 3],
+    pub _padding3: u32,
+    pub sun_color: [f32; 3],
+    pub _padding4: u32,
+    pub ambient_color: [f32; 3],
+    pub _padding5: u32,
+}
+
+impl Light {
+    // directional light and ambient follow the time of day
+    pub fn apply_sky(&mut self, sky: &Sky) {
+        self.sun_direction = sky.light_direction.into();
+        self.sun_color = sky.light_color.into();
+        self.ambient_color = sky.ambient_color.into();
+    }
 }
 
 pub fn create_light_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("light_bind_group_layout"),
-        entries: &[wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: None,
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
             },
-            count: None,
-        }],
+            // fog, see rendering::fog
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // point lights, light clusters and light indices, see point_lights
+            storage_entry(2),
+            storage_entry(3),
+            storage_entry(4),
+        ],
     })
 }
+
+fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
@@ -10,6 +10,7 @@ var<uniform> u_camera: CameraUniform;
 struct VertexOutput {
     [[builtin(position)]] builtin_position: vec4<f32>;
     [[location(0)]] color: vec2<f32>;
+    [[location(1)]] position: vec3<f32>;
 };
 
 
@@ -32,6 +33,7 @@ fn vs_main(
     var out: VertexOutput;
     let scale = 0.25;
     let scaled_position = position * scale + u_light.position;
+    out.position = scaled_position;
     out.builtin_position = u_camera.projection_view * vec4<f32>(scaled_position, 1.0);
     return out;
 }
@@ -40,5 +42,6 @@ fn vs_main(
 fn fs_main(
     in: VertexOutput,
 ) -> [[location(0)]] vec4<f32> {
-    return vec4<f32>(u_light.color, 1.0);
+    // brighter than white so the marker blooms
+    return vec4<f32>(apply_fog(u_light.color * 4.0, distance(in.position, u_camera.position)), 1.0);
 }
\ No newline at end of file
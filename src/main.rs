@@ -1,10 +1,5 @@
-use camera::Camera;
-use camera_controller::*;
-use cgmath::{InnerSpace, Zero};
+use anyhow::{Context, Result};
 use futures::executor::block_on;
-use model::Model;
-use rendering::gpu_resources::GpuResources;
-use voxel_tools::chunks::Chunks;
 use wgpu::util::DeviceExt;
 use winit::{
     event::*,
@@ -12,46 +7,58 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
-use crate::{
+use teal_mountain::{
+    camera::Camera,
+    camera_controller::*,
+    cli::Args,
+    debug_overlay::{DebugOverlay, DebugStats},
+    debug_view::{DebugView, DebugViews},
     depth_pass::DepthPass,
-    light::Light,
+    light::{self, Light},
+    model,
+    point_lights::{self, PointLight, PointLightId, PointLights},
+    props::{ModelId, PropSystem, ScatterRule},
     rendering::{
-        render_utils::create_render_pipeline, vertex_desc::VertexDesc, vertex_instance::*,
+        adapter_selection::{select_adapter, AdapterConfig},
+        capabilities::Capabilities,
+        debug_lines::DebugLines,
+        fog::{self, Fog, FogSettings},
+        frustum::Frustum,
+        gpu_resources::GpuResources,
+        hdr::{HdrPipeline, HdrSettings, Tonemapper, HDR_FORMAT},
+        msaa,
+        overlay::OverlayRenderer,
+        render_utils::{create_render_pipeline_with_options, AmbientTarget, PipelineOptions},
+        shadows::{ShadowMaps, ShadowSettings},
+        sky::SkyPass,
+        ssao::{Ssao, SsaoSettings},
+        vertex_desc::VertexDesc,
+        vertex_instance::*,
     },
-    voxel_tools::{
-        rendering::voxel_pipeline::create_voxel_pipeline,
+    texture::{self, SamplerOptions, TextureOptions},
+    time_of_day::{TimeOfDay, DEFAULT_DAY_LENGTH, SCRUB_STEP},
+    voxel_tools::block::BlockType,
+    voxel_tools::chunks::Chunks,
+    voxel_tools::mesh_cache::MeshCache,
+    voxel_tools::rendering::{
+        block_textures::{BlockTextureArray, BLOCK_TEXTURE_DIR},
+        voxel_pipeline::{create_voxel_pipeline, create_voxel_shadow_pipeline},
     },
+    voxel_tools::spawn::find_spawn,
 };
 
-mod camera;
-mod camera_controller;
-mod color;
-mod depth_pass;
-mod light;
-mod model;
-mod rendering;
-mod texture;
-mod voxel_tools;
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Vertex {
-    pub position: [f32; 3],
-    //color: [f32; 3],
-    pub tex_coords: [f32; 2],
-}
-
-pub const NUM_INSTANCES_PER_ROW: u32 = 100;
-pub const NUM_INSTANCES: u32 = NUM_INSTANCES_PER_ROW * NUM_INSTANCES_PER_ROW;
-pub const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(
-    NUM_INSTANCES_PER_ROW as f32 * 0.5f32,
-    0.0,
-    NUM_INSTANCES_PER_ROW as f32 * 0.5f32,
-);
+// degrees per second the point light circles around the origin
+const LIGHT_ORBIT_SPEED: f32 = 60f32;
+// colors of the lights placed with L, picked in turn
+const SPAWNED_LIGHT_COLORS: [[f32; 3]; 4] = [
+    [1.0, 0.6, 0.3],
+    [0.3, 0.6, 1.0],
+    [0.4, 1.0, 0.4],
+    [1.0, 0.3, 0.8],
+];
 
 struct State {
     gpu_resources: GpuResources,
-    instance_buffer: wgpu::Buffer,
     rotation: f32,
     surface: wgpu::Surface,
     device: wgpu::Device,
@@ -60,51 +67,75 @@ struct State {
     swap_chain: wgpu::SwapChain,
     render_pipeline: wgpu::RenderPipeline,
     size: winit::dpi::PhysicalSize<u32>,
+    // follows the sky color at the horizon
     clear_color: wgpu::Color,
+    time_of_day: TimeOfDay,
+    sky_pass: SkyPass,
+    // fades chunks into the sky before the render distance
+    fog: Fog,
     camera_uniform_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
     camera: Camera,
     camera_controller: CameraController,
-    depth_pass: depth_pass::DepthPass,
-    obj_model: Model,
+    depth_pass: DepthPass,
+    // the scene is drawn into its hdr target, then bloomed and tone mapped into the frame
+    hdr: HdrPipeline,
+    // darkens corners between the opaque and the translucent geometry
+    ssao: Ssao,
+    // drawn on top of the tone mapped frame
+    overlay_renderer: OverlayRenderer,
+    // F3 statistics
+    debug_overlay: DebugOverlay,
+    // F4 wireframe, normals and other views, F5 chunk bounds
+    debug_views: DebugViews,
+    debug_lines: DebugLines,
+    props: PropSystem,
+    // also drawn as the light marker
+    turkey: ModelId,
 
     light_render_pipeline: wgpu::RenderPipeline,
     light_bind_group: wgpu::BindGroup,
     light: Light,
     light_buffer: wgpu::Buffer,
+    point_lights: PointLights,
+    // circles around the origin, drawn with the light marker
+    orbit_light: PointLightId,
+    // placed at the camera with L, removed again with K
+    spawned_lights: Vec<PointLightId>,
 
     voxel_render_pipeline: wgpu::RenderPipeline,
+    // water and glass, blended after everything opaque
+    voxel_translucent_pipeline: wgpu::RenderPipeline,
+    block_textures: BlockTextureArray,
+    // sun depth cascades, rendered from the chunk meshes before the main pass
+    shadow_maps: ShadowMaps,
+    voxel_shadow_pipeline: wgpu::RenderPipeline,
     mouse_pressed: bool,
 
     chunks: Chunks,
 }
 
 impl State {
-    async fn new(window: &Window) -> Self {
+    async fn new(window: &Window, adapter_config: &AdapterConfig, args: &Args) -> Result<Self> {
         let size = window.inner_size();
 
         // handler for our gpu
-        let instance = wgpu::Instance::new(wgpu::BackendBit::DX12);
+        let instance = wgpu::Instance::new(adapter_config.all_backends());
         let surface = unsafe { instance.create_surface(window) };
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .unwrap();
+        let adapter = select_adapter(&instance, &surface, adapter_config)?;
+        let capabilities = Capabilities::from_adapter(&adapter)?;
         let (device, queue) = adapter
             .request_device(
-                &wgpu::DeviceDescriptor {
-                    //features: wgpu::Features::empty(),
-                    features: wgpu::Features::NON_FILL_POLYGON_MODE,
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
+                &capabilities.device_descriptor(),
                 None, // trace path
             )
             .await
-            .unwrap();
+            .with_context(|| {
+                format!(
+                    "creating a device on the adapter {}",
+                    adapter.get_info().name
+                )
+            })?;
         let sc_desc = wgpu::SwapChainDescriptor {
             usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
             format: adapter
@@ -115,6 +146,13 @@ impl State {
             present_mode: wgpu::PresentMode::Fifo,
         };
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
+        let sample_count = msaa::sample_count_from_env(
+            &device,
+            &capabilities,
+            HDR_FORMAT,
+            texture::Texture::DEPTH_FORMAT,
+        )?;
+        log::info!("msaa: {}x", sample_count);
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -125,7 +163,7 @@ impl State {
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
                             view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
                         },
                         count: None,
                     },
@@ -142,11 +180,20 @@ impl State {
                 label: Some("texture_bind_group_layout"),
             });
 
-        let light = Light {
+        let time_of_day = TimeOfDay::new(DEFAULT_DAY_LENGTH);
+        let mut light = Light {
             position: [50.0, 2.0, 50.0],
             _padding: 0,
             color: [1.0, 1.0, 1.0],
+            _padding2: 0,
+            sun_direction: [0.0, 1.0, 0.0],
+            _padding3: 0,
+            sun_color: [0.0, 0.0, 0.0],
+            _padding4: 0,
+            ambient_color: [0.0, 0.0, 0.0],
+            _padding5: 0,
         };
+        light.apply_sky(&time_of_day.sky());
 
         let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("light buffer"),
@@ -154,60 +201,40 @@ impl State {
             usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
         });
 
+        let fog = Fog::new(&device, FogSettings::default());
+        let mut point_lights = PointLights::new(&device);
+        let orbit_light = point_lights.add(PointLight {
+            position: light.position.into(),
+            color: light.color.into(),
+            intensity: 60.0,
+            radius: 40.0,
+        });
         let light_bind_group_layout = light::create_light_bind_group_layout(&device);
 
+        let point_light_entries = point_lights.bind_group_entries();
         let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("light bind group"),
             layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: fog.buffer().as_entire_binding(),
+                },
+                point_light_entries[0].clone(),
+                point_light_entries[1].clone(),
+                point_light_entries[2].clone(),
+            ],
         });
 
-        let clear_color = wgpu::Color {
-            r: 0.1,
-            g: 0.2,
-            b: 0.3,
-            a: 1.0,
-        };
+        let clear_color = time_of_day.sky().clear_color();
+        let sky_pass = SkyPass::new(&device, HDR_FORMAT, sample_count);
         let aspect = sc_desc.width as f32 / sc_desc.height as f32;
         let mut camera = Camera::new(aspect);
 
-        let offset = 8f32;
-        let instances = (0..NUM_INSTANCES_PER_ROW)
-            .flat_map(|z| {
-                (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                    let position = cgmath::Vector3::new(x as f32 * offset, 0f32, z as f32 * offset)
-                        - INSTANCE_DISPLACEMENT;
-                    let rotation = if position.is_zero() {
-                        cgmath::Quaternion::from_axis_angle(
-                            cgmath::Vector3::unit_z(),
-                            cgmath::Deg(0.0),
-                        )
-                    } else {
-                        cgmath::Quaternion::from_axis_angle(
-                            position.clone().normalize(),
-                            cgmath::Deg(45.0),
-                        )
-                    };
-                    //let rotation = cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0));
-                    VertexInstance { position, rotation }
-                })
-            })
-            .collect::<Vec<_>>();
-        use cgmath::Rotation3;
-
-        let instance_data = instances
-            .iter()
-            .map(VertexInstance::to_raw)
-            .collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("instance_buffer"),
-            contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsage::VERTEX,
-        });
-
         camera.update_uniform();
         let camera_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("uniform buffer"),
@@ -243,7 +270,12 @@ impl State {
         use std::borrow::Cow;
         let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
             label: Some("shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!(
+                "{}\n{}\n{}",
+                fog::shader_source(2),
+                point_lights::shader_source(2),
+                include_str!("shader.wgsl")
+            ))),
             flags: shader_flags,
         });
 
@@ -258,20 +290,29 @@ impl State {
                 push_constant_ranges: &[],
             });
 
-        println!("creating pipeline");
-        let render_pipeline = create_render_pipeline(
+        log::debug!("creating model pipeline");
+        let render_pipeline = create_render_pipeline_with_options(
             &device,
             &render_pipeline_layout,
-            sc_desc.format,
+            HDR_FORMAT,
             Some(texture::Texture::DEPTH_FORMAT),
             &[model::ModelVertex::desc(), VertexInstanceRaw::desc()],
             shader_module,
             "render_pipeline",
+            PipelineOptions {
+                sample_count,
+                ambient_target: AmbientTarget::Write,
+                ..PipelineOptions::default()
+            },
         );
 
         let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
             label: Some("light-shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("light.wgsl"))),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!(
+                "{}\n{}",
+                fog::shader_source(1),
+                include_str!("light.wgsl")
+            ))),
             flags: shader_flags,
         });
 
@@ -281,45 +322,161 @@ impl State {
                 bind_group_layouts: &[&camera_bind_group_layout, &light_bind_group_layout],
                 push_constant_ranges: &[],
             });
-            create_render_pipeline(
+            create_render_pipeline_with_options(
                 &device,
                 &layout,
-                sc_desc.format,
+                HDR_FORMAT,
                 Some(texture::Texture::DEPTH_FORMAT),
                 &[model::ModelVertex::desc()],
                 shader_module,
                 "light_render_pipeline",
+                PipelineOptions {
+                    sample_count,
+                    ambient_target: AmbientTarget::Keep,
+                    ..PipelineOptions::default()
+                },
             )
         };
 
         let camera_controller = CameraController::new(10.2, 1.0);
-        let depth_pass = DepthPass::new(&device, &sc_desc);
+        let depth_pass = DepthPass::new(&device, &sc_desc, sample_count);
+        let hdr = HdrPipeline::new(&device, &sc_desc, sample_count, HdrSettings::default());
+        let ssao = Ssao::new(
+            &device,
+            &sc_desc,
+            &depth_pass.texture.view,
+            HDR_FORMAT,
+            sample_count,
+            SsaoSettings::default(),
+        );
+        let overlay_renderer = OverlayRenderer::new(&device, &queue, &sc_desc);
 
-        let obj_model = model::Model::load(
+        let mut props = PropSystem::new();
+        let turkey = props.add_model(
+            model::Model::load(
+                &device,
+                &queue,
+                &texture_bind_group_layout,
+                std::path::Path::new("res/turkey.obj"),
+            )
+            .context("loading the turkey model")?,
+        );
+        props.add_scatter_rule(ScatterRule {
+            model: turkey,
+            surface: BlockType::Grass,
+            chance: 0.002,
+            scale: 0.2..0.35,
+            seed: 1,
+        })?;
+
+        let block_textures_bind_group_layout = BlockTextureArray::create_bind_group_layout(&device);
+        let block_textures = BlockTextureArray::load(
             &device,
             &queue,
-            &texture_bind_group_layout,
-            std::path::Path::new("res/turkey.obj"),
+            &block_textures_bind_group_layout,
+            BLOCK_TEXTURE_DIR,
+            &TextureOptions {
+                sampler: SamplerOptions {
+                    address_mode: wgpu::AddressMode::Repeat,
+                    // keep the blocky look up close
+                    mag_filter: wgpu::FilterMode::Nearest,
+                    ..SamplerOptions::trilinear(16)
+                },
+                ..TextureOptions::mipmapped()
+            },
         )
-        .unwrap();
+        .context("loading the block textures")?;
 
-        let voxel_render_pipeline =
-            create_voxel_pipeline(&device, sc_desc.format, &light_bind_group_layout);
+        let shadow_bind_group_layout = ShadowMaps::create_bind_group_layout(&device);
+        let shadow_cascade_bind_group_layout =
+            ShadowMaps::create_cascade_bind_group_layout(&device);
+        let shadow_maps = ShadowMaps::new(
+            &device,
+            &shadow_bind_group_layout,
+            &shadow_cascade_bind_group_layout,
+            ShadowSettings::default(),
+        );
+        let voxel_shadow_pipeline =
+            create_voxel_shadow_pipeline(&device, &shadow_cascade_bind_group_layout);
 
-        let mut gpu_resources = GpuResources::new();
+        let voxel_render_pipeline = create_voxel_pipeline(
+            &device,
+            HDR_FORMAT,
+            &light_bind_group_layout,
+            &block_textures_bind_group_layout,
+            &shadow_bind_group_layout,
+            PipelineOptions {
+                sample_count,
+                ambient_target: AmbientTarget::Write,
+                ..PipelineOptions::default()
+            },
+        );
+        let voxel_translucent_pipeline = create_voxel_pipeline(
+            &device,
+            HDR_FORMAT,
+            &light_bind_group_layout,
+            &block_textures_bind_group_layout,
+            &shadow_bind_group_layout,
+            PipelineOptions {
+                sample_count,
+                ambient_target: AmbientTarget::Keep,
+                ..PipelineOptions::translucent()
+            },
+        );
+        let debug_views = DebugViews::new(&capabilities, |options| {
+            create_voxel_pipeline(
+                &device,
+                HDR_FORMAT,
+                &light_bind_group_layout,
+                &block_textures_bind_group_layout,
+                &shadow_bind_group_layout,
+                PipelineOptions {
+                    sample_count,
+                    ambient_target: AmbientTarget::Keep,
+                    ..options
+                },
+            )
+        });
+        let debug_lines = DebugLines::new(&device, HDR_FORMAT, sample_count);
+
+        let mut gpu_resources = GpuResources::new(&device, &capabilities);
 
         let mut chunks = Chunks::new();
+        chunks.block_layers = block_textures.layers;
+        let world_settings = args.world_settings()?;
+        log::info!("world: {:?}", world_settings);
+        chunks.generator = world_settings.generator();
+        if let Some(render_distance) = args.render_distance {
+            chunks.set_render_distance(render_distance);
+        }
+        chunks.mesh_cache = match args.mesh_cache_dir() {
+            Some(dir) => Some(MeshCache::new(dir)?),
+            None => MeshCache::from_env()?,
+        };
+        if let Some(position) = args
+            .start_position
+            .or_else(|| find_spawn(&chunks.generator, cgmath::Vector3::new(0, 0, 0)))
+        {
+            log::info!("starting at {:?}", position);
+            camera.position = (position.x, position.y, position.z).into();
+        } else {
+            log::warn!(
+                "no open ground near the origin, starting at {:?}",
+                camera.position
+            );
+        }
+        chunks.position = (camera.position.x, camera.position.y, camera.position.z).into();
+        chunks.direction = camera.direction();
         // find what chunks needs to be loaded
-        chunks.update_load_data_queue();
-        chunks.update_load_mesh_queue();
+        chunks.update_streaming();
 
         // load voxel data in chunks
         chunks.build_chunk_data_in_queue();
 
         // load meshes based on voxel data in chunk
-        chunks.build_chunk_meshes_in_queue(&device, &mut gpu_resources);
+        chunks.build_chunk_meshes_in_queue(&device, &queue, &mut gpu_resources);
 
-        Self {
+        Ok(Self {
             gpu_resources,
             chunks,
             rotation: 0f32,
@@ -331,20 +488,36 @@ impl State {
             device,
             queue,
             depth_pass,
+            hdr,
+            ssao,
+            overlay_renderer,
+            debug_overlay: DebugOverlay::default(),
+            debug_views,
+            debug_lines,
             sc_desc,
             swap_chain,
             size,
             clear_color,
+            time_of_day,
+            sky_pass,
+            fog,
             render_pipeline,
-            instance_buffer,
-            obj_model,
+            props,
+            turkey,
             light_bind_group,
             light,
             light_buffer,
+            point_lights,
+            orbit_light,
+            spawned_lights: Vec::new(),
             light_render_pipeline,
             voxel_render_pipeline,
+            voxel_translucent_pipeline,
+            block_textures,
+            shadow_maps,
+            voxel_shadow_pipeline,
             mouse_pressed: false,
-        }
+        })
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -354,6 +527,10 @@ impl State {
         self.camera.aspect = new_size.width as f32 / new_size.height as f32;
         self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
         self.depth_pass.resize(&self.device, &self.sc_desc);
+        self.hdr.resize(&self.device, &self.sc_desc);
+        self.ssao
+            .resize(&self.device, &self.sc_desc, &self.depth_pass.texture.view);
+        self.overlay_renderer.resize(&self.queue, &self.sc_desc);
     }
 
     fn input(&mut self, event: &DeviceEvent) -> bool {
@@ -376,7 +553,113 @@ impl State {
                 virtual_keycode: Some(key),
                 state,
                 ..
-            }) => self.camera_controller.process_keyboard(*key, *state),
+            }) => {
+                self.process_time_keys(*key, *state)
+                    || self.process_light_keys(*key, *state)
+                    || self.process_post_keys(*key, *state)
+                    || self.process_debug_keys(*key, *state)
+                    || self.camera_controller.process_keyboard(*key, *state)
+            }
+            _ => false,
+        }
+    }
+
+    // L places a point light at the camera, K removes the last placed one
+    fn process_light_keys(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        if state != ElementState::Pressed {
+            return false;
+        }
+        match key {
+            VirtualKeyCode::L => {
+                let color =
+                    SPAWNED_LIGHT_COLORS[self.spawned_lights.len() % SPAWNED_LIGHT_COLORS.len()];
+                let id = self.point_lights.add(PointLight {
+                    position: self.chunks.position,
+                    color: color.into(),
+                    intensity: 20.0,
+                    radius: 16.0,
+                });
+                self.spawned_lights.push(id);
+                true
+            }
+            VirtualKeyCode::K => {
+                if let Some(id) = self.spawned_lights.pop() {
+                    self.point_lights.remove(id);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // B toggles bloom, M switches between the tone mappers, O cycles the ssao quality
+    fn process_post_keys(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        if state != ElementState::Pressed {
+            return false;
+        }
+        let settings = &mut self.hdr.settings;
+        match key {
+            VirtualKeyCode::B => {
+                settings.bloom = !settings.bloom;
+                true
+            }
+            VirtualKeyCode::M => {
+                settings.tonemapper = match settings.tonemapper {
+                    Tonemapper::Aces => Tonemapper::Reinhard,
+                    Tonemapper::Reinhard => Tonemapper::Aces,
+                };
+                true
+            }
+            VirtualKeyCode::O => {
+                self.ssao.settings.quality = self.ssao.settings.quality.next();
+                self.ssao
+                    .resize(&self.device, &self.sc_desc, &self.depth_pass.texture.view);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // F3 shows the statistics overlay, F4 cycles the debug views, F5 shows the chunk bounds
+    fn process_debug_keys(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        if state != ElementState::Pressed {
+            return false;
+        }
+        match key {
+            VirtualKeyCode::F3 => {
+                self.debug_overlay.toggle();
+                true
+            }
+            VirtualKeyCode::F4 => {
+                self.debug_views.cycle();
+                true
+            }
+            VirtualKeyCode::F5 => {
+                self.debug_views.chunk_bounds = !self.debug_views.chunk_bounds;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // T pauses the day, [ and ] scrub an hour back or forward
+    fn process_time_keys(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        if state != ElementState::Pressed {
+            return false;
+        }
+        match key {
+            VirtualKeyCode::T => {
+                self.time_of_day.paused = !self.time_of_day.paused;
+                true
+            }
+            VirtualKeyCode::LBracket => {
+                self.time_of_day.scrub(-SCRUB_STEP);
+                true
+            }
+            VirtualKeyCode::RBracket => {
+                self.time_of_day.scrub(SCRUB_STEP);
+                true
+            }
             _ => false,
         }
     }
@@ -384,15 +667,32 @@ impl State {
     fn update(&mut self, dt: std::time::Duration) {
         use cgmath::Rotation3;
         let old_position: cgmath::Vector3<_> = self.light.position.into();
+        let orbit_angle = cgmath::Deg(LIGHT_ORBIT_SPEED * dt.as_secs_f32());
         self.light.position =
-            (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(1.0))
+            (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), orbit_angle)
                 * old_position)
                 .into();
+        self.point_lights
+            .set_position(self.orbit_light, self.light.position.into());
+
+        self.time_of_day.update(dt);
+        let sky = self.time_of_day.sky();
+        self.light.apply_sky(&sky);
+        self.clear_color = sky.clear_color();
+        self.fog.update(
+            &self.queue,
+            sky.horizon_color.into(),
+            self.chunks.render_distance(),
+        );
         self.queue
             .write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[self.light]));
+        self.hdr.update(&self.queue);
 
         self.camera_controller.update_camera(&mut self.camera, dt);
         self.camera.update_uniform();
+        self.sky_pass.update(&self.queue, &self.camera, &sky);
+        self.ssao.update(&self.queue, &self.camera);
+        self.depth_pass.update(&self.queue, &self.camera);
         self.rotation += 3f32;
         self.queue.write_buffer(
             &self.camera_uniform_buffer,
@@ -406,21 +706,49 @@ impl State {
             self.camera.position.z,
         )
             .into();
+        self.chunks.direction = self.camera.direction();
 
-        use rand::*;
-        if rand::thread_rng().gen_range(0..5) == 0 {
-            self.chunks.update_load_data_queue();
-            self.chunks.update_load_mesh_queue();
-
-            self.chunks.update_unload_mesh_queue();
-            self.chunks.update_unload_data_queue();
-        }
+        // only recomputes what to load when a chunk boundary was crossed
+        self.chunks.update_streaming();
         self.chunks
             .build_chunk_data_in_queue();
         self.chunks
-            .build_chunk_meshes_in_queue(&self.device, &mut self.gpu_resources);
+            .build_chunk_meshes_in_queue(&self.device, &self.queue, &mut self.gpu_resources);
         self.chunks.unload_data_queue();
-        self.chunks.unload_mesh_queue(&mut self.gpu_resources);
+        self.chunks
+            .unload_mesh_queue(&mut self.gpu_resources.chunk_buffers);
+
+        self.props.update_scatter(&self.chunks);
+        self.props.update_buffers(&self.device, &self.queue);
+
+        let frustum = Frustum::from_view_projection(self.camera.build_view_projection_matrix());
+        self.chunks
+            .prepare_draw(&self.device, &self.queue, &mut self.gpu_resources, &frustum);
+        self.point_lights
+            .update(&self.queue, self.chunks.position, &frustum);
+        self.debug_views
+            .build_chunk_bounds(&mut self.debug_lines, &self.chunks, &frustum);
+        self.debug_lines.prepare(&self.device, &self.queue);
+
+        self.shadow_maps
+            .update(&self.queue, &self.camera, self.light.sun_direction.into());
+        self.chunks.prepare_shadow_draw(
+            &self.device,
+            &self.queue,
+            &mut self.gpu_resources,
+            &self.shadow_maps.caster_frustums(),
+        );
+
+        self.debug_overlay.update(dt);
+        let stats = DebugStats {
+            camera_position: self.camera.position,
+            chunk_position: self.chunks.current_chunk_pos(),
+            chunks: self.chunks.stats(),
+            vertex_count: self.chunks.get_vertex_count(),
+            chunk_memory: self.gpu_resources.chunk_buffers.memory_usage(),
+            debug_view: self.debug_views.view.name(),
+        };
+        self.debug_overlay.build(&mut self.overlay_renderer, &stats);
     }
 
     fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
@@ -431,17 +759,34 @@ impl State {
                 label: Some("Render encoder"),
             });
 
+        self.shadow_maps.render(
+            &mut encoder,
+            &self.voxel_shadow_pipeline,
+            &self.chunks,
+            &self.gpu_resources,
+        );
+
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("main render pass"),
-            color_attachments: &[wgpu::RenderPassColorAttachment {
-                //attachment: &frame.view,
-                view: &frame.view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(self.clear_color),
-                    store: true,
+            color_attachments: &[
+                wgpu::RenderPassColorAttachment {
+                    view: self.hdr.scene_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color),
+                        store: true,
+                    },
                 },
-            }],
+                // resolved here already, ssao reads it before the translucent pass
+                wgpu::RenderPassColorAttachment {
+                    view: self.ssao.ambient_view(),
+                    resolve_target: self.ssao.ambient_resolve_target(),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                },
+            ],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: &self.depth_pass.texture.view,
                 depth_ops: Some(wgpu::Operations {
@@ -452,40 +797,125 @@ impl State {
             }),
         });
 
-        render_pass.set_pipeline(&self.voxel_render_pipeline);
+        self.sky_pass.draw(&mut render_pass);
 
-        let _ = self.chunks.draw(
+        let debug_pipeline = self.debug_views.voxel_pipeline();
+        render_pass.set_pipeline(debug_pipeline.unwrap_or(&self.voxel_render_pipeline));
+
+        self.chunks.draw(
             &mut render_pass,
             &self.camera_bind_group,
             &self.light_bind_group,
+            &self.block_textures.bind_group,
+            &self.shadow_maps.bind_group,
             &self.gpu_resources,
         );
 
-        let pipeline = &self.render_pipeline;
-        use crate::model::DrawLight;
+        render_pass.set_pipeline(&self.render_pipeline);
+        self.props.draw(
+            &mut render_pass,
+            &self.camera_bind_group,
+            &self.light_bind_group,
+        );
+
+        use model::DrawLight;
         render_pass.set_pipeline(&self.light_render_pipeline);
-        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
         render_pass.draw_light_model(
-            &self.obj_model,
+            self.props.model(self.turkey),
             &self.camera_bind_group,
             &self.light_bind_group,
         );
+        drop(render_pass);
 
-        render_pass.set_pipeline(pipeline);
+        // the occlusion reads the depth of the opaque geometry, so it can't be attached
+        if self.debug_views.is_shaded() {
+            self.ssao.render(&mut encoder, self.hdr.scene_view());
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("translucent render pass"),
+            color_attachments: &[
+                wgpu::RenderPassColorAttachment {
+                    view: self.hdr.scene_view(),
+                    // the last pass drawing the scene
+                    resolve_target: self.hdr.scene_resolve_target(),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                },
+                // only attached so the scene pipelines fit both passes, nothing writes it
+                wgpu::RenderPassColorAttachment {
+                    view: self.ssao.ambient_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: false,
+                    },
+                },
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_pass.texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        // debug views draw translucent blocks opaque
+        render_pass.set_pipeline(debug_pipeline.unwrap_or(&self.voxel_translucent_pipeline));
+        self.chunks.draw_translucent(
+            &mut render_pass,
+            &self.camera_bind_group,
+            &self.light_bind_group,
+            &self.block_textures.bind_group,
+            &self.shadow_maps.bind_group,
+            &self.gpu_resources,
+        );
+        self.debug_lines
+            .draw(&mut render_pass, &self.camera_bind_group);
 
         // encoder.finish needs ownership of encoder, render_pass is not needed any more and holds a ref, so drop it
         drop(render_pass);
-        self.depth_pass.render(&frame, &mut encoder);
+        self.hdr.render(&mut encoder, &frame.view);
+        self.depth_pass.render(
+            &frame,
+            &mut encoder,
+            self.debug_views.view == DebugView::Depth,
+        );
+        self.overlay_renderer
+            .render(&self.device, &self.queue, &mut encoder, &frame.view);
         self.queue.submit(std::iter::once(encoder.finish()));
         Ok(())
     }
 }
 
 fn main() {
-    env_logger::init();
+    // RUST_LOG overrides this, e.g. RUST_LOG=teal_mountain=debug,timing=trace
+    env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or("warn,teal_mountain=info,timing=info"),
+    )
+    .init();
+    let args = match Args::from_env() {
+        Ok(args) => args,
+        Err(e) => {
+            log::error!("{:?}", e);
+            std::process::exit(1);
+        }
+    };
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
-    let mut state = block_on(State::new(&window));
+    let mut state = match AdapterConfig::from_env()
+        .and_then(|adapter_config| block_on(State::new(&window, &adapter_config, &args)))
+    {
+        Ok(state) => state,
+        Err(e) => {
+            log::error!("{:?}", e);
+            std::process::exit(1);
+        }
+    };
 
     let mut last_render_time = std::time::Instant::now();
     event_loop.run(move |event, _, control_flow| match event {
@@ -500,12 +930,18 @@ fn main() {
                 Err(wgpu::SwapChainError::Lost) => state.resize(state.size),
                 Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
                 // all other errors (Outdated, Timeout) should be resolved by the next frame
-                Err(e) => println!("{:?}", e),
+                Err(e) => log::warn!("swap chain error: {:?}", e),
             }
         }
         Event::DeviceEvent { ref event, .. } => {
             state.input(event);
         }
+        Event::LoopDestroyed => {
+            state.chunks.timings.log_summary();
+            if let Some(mesh_cache) = &state.chunks.mesh_cache {
+                mesh_cache.log_summary();
+            }
+        }
         Event::MainEventsCleared => {
             // all events have been handled
             window.request_redraw();
@@ -6,7 +6,7 @@ use wgpu::util::DeviceExt;
 use crate::{
     color::{self, Color},
     rendering::vertex_desc::VertexDesc,
-    texture::Texture,
+    texture::{Texture, TextureOptions},
 };
 pub trait DrawModel<'a, 'b>
 where
@@ -98,10 +98,10 @@ where
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ModelVertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2],
-    normal: [f32; 3],
-    color_diffuse: [f32; 3],
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    pub color_diffuse: [f32; 3],
 }
 
 impl VertexDesc for ModelVertex {
@@ -147,6 +147,54 @@ pub struct Material {
     pub bind_group: wgpu::BindGroup,
 }
 
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        name: &str,
+        diffuse_texture: Texture,
+        diffuse_color: Color,
+    ) -> Self {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(name),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                },
+            ],
+        });
+        Self {
+            name: name.to_string(),
+            diffuse_texture,
+            diffuse_color,
+            bind_group,
+        }
+    }
+
+    // untextured, for meshes without a material
+    pub fn white(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Result<Self> {
+        use color::colors::*;
+        let texture = Texture::from_color(device, queue, WHITE, 128, 128)?;
+        Ok(Self::new(
+            device,
+            layout,
+            "default_material",
+            texture,
+            WHITE,
+        ))
+    }
+}
+
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
@@ -155,17 +203,64 @@ pub struct Mesh {
     pub material_id: usize,
 }
 
+impl Mesh {
+    pub fn new(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[ModelVertex],
+        indices: &[u32],
+        material_id: usize,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} vertex_buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} index_buffer", name)),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsage::INDEX,
+        });
+        Self {
+            name: name.to_string(),
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            material_id,
+        }
+    }
+}
+
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
 }
 
 impl Model {
+    // the loader is picked from the file extension
     pub fn load<P: AsRef<Path>>(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         layout: &wgpu::BindGroupLayout,
         path: P,
+    ) -> Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("obj") => Self::load_obj(device, queue, layout, path),
+            Some("gltf") | Some("glb") => Self::load_gltf(device, queue, layout, path),
+            _ => bail!("unsupported model format: {:?}", path.as_ref()),
+        }
+    }
+
+    pub fn load_obj<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        path: P,
     ) -> Result<Self> {
         let (obj_models, obj_materials) = tobj::load_obj(
             path.as_ref(),
@@ -181,86 +276,128 @@ impl Model {
         let containing_folder = path.as_ref().parent().context("Directory has no parent")?;
 
         let mut materials = Vec::new();
+        // textured materials need texture coordinates
+        let mut textured = Vec::new();
         for mat in obj_materials {
-            println!("mat data: {:?}", mat);
+            log::debug!("mat data: {:?}", mat);
             let diffuse_path = mat.diffuse_texture;
             // no diffuse texture
-            let diffuse_texture = if diffuse_path.len() != 0 {
-                Texture::load(device, queue, containing_folder.join(diffuse_path.clone())).unwrap()
+            let diffuse_texture = if !diffuse_path.is_empty() {
+                let texture_path = containing_folder.join(&diffuse_path);
+                Texture::load(device, queue, &texture_path, &TextureOptions::mipmapped())
+                    .with_context(|| format!("loading texture {:?}", texture_path))?
             } else {
                 use color::colors::*;
-                Texture::from_color(device, queue, WHITE, 128, 128).unwrap()
+                Texture::from_color(device, queue, WHITE, 128, 128)?
             };
-            //} else {
-            //    None
-            //};
-            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some(&diffuse_path),
-                layout: layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                    },
-                ],
-            });
             let diffuse_color = Color::from(mat.diffuse);
-            materials.push(Material {
-                name: mat.name,
-                diffuse_texture: diffuse_texture,
-                bind_group,
-                diffuse_color: diffuse_color,
-            });
+            materials.push(Material::new(
+                device,
+                layout,
+                &mat.name,
+                diffuse_texture,
+                diffuse_color,
+            ));
+            textured.push(!diffuse_path.is_empty());
         }
 
         let mut meshes = Vec::new();
         for m in obj_models {
-            let mut vertices = Vec::new();
-            println!("mesh indices: {:?}", m.mesh.indices.len());
-            println!("mesh postiions: {:?}", m.mesh.positions.len());
-            println!("mesh normals: {:?}", m.mesh.normals.len());
-            for i in 0..m.mesh.positions.len() / 3 {
-                vertices.push(ModelVertex {
-                    position: [
-                        m.mesh.positions[i * 3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ],
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ],
-                    color_diffuse: materials[m.mesh.material_id.unwrap()].diffuse_color.into(),
-                });
+            log::debug!(
+                "mesh {}: {} indices, {} positions, {} normals",
+                m.name,
+                m.mesh.indices.len(),
+                m.mesh.positions.len(),
+                m.mesh.normals.len()
+            );
+            let material_id = match m.mesh.material_id {
+                Some(material_id) if material_id < materials.len() => material_id,
+                _ => {
+                    materials.push(Material::white(device, queue, layout)?);
+                    textured.push(false);
+                    materials.len() - 1
+                }
+            };
+
+            let vertex_count = m.mesh.positions.len() / 3;
+            let positions = m
+                .mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| [p[0], p[1], p[2]])
+                .collect::<Vec<_>>();
+            let normals = if m.mesh.normals.len() == vertex_count * 3 {
+                m.mesh
+                    .normals
+                    .chunks_exact(3)
+                    .map(|n| [n[0], n[1], n[2]])
+                    .collect::<Vec<_>>()
+            } else {
+                log::debug!("generating normals for mesh: {}", m.name);
+                generate_normals(&positions, &m.mesh.indices)
+            };
+            let has_tex_coords = m.mesh.texcoords.len() == vertex_count * 2;
+            if !has_tex_coords && textured[material_id] {
+                bail!("textured mesh {} has no texture coordinates", m.name);
             }
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} vertex_buffer", path.as_ref())),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} index_buffer", path.as_ref())),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
-                usage: wgpu::BufferUsage::INDEX,
-            });
-            meshes.push(Mesh {
-                name: m.name,
-                vertex_buffer,
-                index_buffer,
-                num_indices: m.mesh.indices.len() as u32,
-                material_id: m.mesh.material_id.unwrap_or(0),
-            });
+
+            let color_diffuse = materials[material_id].diffuse_color.into();
+            let vertices = (0..vertex_count)
+                .map(|i| ModelVertex {
+                    position: positions[i],
+                    tex_coords: if has_tex_coords {
+                        [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]]
+                    } else {
+                        [0f32, 0f32]
+                    },
+                    normal: normals[i],
+                    color_diffuse,
+                })
+                .collect::<Vec<_>>();
+            meshes.push(Mesh::new(
+                device,
+                &m.name,
+                &vertices,
+                &m.mesh.indices,
+                material_id,
+            ));
         }
         Ok(Self { meshes, materials })
     }
 }
 
+// smooth per vertex normals, every triangle contributes weighted by its area
+pub fn generate_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    use cgmath::{InnerSpace, Vector3, Zero};
+    let mut normals = vec![Vector3::<f32>::zero(); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let corners = [
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        ];
+        if corners.iter().any(|corner| *corner >= positions.len()) {
+            continue;
+        }
+        let [a, b, c] = corners.map(|corner| Vector3::from(positions[corner]));
+        // not normalized, its length is twice the triangle area
+        let face_normal = (b - a).cross(c - a);
+        for corner in corners.iter() {
+            normals[*corner] += face_normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| {
+            if normal.magnitude2() > 0f32 {
+                normal.normalize().into()
+            } else {
+                [0f32, 1f32, 0f32]
+            }
+        })
+        .collect()
+}
+
 pub trait DrawLight<'a, 'b>
 where
     'b: 'a,
@@ -0,0 +1,481 @@
+use cgmath::{InnerSpace, Vector3};
+use std::collections::HashMap;
+
+use crate::{rendering::frustum::Frustum, voxel_tools::chunk::SIZE};
+
+// the light buffers have a fixed size so the light bind group never has to be rebuilt
+pub const MAX_POINT_LIGHTS: usize = 256;
+// light references over all clusters, lights beyond this are dropped from their clusters
+pub const MAX_LIGHT_INDICES: usize = 16384;
+// world units, one chunk per cluster
+pub const CLUSTER_SIZE: f32 = SIZE as f32;
+// clusters along x, y and z, centered on the camera
+pub const CLUSTER_DIMS: [u32; 3] = [16, 8, 16];
+pub const CLUSTER_COUNT: usize = (CLUSTER_DIMS[0] * CLUSTER_DIMS[1] * CLUSTER_DIMS[2]) as usize;
+
+// the point light structs and bindings with point_lighting, prepended to lit shaders
+// the lights are bound next to the light uniform, light_group is the index of that bind group
+pub fn shader_source(light_group: u32) -> String {
+    include_str!("point_lights.wgsl").replace("LIGHT_GROUP", &light_group.to_string())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PointLightId(u64);
+
+#[derive(Copy, Clone, Debug)]
+pub struct PointLight {
+    pub position: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    // no light reaches further than this, the falloff is smoothed to 0 at the radius
+    pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointLightRaw {
+    position: [f32; 3],
+    radius: f32,
+    color: [f32; 3],
+    intensity: f32,
+}
+
+impl PointLight {
+    pub fn to_raw(self) -> PointLightRaw {
+        PointLightRaw {
+            position: self.position.into(),
+            radius: self.radius,
+            color: self.color.into(),
+            intensity: self.intensity,
+        }
+    }
+}
+
+// header of the cluster storage buffer, followed by one [first index, count] per cluster
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ClusterHeader {
+    // xyz: world position of the first cluster's min corner, w: cluster size
+    origin: [f32; 4],
+    dims: [u32; 4],
+}
+
+// axis aligned grid of clusters around a center, snapped to whole clusters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClusterGrid {
+    pub origin: Vector3<f32>,
+    pub cell_size: f32,
+    pub dims: [u32; 3],
+}
+
+impl ClusterGrid {
+    pub fn around(center: Vector3<f32>, cell_size: f32, dims: [u32; 3]) -> Self {
+        let snap =
+            |value: f32, dim: u32| ((value / cell_size).floor() - (dim / 2) as f32) * cell_size;
+        Self {
+            origin: Vector3::new(
+                snap(center.x, dims[0]),
+                snap(center.y, dims[1]),
+                snap(center.z, dims[2]),
+            ),
+            cell_size,
+            dims,
+        }
+    }
+
+    pub fn cell_count(&self) -> usize {
+        (self.dims[0] * self.dims[1] * self.dims[2]) as usize
+    }
+
+    // x changes fastest, like the lookup in voxel.wgsl
+    pub fn cell_index(&self, x: u32, y: u32, z: u32) -> usize {
+        (x + self.dims[0] * (y + self.dims[1] * z)) as usize
+    }
+
+    // clamped to the grid, None if the box is completely outside
+    fn cell_range(&self, min: Vector3<f32>, max: Vector3<f32>) -> Option<([u32; 3], [u32; 3])> {
+        let mut first = [0u32; 3];
+        let mut last = [0u32; 3];
+        for axis in 0..3 {
+            let low = ((min[axis] - self.origin[axis]) / self.cell_size).floor();
+            let high = ((max[axis] - self.origin[axis]) / self.cell_size).floor();
+            if high < 0f32 || low >= self.dims[axis] as f32 {
+                return None;
+            }
+            first[axis] = low.max(0f32) as u32;
+            last[axis] = (high as u32).min(self.dims[axis] - 1);
+        }
+        Some((first, last))
+    }
+
+    fn cell_min(&self, x: u32, y: u32, z: u32) -> Vector3<f32> {
+        self.origin + Vector3::new(x as f32, y as f32, z as f32) * self.cell_size
+    }
+}
+
+// lights touching every cluster, stored as one flat index list
+#[derive(Clone, Debug, Default)]
+pub struct LightClusters {
+    // [first index, count] per cluster
+    pub cells: Vec<[u32; 2]>,
+    pub indices: Vec<u32>,
+    // references that didn't fit in max_indices
+    pub dropped: usize,
+}
+
+// assigns every light to the clusters its sphere overlaps
+// lights outside of the frustum can't light anything visible and are skipped
+pub fn cluster_lights(
+    grid: &ClusterGrid,
+    lights: &[PointLightRaw],
+    frustum: Option<&Frustum>,
+    max_indices: usize,
+) -> LightClusters {
+    // (cluster, light) pairs, sorted by cluster to build the flat list
+    let mut pairs = Vec::new();
+    for (light_index, light) in lights.iter().enumerate() {
+        let center = Vector3::from(light.position);
+        let extent = Vector3::new(light.radius, light.radius, light.radius);
+        let (min, max) = (center - extent, center + extent);
+        if let Some(frustum) = frustum {
+            if !frustum.intersects_aabb(min, max) {
+                continue;
+            }
+        }
+        let (first, last) = match grid.cell_range(min, max) {
+            Some(range) => range,
+            None => continue,
+        };
+        for z in first[2]..=last[2] {
+            for y in first[1]..=last[1] {
+                for x in first[0]..=last[0] {
+                    // the corners of the sphere's box are often outside of the sphere
+                    let cell_min = grid.cell_min(x, y, z);
+                    let cell_max = cell_min + Vector3::new(1f32, 1f32, 1f32) * grid.cell_size;
+                    if distance_to_box(center, cell_min, cell_max) <= light.radius {
+                        pairs.push((grid.cell_index(x, y, z) as u32, light_index as u32));
+                    }
+                }
+            }
+        }
+    }
+    pairs.sort_unstable();
+
+    let mut clusters = LightClusters {
+        cells: vec![[0u32; 2]; grid.cell_count()],
+        indices: Vec::with_capacity(pairs.len().min(max_indices)),
+        dropped: pairs.len().saturating_sub(max_indices),
+    };
+    for (cell, light_index) in pairs.into_iter().take(max_indices) {
+        let range = &mut clusters.cells[cell as usize];
+        if range[1] == 0 {
+            range[0] = clusters.indices.len() as u32;
+        }
+        range[1] += 1;
+        clusters.indices.push(light_index);
+    }
+    clusters
+}
+
+fn distance_to_box(point: Vector3<f32>, min: Vector3<f32>, max: Vector3<f32>) -> f32 {
+    let closest = Vector3::new(
+        point.x.clamp(min.x, max.x),
+        point.y.clamp(min.y, max.y),
+        point.z.clamp(min.z, max.z),
+    );
+    (point - closest).magnitude()
+}
+
+// point lights in storage buffers, culled into clusters around the camera every frame
+pub struct PointLights {
+    lights: HashMap<PointLightId, PointLight>,
+    next_id: u64,
+    light_buffer: wgpu::Buffer,
+    cluster_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    // lights and references of the last update, for stats
+    visible_count: usize,
+    index_count: usize,
+    // references dropped by the last update, only warned about when it starts
+    dropped: usize,
+}
+
+impl PointLights {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let create_buffer = |label, size| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as u64,
+                usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        Self {
+            lights: HashMap::new(),
+            next_id: 0,
+            light_buffer: create_buffer(
+                "point_light_buffer",
+                MAX_POINT_LIGHTS * std::mem::size_of::<PointLightRaw>(),
+            ),
+            cluster_buffer: create_buffer(
+                "light_cluster_buffer",
+                std::mem::size_of::<ClusterHeader>()
+                    + CLUSTER_COUNT * std::mem::size_of::<[u32; 2]>(),
+            ),
+            index_buffer: create_buffer(
+                "light_index_buffer",
+                MAX_LIGHT_INDICES * std::mem::size_of::<u32>(),
+            ),
+            visible_count: 0,
+            index_count: 0,
+            dropped: 0,
+        }
+    }
+
+    // lights past MAX_POINT_LIGHTS are ignored when rendering
+    pub fn add(&mut self, light: PointLight) -> PointLightId {
+        let id = PointLightId(self.next_id);
+        self.next_id += 1;
+        self.lights.insert(id, light);
+        id
+    }
+
+    pub fn remove(&mut self, id: PointLightId) -> Option<PointLight> {
+        self.lights.remove(&id)
+    }
+
+    pub fn get(&self, id: PointLightId) -> Option<&PointLight> {
+        self.lights.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: PointLightId) -> Option<&mut PointLight> {
+        self.lights.get_mut(&id)
+    }
+
+    // returns false if the light doesn't exist
+    pub fn set_position(&mut self, id: PointLightId, position: Vector3<f32>) -> bool {
+        match self.lights.get_mut(&id) {
+            Some(light) => {
+                light.position = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    // lights that touched at least one visible cluster and the references to them
+    pub fn stats(&self) -> (usize, usize) {
+        (self.visible_count, self.index_count)
+    }
+
+    // the storage buffers at bindings 2, 3 and 4 of the light bind group
+    pub fn bind_group_entries(&self) -> [wgpu::BindGroupEntry<'_>; 3] {
+        [
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: self.light_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: self.cluster_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: self.index_buffer.as_entire_binding(),
+            },
+        ]
+    }
+
+    // culls the lights into the clusters around the camera and uploads everything
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera_position: Vector3<f32>,
+        frustum: &Frustum,
+    ) {
+        let mut ids = self.lights.keys().copied().collect::<Vec<_>>();
+        // oldest lights win when there are too many
+        ids.sort_by_key(|id| id.0);
+        let raw = ids
+            .iter()
+            .take(MAX_POINT_LIGHTS)
+            .map(|id| self.lights[id].to_raw())
+            .collect::<Vec<_>>();
+
+        let grid = ClusterGrid::around(camera_position, CLUSTER_SIZE, CLUSTER_DIMS);
+        let clusters = cluster_lights(&grid, &raw, Some(frustum), MAX_LIGHT_INDICES);
+        if clusters.dropped > 0 && self.dropped == 0 {
+            log::warn!("too many lights per cluster, dropped {}", clusters.dropped);
+        }
+        self.dropped = clusters.dropped;
+
+        let mut visible = clusters.indices.clone();
+        visible.sort_unstable();
+        visible.dedup();
+        self.visible_count = visible.len();
+        self.index_count = clusters.indices.len();
+
+        if !raw.is_empty() {
+            queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&raw));
+        }
+        let header = ClusterHeader {
+            origin: grid.origin.extend(grid.cell_size).into(),
+            dims: [grid.dims[0], grid.dims[1], grid.dims[2], 0],
+        };
+        queue.write_buffer(&self.cluster_buffer, 0, bytemuck::cast_slice(&[header]));
+        queue.write_buffer(
+            &self.cluster_buffer,
+            std::mem::size_of::<ClusterHeader>() as u64,
+            bytemuck::cast_slice(&clusters.cells),
+        );
+        if !clusters.indices.is_empty() {
+            queue.write_buffer(
+                &self.index_buffer,
+                0,
+                bytemuck::cast_slice(&clusters.indices),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::OPENGL_TO_WGPU_MATRIX;
+    use std::collections::HashSet;
+
+    // 4x4x4 clusters of 16 units around the origin, from -32 to 32
+    fn grid() -> ClusterGrid {
+        ClusterGrid::around(Vector3::new(0.0, 0.0, 0.0), 16.0, [4, 4, 4])
+    }
+
+    fn light(position: Vector3<f32>, radius: f32) -> PointLightRaw {
+        PointLight {
+            position,
+            color: Vector3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            radius,
+        }
+        .to_raw()
+    }
+
+    // the clusters (x, y, z) a light was assigned to
+    fn clusters_of(grid: &ClusterGrid, clusters: &LightClusters, light: u32) -> HashSet<[u32; 3]> {
+        let mut cells = HashSet::new();
+        for z in 0..grid.dims[2] {
+            for y in 0..grid.dims[1] {
+                for x in 0..grid.dims[0] {
+                    let [first, count] = clusters.cells[grid.cell_index(x, y, z)];
+                    let indices = &clusters.indices[first as usize..(first + count) as usize];
+                    if indices.contains(&light) {
+                        cells.insert([x, y, z]);
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn lights_land_in_the_clusters_their_radius_overlaps() {
+        let grid = grid();
+        assert_eq!(grid.origin, Vector3::new(-32.0, -32.0, -32.0));
+        let lights = [
+            // inside a single cluster
+            light(Vector3::new(8.0, 8.0, 8.0), 4.0),
+            // reaching over the boundary at x = 16
+            light(Vector3::new(14.0, 8.0, 8.0), 3.0),
+            // its box touches 27 clusters, its sphere only the center and the 6 faces
+            light(Vector3::new(8.0, 8.0, 8.0), 10.0),
+            // partially outside of the grid
+            light(Vector3::new(40.0, -24.0, -24.0), 10.0),
+            // completely outside of the grid
+            light(Vector3::new(100.0, 0.0, 0.0), 10.0),
+        ];
+        let clusters = cluster_lights(&grid, &lights, None, MAX_LIGHT_INDICES);
+        assert_eq!(clusters.dropped, 0);
+        assert_eq!(clusters.cells.len(), grid.cell_count());
+
+        let set = |cells: &[[u32; 3]]| cells.iter().copied().collect::<HashSet<_>>();
+        assert_eq!(clusters_of(&grid, &clusters, 0), set(&[[2, 2, 2]]));
+        assert_eq!(
+            clusters_of(&grid, &clusters, 1),
+            set(&[[2, 2, 2], [3, 2, 2]])
+        );
+        assert_eq!(
+            clusters_of(&grid, &clusters, 2),
+            set(&[
+                [2, 2, 2],
+                [1, 2, 2],
+                [3, 2, 2],
+                [2, 1, 2],
+                [2, 3, 2],
+                [2, 2, 1],
+                [2, 2, 3],
+            ])
+        );
+        assert_eq!(clusters_of(&grid, &clusters, 3), set(&[[3, 0, 0]]));
+        assert!(clusters_of(&grid, &clusters, 4).is_empty());
+        assert_eq!(clusters.indices.len(), 1 + 2 + 7 + 1);
+    }
+
+    #[test]
+    fn lights_outside_of_the_frustum_are_skipped() {
+        let grid = grid();
+        // at the origin looking down -z
+        let view = cgmath::Matrix4::look_to_rh(
+            cgmath::Point3::new(0.0, 0.0, 0.0),
+            -Vector3::unit_z(),
+            Vector3::unit_y(),
+        );
+        let projection =
+            OPENGL_TO_WGPU_MATRIX * cgmath::perspective(cgmath::Deg(60.0), 1.0, 0.1, 100.0);
+        let frustum = Frustum::from_view_projection(projection * view);
+        let lights = [
+            light(Vector3::new(0.0, 0.0, -20.0), 2.0),
+            // behind the camera
+            light(Vector3::new(0.0, 0.0, 20.0), 2.0),
+            // behind the camera, but reaching in front of it
+            light(Vector3::new(0.0, 0.0, 2.0), 4.0),
+        ];
+        let clusters = cluster_lights(&grid, &lights, Some(&frustum), MAX_LIGHT_INDICES);
+        assert!(!clusters_of(&grid, &clusters, 0).is_empty());
+        assert!(clusters_of(&grid, &clusters, 1).is_empty());
+        assert!(!clusters_of(&grid, &clusters, 2).is_empty());
+        // without a frustum every light is kept
+        let clusters = cluster_lights(&grid, &lights, None, MAX_LIGHT_INDICES);
+        assert!(!clusters_of(&grid, &clusters, 1).is_empty());
+    }
+
+    #[test]
+    fn references_beyond_the_limit_are_dropped() {
+        let grid = grid();
+        // 7 clusters each
+        let lights = [
+            light(Vector3::new(8.0, 8.0, 8.0), 10.0),
+            light(Vector3::new(8.0, 8.0, 8.0), 10.0),
+            light(Vector3::new(8.0, 8.0, 8.0), 10.0),
+        ];
+        let clusters = cluster_lights(&grid, &lights, None, 10);
+        assert_eq!(clusters.indices.len(), 10);
+        assert_eq!(clusters.dropped, 21 - 10);
+        // the cells still point at valid ranges of the shortened list
+        let total = clusters
+            .cells
+            .iter()
+            .map(|[first, count]| {
+                assert!((first + count) as usize <= clusters.indices.len());
+                *count as usize
+            })
+            .sum::<usize>();
+        assert_eq!(total, 10);
+    }
+}
@@ -0,0 +1,69 @@
+struct PointLight {
+    position: vec3<f32>;
+    radius: f32;
+    color: vec3<f32>;
+    intensity: f32;
+};
+
+[[block]]
+struct PointLightList {
+    lights: [[stride(32)]] array<PointLight>;
+};
+
+[[block]]
+struct LightClusters {
+    // xyz: world position of the first cluster, w: cluster size
+    origin: vec4<f32>;
+    dims: vec4<u32>;
+    // x: first index, y: light count
+    cells: [[stride(8)]] array<vec2<u32>>;
+};
+
+[[block]]
+struct LightIndices {
+    indices: [[stride(4)]] array<u32>;
+};
+
+// bindings 2, 3 and 4 of the light bind group, the group is filled in by
+// point_lights::shader_source
+[[group(LIGHT_GROUP), binding(2)]]
+var<storage> s_point_lights: [[access(read)]] PointLightList;
+[[group(LIGHT_GROUP), binding(3)]]
+var<storage> s_clusters: [[access(read)]] LightClusters;
+[[group(LIGHT_GROUP), binding(4)]]
+var<storage> s_light_indices: [[access(read)]] LightIndices;
+
+// diffuse and specular of the point lights in the cluster of the position
+fn point_lighting(position: vec3<f32>, normal: vec3<f32>, view_dir: vec3<f32>) -> vec3<f32> {
+    let cell = vec3<i32>(floor((position - s_clusters.origin.xyz) / s_clusters.origin.w));
+    let dims = vec3<i32>(s_clusters.dims.xyz);
+    if (any(cell < vec3<i32>(0)) || any(cell >= dims)) {
+        return vec3<f32>(0.0);
+    }
+    let range = s_clusters.cells[cell.x + dims.x * (cell.y + dims.y * cell.z)];
+    var result: vec3<f32> = vec3<f32>(0.0);
+    var i: u32 = 0u;
+    loop {
+        if (i >= range.y) {
+            break;
+        }
+        // nested brackets would lex as the end of an attribute
+        let light_index = s_light_indices.indices[range.x + i];
+        let light = s_point_lights.lights[light_index];
+        let to_light = light.position - position;
+        let light_distance = length(to_light);
+        let light_dir = to_light / max(light_distance, 0.0001);
+        // inverse square, smoothly windowed to reach 0 at the radius
+        let ratio = light_distance / light.radius;
+        let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+        let attenuation = window * window / (light_distance * light_distance + 1.0);
+        let diffuse = max(dot(normal, light_dir), 0.0);
+        let half_dir = normalize(view_dir + light_dir);
+        let specular = pow(max(dot(normal, half_dir), 0.0), 32.0);
+        result = result + light.color * light.intensity * attenuation * (diffuse + specular);
+        continuing {
+            i = i + 1u;
+        }
+    }
+    return result;
+}
@@ -0,0 +1,452 @@
+use anyhow::{bail, Result};
+use cgmath::{Quaternion, Rotation3, Vector3};
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    model::{DrawModel, Model},
+    rendering::vertex_instance::{VertexInstance, VertexInstanceRaw},
+    voxel_tools::{
+        block::BlockType,
+        chunk::{Chunk, LocalCoordinate, SIZE},
+        chunks::Chunks,
+    },
+};
+
+// index into PropSystem::models
+pub type ModelId = usize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PropId(u64);
+
+// a model placed in the world
+#[derive(Copy, Clone, Debug)]
+pub struct Prop {
+    pub model: ModelId,
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: f32,
+}
+
+impl Prop {
+    fn instance(&self) -> VertexInstance {
+        VertexInstance {
+            position: self.position,
+            rotation: self.rotation,
+            scale: self.scale,
+        }
+    }
+}
+
+// places a model on top of exposed blocks of one type
+#[derive(Clone, Debug)]
+pub struct ScatterRule {
+    pub model: ModelId,
+    // the block the prop stands on, it needs air above it
+    pub surface: BlockType,
+    // chance for every matching surface voxel to get a prop
+    pub chance: f32,
+    pub scale: Range<f32>,
+    // different rules with the same chance pick different voxels
+    pub seed: u64,
+}
+
+// instances of one model, rebuilt when props change
+struct InstanceBatch {
+    buffer: Option<wgpu::Buffer>,
+    // in instances
+    capacity: u64,
+    count: u32,
+}
+
+pub struct PropSystem {
+    models: Vec<Model>,
+    batches: Vec<InstanceBatch>,
+    props: HashMap<PropId, Prop>,
+    next_id: u64,
+    // instance buffers are out of date
+    dirty: bool,
+    scatter_rules: Vec<ScatterRule>,
+    // props spawned by the scatter rules, removed again with their chunk
+    chunk_props: HashMap<Vector3<i32>, Vec<PropId>>,
+}
+
+impl Default for PropSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PropSystem {
+    pub fn new() -> Self {
+        Self {
+            models: Vec::new(),
+            batches: Vec::new(),
+            props: HashMap::new(),
+            next_id: 0,
+            dirty: false,
+            scatter_rules: Vec::new(),
+            chunk_props: HashMap::new(),
+        }
+    }
+
+    pub fn add_model(&mut self, model: Model) -> ModelId {
+        self.models.push(model);
+        self.batches.push(InstanceBatch {
+            buffer: None,
+            capacity: 0,
+            count: 0,
+        });
+        self.models.len() - 1
+    }
+
+    pub fn model(&self, model: ModelId) -> &Model {
+        &self.models[model]
+    }
+
+    pub fn spawn(&mut self, prop: Prop) -> PropId {
+        let id = PropId(self.next_id);
+        self.next_id += 1;
+        self.props.insert(id, prop);
+        self.dirty = true;
+        id
+    }
+
+    pub fn despawn(&mut self, id: PropId) -> Option<Prop> {
+        let prop = self.props.remove(&id);
+        self.dirty |= prop.is_some();
+        prop
+    }
+
+    // props are scaled by a value in the scale range, a zero scale can't be drawn
+    pub fn add_scatter_rule(&mut self, rule: ScatterRule) -> Result<()> {
+        let scale = &rule.scale;
+        if !(scale.start > 0f32 && scale.start.is_finite() && scale.end.is_finite())
+            || scale.end < scale.start
+        {
+            bail!(
+                "scatter rule scale has to be a positive range, found {:?}",
+                scale
+            );
+        }
+        self.scatter_rules.push(rule);
+        Ok(())
+    }
+
+    // marks the instance buffers out of date, so only ask for it when moving props
+    pub fn get_mut(&mut self, id: PropId) -> Option<&mut Prop> {
+        let prop = self.props.get_mut(&id);
+        self.dirty |= prop.is_some();
+        prop
+    }
+
+    pub fn prop_count(&self) -> usize {
+        self.props.len()
+    }
+
+    // scatters props on chunks that got a mesh and removes the props of unloaded ones
+    pub fn update_scatter(&mut self, chunks: &Chunks) {
+        let unloaded = self
+            .chunk_props
+            .keys()
+            .filter(|chunk_pos| !chunks.has_mesh(**chunk_pos))
+            .copied()
+            .collect::<Vec<_>>();
+        for chunk_pos in unloaded {
+            for id in self.chunk_props.remove(&chunk_pos).unwrap_or_default() {
+                self.despawn(id);
+            }
+        }
+
+        let loaded = chunks
+            .mesh_positions()
+            .filter(|chunk_pos| !self.chunk_props.contains_key(chunk_pos))
+            .copied()
+            .collect::<Vec<_>>();
+        for chunk_pos in loaded {
+            // waits for the chunk above, so the top layer always sees the same neighbours
+            let (chunk, above) = match (
+                chunks.get_chunk(chunk_pos),
+                chunks.get_chunk(chunk_pos + Vector3::unit_y()),
+            ) {
+                (Some(chunk), Some(above)) => (chunk, above),
+                _ => continue,
+            };
+            let props = scatter_chunk(&self.scatter_rules, chunk, above, chunk_pos);
+            let ids = props.into_iter().map(|prop| self.spawn(prop)).collect();
+            self.chunk_props.insert(chunk_pos, ids);
+        }
+    }
+
+    // copies the instances of every model to the gpu if any prop changed
+    pub fn update_buffers(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        let mut instances = vec![Vec::<VertexInstanceRaw>::new(); self.models.len()];
+        for prop in self.props.values() {
+            if let Some(model_instances) = instances.get_mut(prop.model) {
+                model_instances.push(prop.instance().to_raw());
+            }
+        }
+        for (batch, instances) in self.batches.iter_mut().zip(instances) {
+            batch.count = instances.len() as u32;
+            if instances.is_empty() {
+                continue;
+            }
+            let needed = instances.len() as u64;
+            if batch.buffer.is_none() || needed > batch.capacity {
+                batch.capacity = needed.next_power_of_two();
+                batch.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("prop_instance_buffer"),
+                    size: batch.capacity * std::mem::size_of::<VertexInstanceRaw>() as u64,
+                    usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                }));
+            }
+            if let Some(buffer) = &batch.buffer {
+                queue.write_buffer(buffer, 0, bytemuck::cast_slice(&instances));
+            }
+        }
+    }
+
+    // expects a pipeline taking ModelVertex and VertexInstanceRaw to be set
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    ) {
+        for (model, batch) in self.models.iter().zip(self.batches.iter()) {
+            let buffer = match &batch.buffer {
+                Some(buffer) if batch.count > 0 => buffer,
+                _ => continue,
+            };
+            render_pass.set_vertex_buffer(1, buffer.slice(..));
+            render_pass.draw_model_instanced(
+                model,
+                0..batch.count,
+                camera_bind_group,
+                light_bind_group,
+            );
+        }
+    }
+}
+
+// props for one chunk, the same chunk always gets the same props no matter
+// when or in which order chunks are loaded
+// surfaces at the top of the chunk need the chunk above to know if there is air
+pub fn scatter_chunk(
+    rules: &[ScatterRule],
+    chunk: &Chunk,
+    above: &Chunk,
+    chunk_pos: Vector3<i32>,
+) -> Vec<Prop> {
+    let mut props = Vec::new();
+    let chunk_world_pos = Chunks::chunk_to_world(chunk_pos);
+    let size = SIZE as i32;
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let block = match chunk.get_voxel(LocalCoordinate(x, y, z)) {
+                    Some(voxel) => voxel.block_type(),
+                    None => continue,
+                };
+                let above_block = if y + 1 < size {
+                    chunk.get_voxel(LocalCoordinate(x, y + 1, z))
+                } else {
+                    above.get_voxel(LocalCoordinate(x, 0, z))
+                }
+                .map(|voxel| voxel.block_type());
+                if above_block != Some(BlockType::Air) {
+                    continue;
+                }
+
+                let world = (
+                    chunk_pos.x * size + x,
+                    chunk_pos.y * size + y,
+                    chunk_pos.z * size + z,
+                );
+                for rule in rules.iter().filter(|rule| rule.surface == block) {
+                    let hash = hash_position(rule.seed, world);
+                    // chance, yaw and scale each read their own bits of the hash
+                    if unit_float(hash, 0, 24) >= rule.chance {
+                        continue;
+                    }
+                    let yaw = unit_float(hash, 24, 20) * std::f32::consts::PI * 2f32;
+                    let scale_t = unit_float(hash, 44, 20);
+                    let scale = rule.scale.start + (rule.scale.end - rule.scale.start) * scale_t;
+                    props.push(Prop {
+                        model: rule.model,
+                        // standing on the top face, voxels are centered on their position
+                        position: chunk_world_pos
+                            + Vector3::new(x as f32, y as f32 + 0.5f32, z as f32),
+                        rotation: Quaternion::from_angle_y(cgmath::Rad(yaw)),
+                        scale,
+                    });
+                }
+            }
+        }
+    }
+    props
+}
+
+// splitmix64 of the seed and world voxel position
+fn hash_position(seed: u64, (x, y, z): (i32, i32, i32)) -> u64 {
+    let mut hash = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^ (hash >> 31)
+}
+
+// 0..1 from `bits` bits of the hash starting at `shift`
+fn unit_float(hash: u64, shift: u32, bits: u32) -> f32 {
+    ((hash >> shift) & ((1u64 << bits) - 1)) as f32 / (1u64 << bits) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_tools::voxel::Voxel;
+
+    fn rule(scale: Range<f32>) -> ScatterRule {
+        ScatterRule {
+            model: 0,
+            surface: BlockType::Grass,
+            chance: 0.5,
+            scale,
+            seed: 0,
+        }
+    }
+
+    fn set(chunk: &mut Chunk, (x, y, z): (i32, i32, i32), block: BlockType) {
+        chunk.voxels[Chunk::get_index(LocalCoordinate(x, y, z))] = Voxel::new_block(block);
+    }
+
+    // a grass floor at the bottom of the chunk with air above
+    fn floor() -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0..SIZE as i32 {
+            for z in 0..SIZE as i32 {
+                set(&mut chunk, (x, 0, z), BlockType::Grass);
+            }
+        }
+        chunk
+    }
+
+    // comparable, props don't implement PartialEq
+    fn placements(props: &[Prop]) -> Vec<[f32; 8]> {
+        props
+            .iter()
+            .map(|prop| {
+                let rotation = prop.rotation;
+                [
+                    prop.position.x,
+                    prop.position.y,
+                    prop.position.z,
+                    rotation.s,
+                    rotation.v.x,
+                    rotation.v.y,
+                    rotation.v.z,
+                    prop.scale,
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scattering_is_the_same_for_the_same_chunk_and_seed() {
+        let (chunk, above) = (floor(), Chunk::new());
+        let rules = [rule(0.5..2.0)];
+        let chunk_pos = Vector3::new(3, -1, 7);
+        let props = scatter_chunk(&rules, &chunk, &above, chunk_pos);
+        assert!(!props.is_empty() && props.len() < SIZE * SIZE);
+        assert_eq!(
+            placements(&props),
+            placements(&scatter_chunk(&rules, &chunk, &above, chunk_pos))
+        );
+        for prop in props.iter() {
+            assert!((0.5..2.0).contains(&prop.scale));
+        }
+    }
+
+    #[test]
+    fn rule_seeds_pick_different_voxels() {
+        let (chunk, above) = (floor(), Chunk::new());
+        let chunk_pos = Vector3::new(0, 0, 0);
+        let seeded = |seed| {
+            let rules = [ScatterRule {
+                seed,
+                ..rule(1.0..1.0)
+            }];
+            placements(&scatter_chunk(&rules, &chunk, &above, chunk_pos))
+        };
+        assert_ne!(seeded(1), seeded(2));
+    }
+
+    #[test]
+    fn props_only_stand_on_the_surface_block_below_air() {
+        let mut chunk = floor();
+        let mut above = Chunk::new();
+        let top = SIZE as i32 - 1;
+        // covered by glass and by water
+        set(&mut chunk, (0, 1, 0), BlockType::Glass);
+        set(&mut chunk, (1, 1, 0), BlockType::Water);
+        // not the surface block of the rule
+        set(&mut chunk, (2, 0, 0), BlockType::Glass);
+        // grass at the top of the chunk, covered by the chunk above in one column
+        set(&mut chunk, (4, top, 4), BlockType::Grass);
+        set(&mut chunk, (5, top, 5), BlockType::Grass);
+        set(&mut above, (5, 0, 5), BlockType::Grass);
+
+        let rules = [ScatterRule {
+            chance: 1.0,
+            ..rule(1.0..1.0)
+        }];
+        let props = scatter_chunk(&rules, &chunk, &above, Vector3::new(0, 0, 0));
+        let positions = props.iter().map(|prop| prop.position).collect::<Vec<_>>();
+        // the floor minus three columns, plus the open top block
+        assert_eq!(positions.len(), SIZE * SIZE - 3 + 1);
+        for covered in [
+            (0.0, 0.5, 0.0),
+            (1.0, 0.5, 0.0),
+            (2.0, 0.5, 0.0),
+            (5.0, top as f32 + 0.5, 5.0),
+        ]
+        .iter()
+        {
+            assert!(!positions.contains(&Vector3::new(covered.0, covered.1, covered.2)));
+        }
+        assert!(positions.contains(&Vector3::new(4.0, top as f32 + 0.5, 4.0)));
+        assert!(positions.contains(&Vector3::new(3.0, 0.5, 0.0)));
+    }
+
+    #[test]
+    fn scatter_rules_need_a_positive_scale() {
+        let mut props = PropSystem::new();
+        for scale in [
+            0.0..1.0,
+            -1.0..0.5,
+            0.5..0.2,
+            0.5..f32::INFINITY,
+            f32::NAN..1.0,
+        ]
+        .iter()
+        {
+            assert!(
+                props.add_scatter_rule(rule(scale.clone())).is_err(),
+                "{:?}",
+                scale
+            );
+        }
+        assert!(props.scatter_rules.is_empty());
+        props.add_scatter_rule(rule(0.2..0.35)).unwrap();
+        // a single scale
+        props.add_scatter_rule(rule(1.0..1.0)).unwrap();
+        assert_eq!(props.scatter_rules.len(), 2);
+    }
+}
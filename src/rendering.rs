@@ -1,4 +1,21 @@
+pub mod adapter_selection;
+pub mod bitmap_font;
+pub mod capabilities;
+pub mod debug_lines;
+pub mod fog;
+pub mod frustum;
 pub mod gpu_resources;
+pub mod hdr;
+pub mod indirect_draw;
+pub mod mesh_buffer_pool;
+pub mod mipmaps;
+pub mod msaa;
+pub mod overlay;
+pub mod post_process;
+pub mod range_allocator;
 pub mod render_utils;
+pub mod shadows;
+pub mod sky;
+pub mod ssao;
 pub mod vertex_desc;
 pub mod vertex_instance;
@@ -0,0 +1,172 @@
+use anyhow::{anyhow, bail, Context, Result};
+
+// overrides the backends, e.g. WGPU_BACKEND=vulkan or WGPU_BACKEND=dx12,gl
+pub const BACKEND_ENV: &str = "WGPU_BACKEND";
+// WGPU_POWER_PREF=low prefers integrated gpus, high (the default) discrete ones
+pub const POWER_PREFERENCE_ENV: &str = "WGPU_POWER_PREF";
+// WGPU_ALLOW_SOFTWARE=0 fails instead of rendering on the cpu
+pub const ALLOW_SOFTWARE_ENV: &str = "WGPU_ALLOW_SOFTWARE";
+
+// which adapters may be picked and in which order
+#[derive(Clone, Debug)]
+pub struct AdapterConfig {
+    // tried in order, the first group with a usable hardware adapter wins
+    pub backends: Vec<wgpu::BackendBit>,
+    pub power_preference: wgpu::PowerPreference,
+    // a software adapter (llvmpipe, warp) is used when no hardware adapter works
+    pub allow_software: bool,
+}
+
+impl Default for AdapterConfig {
+    fn default() -> Self {
+        Self {
+            // vulkan, metal and dx12 first, gl and dx11 only if none of them work
+            backends: vec![wgpu::BackendBit::PRIMARY, wgpu::BackendBit::SECONDARY],
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            allow_software: true,
+        }
+    }
+}
+
+impl AdapterConfig {
+    // the defaults with the environment overrides applied
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+        if let Ok(backends) = std::env::var(BACKEND_ENV) {
+            config.backends = vec![parse_backends(&backends)
+                .with_context(|| format!("invalid {}={:?}", BACKEND_ENV, backends))?];
+        }
+        if let Ok(preference) = std::env::var(POWER_PREFERENCE_ENV) {
+            config.power_preference = parse_power_preference(&preference)
+                .with_context(|| format!("invalid {}={:?}", POWER_PREFERENCE_ENV, preference))?;
+        }
+        if let Ok(allow) = std::env::var(ALLOW_SOFTWARE_ENV) {
+            config.allow_software = !matches!(allow.trim(), "0" | "false" | "no");
+        }
+        Ok(config)
+    }
+
+    // every backend the instance has to be created with
+    pub fn all_backends(&self) -> wgpu::BackendBit {
+        self.backends
+            .iter()
+            .fold(wgpu::BackendBit::empty(), |all, backends| all | *backends)
+    }
+}
+
+// comma separated backend names, case insensitive
+pub fn parse_backends(names: &str) -> Result<wgpu::BackendBit> {
+    let mut backends = wgpu::BackendBit::empty();
+    for name in names.split(',').map(|name| name.trim().to_lowercase()) {
+        backends |= match name.as_str() {
+            "vulkan" | "vk" => wgpu::BackendBit::VULKAN,
+            "metal" | "mtl" => wgpu::BackendBit::METAL,
+            "dx12" | "d3d12" => wgpu::BackendBit::DX12,
+            "dx11" | "d3d11" => wgpu::BackendBit::DX11,
+            "gl" | "opengl" | "gles" => wgpu::BackendBit::GL,
+            "primary" => wgpu::BackendBit::PRIMARY,
+            "secondary" => wgpu::BackendBit::SECONDARY,
+            "all" => wgpu::BackendBit::all(),
+            _ => bail!(
+                "unknown backend {:?}, expected vulkan, metal, dx12, dx11, gl, primary, secondary or all",
+                name
+            ),
+        };
+    }
+    Ok(backends)
+}
+
+pub fn parse_power_preference(name: &str) -> Result<wgpu::PowerPreference> {
+    match name.trim().to_lowercase().as_str() {
+        "low" | "low_power" | "lowpower" => Ok(wgpu::PowerPreference::LowPower),
+        "high" | "high_performance" | "highperformance" => {
+            Ok(wgpu::PowerPreference::HighPerformance)
+        }
+        _ => bail!("unknown power preference {:?}, expected low or high", name),
+    }
+}
+
+// lower is better, None for software adapters
+pub fn hardware_rank(
+    device_type: wgpu::DeviceType,
+    power_preference: wgpu::PowerPreference,
+) -> Option<u32> {
+    use wgpu::DeviceType;
+    let (discrete, integrated) = match power_preference {
+        wgpu::PowerPreference::HighPerformance => (0, 1),
+        wgpu::PowerPreference::LowPower => (1, 0),
+    };
+    match device_type {
+        DeviceType::DiscreteGpu => Some(discrete),
+        DeviceType::IntegratedGpu => Some(integrated),
+        DeviceType::VirtualGpu => Some(2),
+        DeviceType::Other => Some(3),
+        DeviceType::Cpu => None,
+    }
+}
+
+fn describe(info: &wgpu::AdapterInfo) -> String {
+    format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type)
+}
+
+// the best hardware adapter that can present to the surface, trying the backend groups in
+// order, then a software adapter if allowed
+pub fn select_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface,
+    config: &AdapterConfig,
+) -> Result<wgpu::Adapter> {
+    // adapters that can't present to the window are never usable
+    let mut rejected = Vec::new();
+    let mut software = None;
+    for backends in config.backends.iter() {
+        let mut best: Option<(u32, wgpu::Adapter)> = None;
+        for adapter in instance.enumerate_adapters(*backends) {
+            let info = adapter.get_info();
+            if adapter.get_swap_chain_preferred_format(surface).is_none() {
+                rejected.push(format!("{} can't present to the window", describe(&info)));
+                continue;
+            }
+            match hardware_rank(info.device_type, config.power_preference) {
+                Some(rank) if best.as_ref().map_or(true, |(best, _)| rank < *best) => {
+                    best = Some((rank, adapter))
+                }
+                Some(_) => {}
+                None if software.is_none() => software = Some(adapter),
+                None => {}
+            }
+        }
+        if let Some((_, adapter)) = best {
+            log::info!("using adapter {}", describe(&adapter.get_info()));
+            return Ok(adapter);
+        }
+        log::warn!("no hardware adapter for backends {:?}", backends);
+    }
+
+    match software {
+        Some(adapter) if config.allow_software => {
+            log::warn!(
+                "falling back to the software adapter {}, expect low frame rates",
+                describe(&adapter.get_info())
+            );
+            Ok(adapter)
+        }
+        _ => {
+            let mut message = format!(
+                "no usable graphics adapter for backends {:?}",
+                config.all_backends()
+            );
+            if software.is_some() {
+                message += &format!(", software adapters are disabled by {}", ALLOW_SOFTWARE_ENV);
+            }
+            for reason in rejected {
+                message += &format!("\n  {}", reason);
+            }
+            message += &format!(
+                "\nother backends can be tried with {}, e.g. {}=vulkan",
+                BACKEND_ENV, BACKEND_ENV
+            );
+            Err(anyhow!(message))
+        }
+    }
+}
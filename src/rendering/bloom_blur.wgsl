@@ -0,0 +1,29 @@
+[[group(0), binding(0)]]
+var t_source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_post: sampler;
+
+[[block]]
+struct BlurUniform {
+    // xy: (1, 0) for the horizontal pass, (0, 1) for the vertical one
+    direction: vec4<f32>;
+};
+
+[[group(0), binding(2)]]
+var<uniform> u_blur: BlurUniform;
+
+// 9 tap gaussian in 5 samples, linear filtering blends each pair of taps
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    let texel = u_blur.direction.xy / vec2<f32>(textureDimensions(t_source));
+    let offset_1 = texel * 1.3846153846;
+    let offset_2 = texel * 3.2307692308;
+    var color: vec3<f32> = textureSample(t_source, s_post, in.uv).rgb * 0.2270270270;
+    color = color + textureSample(t_source, s_post, in.uv + offset_1).rgb * 0.3162162162;
+    color = color + textureSample(t_source, s_post, in.uv - offset_1).rgb * 0.3162162162;
+    color = color + textureSample(t_source, s_post, in.uv + offset_2).rgb * 0.0702702703;
+    color = color + textureSample(t_source, s_post, in.uv - offset_2).rgb * 0.0702702703;
+    return vec4<f32>(color, 1.0);
+}
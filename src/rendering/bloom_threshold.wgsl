@@ -0,0 +1,25 @@
+[[group(0), binding(0)]]
+var t_scene: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_post: sampler;
+
+[[block]]
+struct HdrUniform {
+    // x: exposure, y: bloom intensity, z: bloom threshold, w: tonemapper (0 aces, 1 reinhard)
+    params: vec4<f32>;
+};
+
+[[group(0), binding(2)]]
+var<uniform> u_hdr: HdrUniform;
+
+// keeps only what is brighter than the threshold
+// the target is half the size, so linear filtering averages 4 scene texels
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_scene, s_post, in.uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - u_hdr.params.z, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
@@ -0,0 +1,145 @@
+use anyhow::{bail, Result};
+
+// features the renderer uses when the adapter has them, nothing else is ever requested
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::from_bits_truncate(
+    wgpu::Features::NON_FILL_POLYGON_MODE.bits()
+        | wgpu::Features::MULTI_DRAW_INDIRECT.bits()
+        | wgpu::Features::TIMESTAMP_QUERY.bits(),
+);
+
+// the counts every adapter has to support, wgpu can't list the others so msaa tries them
+pub const GUARANTEED_SAMPLE_COUNTS: [u32; 2] = [1, 4];
+
+// what the device was created with, checked instead of assuming a feature is there
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    // the subset of OPTIONAL_FEATURES the adapter supports
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    // multisample counts known to work for the color and depth targets, ascending
+    pub sample_counts: Vec<u32>,
+    // llvmpipe, warp and the like, expensive features are better left off
+    pub software: bool,
+}
+
+impl Capabilities {
+    // fails with every limit the adapter falls short of, the renderer needs the defaults
+    pub fn from_adapter(adapter: &wgpu::Adapter) -> Result<Self> {
+        let limits = wgpu::Limits::default();
+        let missing = missing_limits(&adapter.limits(), &limits);
+        if !missing.is_empty() {
+            bail!(
+                "the adapter {} doesn't meet the required limits:\n  {}",
+                adapter.get_info().name,
+                missing.join("\n  ")
+            );
+        }
+        let capabilities = Self {
+            features: adapter.features() & OPTIONAL_FEATURES,
+            limits,
+            sample_counts: GUARANTEED_SAMPLE_COUNTS.to_vec(),
+            software: adapter.get_info().device_type == wgpu::DeviceType::Cpu,
+        };
+        capabilities.log_missing();
+        Ok(capabilities)
+    }
+
+    pub fn device_descriptor(&self) -> wgpu::DeviceDescriptor<'static> {
+        wgpu::DeviceDescriptor {
+            label: None,
+            features: self.features,
+            limits: self.limits.clone(),
+        }
+    }
+
+    // line and point polygon modes
+    pub fn wireframe(&self) -> bool {
+        self.features
+            .contains(wgpu::Features::NON_FILL_POLYGON_MODE)
+    }
+
+    // a whole batch of indirect draws in one call, otherwise one call per draw
+    pub fn multi_draw_indirect(&self) -> bool {
+        self.features.contains(wgpu::Features::MULTI_DRAW_INDIRECT)
+    }
+
+    // gpu timestamps written into query sets
+    pub fn timestamp_queries(&self) -> bool {
+        self.features.contains(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    fn log_missing(&self) {
+        let missing = OPTIONAL_FEATURES - self.features;
+        if missing.is_empty() {
+            log::info!("all optional features are supported");
+        } else {
+            log::info!("optional features not supported: {:?}", missing);
+        }
+    }
+}
+
+// every limit in required that supported doesn't reach, as readable lines
+pub fn missing_limits(supported: &wgpu::Limits, required: &wgpu::Limits) -> Vec<String> {
+    let limits = [
+        (
+            "max_texture_dimension_2d",
+            supported.max_texture_dimension_2d,
+            required.max_texture_dimension_2d,
+        ),
+        (
+            "max_texture_array_layers",
+            supported.max_texture_array_layers,
+            required.max_texture_array_layers,
+        ),
+        (
+            "max_bind_groups",
+            supported.max_bind_groups,
+            required.max_bind_groups,
+        ),
+        (
+            "max_sampled_textures_per_shader_stage",
+            supported.max_sampled_textures_per_shader_stage,
+            required.max_sampled_textures_per_shader_stage,
+        ),
+        (
+            "max_samplers_per_shader_stage",
+            supported.max_samplers_per_shader_stage,
+            required.max_samplers_per_shader_stage,
+        ),
+        (
+            "max_uniform_buffers_per_shader_stage",
+            supported.max_uniform_buffers_per_shader_stage,
+            required.max_uniform_buffers_per_shader_stage,
+        ),
+        (
+            "max_uniform_buffer_binding_size",
+            supported.max_uniform_buffer_binding_size,
+            required.max_uniform_buffer_binding_size,
+        ),
+        (
+            "max_storage_buffers_per_shader_stage",
+            supported.max_storage_buffers_per_shader_stage,
+            required.max_storage_buffers_per_shader_stage,
+        ),
+        (
+            "max_storage_buffer_binding_size",
+            supported.max_storage_buffer_binding_size,
+            required.max_storage_buffer_binding_size,
+        ),
+        (
+            "max_vertex_buffers",
+            supported.max_vertex_buffers,
+            required.max_vertex_buffers,
+        ),
+        (
+            "max_vertex_attributes",
+            supported.max_vertex_attributes,
+            required.max_vertex_attributes,
+        ),
+    ];
+    limits
+        .iter()
+        .filter(|(_, supported, required)| supported < required)
+        .map(|(name, supported, required)| format!("{}: {} of {}", name, supported, required))
+        .collect()
+}
@@ -0,0 +1,172 @@
+use cgmath::Vector3;
+
+use crate::{
+    rendering::{
+        render_utils::{self, AmbientTarget, PipelineOptions},
+        vertex_desc::VertexDesc,
+    },
+    texture,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl VertexDesc for LineVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                // position
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                // color
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                },
+            ],
+        }
+    }
+}
+
+// corner indices of the 12 edges of a box, bit 0 is x, bit 1 is y and bit 2 is z
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+// world space lines, depth tested against the scene, collected anew every frame
+pub struct DebugLines {
+    pipeline: wgpu::RenderPipeline,
+    vertices: Vec<LineVertex>,
+    vertex_buffer: Option<wgpu::Buffer>,
+    // in vertices
+    vertex_capacity: u64,
+    // vertices uploaded by the last prepare
+    prepared: u32,
+}
+
+impl DebugLines {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let visibility = wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT;
+        let camera_bind_group_layout =
+            render_utils::create_bind_group_layout(device, "camera_bind_layout", 0, visibility);
+        let shader_module = render_utils::create_shader_module(
+            device,
+            include_str!("debug_lines.wgsl"),
+            "debug_lines",
+        );
+        let layout = render_utils::create_pipeline_layout(
+            device,
+            "debug_lines",
+            &[&camera_bind_group_layout],
+        );
+        let pipeline = render_utils::create_render_pipeline_with_options(
+            device,
+            &layout,
+            color_format,
+            Some(texture::Texture::DEPTH_FORMAT),
+            &[LineVertex::desc()],
+            shader_module,
+            "debug_lines_pipeline",
+            PipelineOptions {
+                topology: wgpu::PrimitiveTopology::LineList,
+                sample_count,
+                ambient_target: AmbientTarget::Keep,
+                ..PipelineOptions::translucent()
+            },
+        );
+        Self {
+            pipeline,
+            vertices: Vec::new(),
+            vertex_buffer: None,
+            vertex_capacity: 0,
+            prepared: 0,
+        }
+    }
+
+    pub fn line(&mut self, from: Vector3<f32>, to: Vector3<f32>, color: [f32; 4]) {
+        self.vertices.push(LineVertex {
+            position: from.into(),
+            color,
+        });
+        self.vertices.push(LineVertex {
+            position: to.into(),
+            color,
+        });
+    }
+
+    pub fn aabb(&mut self, min: Vector3<f32>, max: Vector3<f32>, color: [f32; 4]) {
+        let corner = |i: usize| {
+            Vector3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        for (a, b) in BOX_EDGES.iter() {
+            self.line(corner(*a), corner(*b), color);
+        }
+    }
+
+    // uploads and clears the lines collected since the last call, draw them with draw
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let needed = self.vertices.len() as u64;
+        self.prepared = needed as u32;
+        if needed == 0 {
+            return;
+        }
+        if self.vertex_buffer.is_none() || needed > self.vertex_capacity {
+            self.vertex_capacity = needed.next_power_of_two();
+            self.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("debug_lines_vertex_buffer"),
+                size: self.vertex_capacity * std::mem::size_of::<LineVertex>() as u64,
+                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let vertex_buffer = self.vertex_buffer.as_ref().expect("created above");
+        queue.write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.vertices.clear();
+    }
+
+    // in a pass with the scene depth attached
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+    ) {
+        let vertex_buffer = match &self.vertex_buffer {
+            Some(vertex_buffer) if self.prepared > 0 => vertex_buffer,
+            _ => return,
+        };
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..self.prepared, 0..1);
+    }
+}
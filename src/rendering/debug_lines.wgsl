@@ -0,0 +1,31 @@
+[[block]]
+struct CameraUniform {
+    position: vec3<f32>;
+    projection_view: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> u_camera: CameraUniform;
+
+struct VertexOutput {
+    [[builtin(position)]] builtin_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.builtin_position = u_camera.projection_view * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,10 @@
+[[group(0), binding(0)]]
+var t_depth: texture_depth_2d;
+
+fn depth_size() -> vec2<i32> {
+    return textureDimensions(t_depth);
+}
+
+fn load_depth(texel: vec2<i32>) -> f32 {
+    return textureLoad(t_depth, texel, 0);
+}
@@ -0,0 +1,12 @@
+// a multisampled depth buffer can't be declared as a depth texture, it reads as float
+[[group(0), binding(0)]]
+var t_depth: texture_multisampled_2d<f32>;
+
+fn depth_size() -> vec2<i32> {
+    return textureDimensions(t_depth);
+}
+
+// the first sample, the other samples only differ along geometry edges
+fn load_depth(texel: vec2<i32>) -> f32 {
+    return textureLoad(t_depth, texel, 0).x;
+}
@@ -0,0 +1,94 @@
+use crate::voxel_tools::chunk::SIZE;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FogMode {
+    Off,
+    // fades evenly from start to end
+    Linear,
+    // thickens quickly after start, 99% fogged at end
+    Exponential,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FogSettings {
+    pub mode: FogMode,
+    // where the fog starts, as a fraction of the fog end
+    pub start: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            mode: FogMode::Linear,
+            start: 0.5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    color: [f32; 4],
+    // x: start, y: end, z: density, w: mode
+    params: [f32; 4],
+}
+
+impl FogUniform {
+    pub fn new(settings: &FogSettings, color: [f32; 3], render_distance: i32) -> Self {
+        let (start, end) = fog_range(settings, render_distance);
+        // ln(100), exponential fog reaches 99% at the end
+        let density = 4.605 / (end - start).max(0.001);
+        let mode = match settings.mode {
+            FogMode::Off => 0f32,
+            FogMode::Linear => 1f32,
+            FogMode::Exponential => 2f32,
+        };
+        Self {
+            color: [color[0], color[1], color[2], 1f32],
+            params: [start, end, density, mode],
+        }
+    }
+}
+
+// start and end distance in world units for a render distance in chunks
+// chunks are only loaded inside the render distance sphere around the camera's chunk,
+// so the fog is complete one chunk before it to hide the edge wherever the camera is
+pub fn fog_range(settings: &FogSettings, render_distance: i32) -> (f32, f32) {
+    let end = ((render_distance - 1).max(1) * SIZE as i32) as f32;
+    let start = end * settings.start.clamp(0f32, 1f32);
+    (start, end)
+}
+
+// FogUniform with fog_amount and apply_fog, prepended to the shaders of fogged geometry
+// the fog is bound next to the light uniform, light_group is the index of that bind group
+pub fn shader_source(light_group: u32) -> String {
+    include_str!("fog.wgsl").replace("LIGHT_GROUP", &light_group.to_string())
+}
+
+pub struct Fog {
+    pub settings: FogSettings,
+    buffer: wgpu::Buffer,
+}
+
+impl Fog {
+    pub fn new(device: &wgpu::Device, settings: FogSettings) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("fog_buffer"),
+            size: std::mem::size_of::<FogUniform>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { settings, buffer }
+    }
+
+    // bound next to the light uniform
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    // the color should match what is behind the terrain, the sky at the horizon
+    pub fn update(&self, queue: &wgpu::Queue, color: [f32; 3], render_distance: i32) {
+        let uniform = FogUniform::new(&self.settings, color, render_distance);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+}
@@ -0,0 +1,28 @@
+[[block]]
+struct FogUniform {
+    color: vec4<f32>;
+    // x: start, y: end, z: density, w: mode (0 off, 1 linear, 2 exponential)
+    params: vec4<f32>;
+};
+
+// binding 1 of the light bind group, the group is filled in by fog::shader_source
+[[group(LIGHT_GROUP), binding(1)]]
+var<uniform> u_fog: FogUniform;
+
+// 0 = clear, 1 = only fog
+fn fog_amount(view_distance: f32) -> f32 {
+    let start = u_fog.params.x;
+    let end = u_fog.params.y;
+    if (u_fog.params.w < 0.5) {
+        return 0.0;
+    }
+    if (u_fog.params.w < 1.5) {
+        return clamp((view_distance - start) / max(end - start, 0.001), 0.0, 1.0);
+    }
+    let thickness = max(view_distance - start, 0.0) * u_fog.params.z;
+    return 1.0 - exp(-thickness);
+}
+
+fn apply_fog(color: vec3<f32>, view_distance: f32) -> vec3<f32> {
+    return mix(color, u_fog.color.xyz, vec3<f32>(fog_amount(view_distance)));
+}
@@ -0,0 +1,43 @@
+use cgmath::{InnerSpace, Vector3, Vector4};
+
+// view frustum planes extracted from a view projection matrix, normals point inwards
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // expects wgpu clip space (depth 0..1), see camera::OPENGL_TO_WGPU_MATRIX
+    pub fn from_view_projection(view_proj: cgmath::Matrix4<f32>) -> Self {
+        let row = |i: usize| {
+            Vector4::new(
+                view_proj.x[i],
+                view_proj.y[i],
+                view_proj.z[i],
+                view_proj.w[i],
+            )
+        };
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        // left, right, bottom, top, near, far
+        let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2];
+        for plane in planes.iter_mut() {
+            let length = plane.truncate().magnitude();
+            if length > 0f32 {
+                *plane /= length;
+            }
+        }
+        Self { planes }
+    }
+
+    // conservative, boxes close to the frustum corners may pass
+    pub fn intersects_aabb(&self, min: Vector3<f32>, max: Vector3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let corner = Vector3::new(
+                if plane.x >= 0f32 { max.x } else { min.x },
+                if plane.y >= 0f32 { max.y } else { min.y },
+                if plane.z >= 0f32 { max.z } else { min.z },
+            );
+            plane.truncate().dot(corner) + plane.w >= 0f32
+        })
+    }
+}
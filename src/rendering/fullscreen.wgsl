@@ -0,0 +1,17 @@
+struct VertexOutput {
+    [[builtin(position)]] builtin_position: vec4<f32>;
+    // 0, 0 is the top left of the target
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// one triangle covering the target, no vertex buffer needed
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = vec2<f32>(f32((index % 2u) * 2u), f32((index / 2u) * 2u));
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    out.builtin_position = vec4<f32>(corner * 2.0 - vec2<f32>(1.0), 0.0, 1.0);
+    return out;
+}
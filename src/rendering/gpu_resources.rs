@@ -1,12 +1,30 @@
-use generational_arena::*;
+use super::{
+    capabilities::Capabilities, indirect_draw::IndirectDrawBuffer, mesh_buffer_pool::MeshBufferPool,
+};
+use crate::voxel_tools::rendering::voxel_vertex::VoxelVertex;
+
 pub struct GpuResources {
-    pub buffer_arena: Arena<wgpu::Buffer>,
+    // all chunk meshes are suballocated from a few large buffers
+    pub chunk_buffers: MeshBufferPool,
+    // draw arguments of the visible chunks, rebuilt every frame
+    pub chunk_draws: IndirectDrawBuffer,
+    // translucent chunk meshes, ordered back to front
+    pub chunk_translucent_draws: IndirectDrawBuffer,
+    // opaque chunk meshes inside any shadow cascade
+    pub chunk_shadow_draws: IndirectDrawBuffer,
 }
 
 impl GpuResources {
-    pub fn new() -> Self {
+    pub fn new(device: &wgpu::Device, capabilities: &Capabilities) -> Self {
+        let multi_draw = capabilities.multi_draw_indirect();
         Self {
-            buffer_arena: Arena::with_capacity(32),
+            chunk_buffers: MeshBufferPool::new(
+                "chunk_buffers",
+                std::mem::size_of::<VoxelVertex>() as u64,
+            ),
+            chunk_draws: IndirectDrawBuffer::new(device, multi_draw),
+            chunk_translucent_draws: IndirectDrawBuffer::new(device, multi_draw),
+            chunk_shadow_draws: IndirectDrawBuffer::new(device, multi_draw),
         }
     }
 }
@@ -0,0 +1,298 @@
+use wgpu::util::DeviceExt;
+
+use crate::{
+    rendering::post_process::{PostInput, PostProcessPass},
+    texture::Texture,
+};
+
+// the scene is rendered into this, so lighting can go past 1 before tone mapping
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// horizontal and vertical blur pairs, every pair widens the glow
+pub const BLOOM_BLUR_PASSES: usize = 2;
+
+// maps hdr colors into the 0..1 range of the swap chain
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tonemapper {
+    // filmic, desaturates very bright colors
+    Aces,
+    // keeps the hue, looks flatter
+    Reinhard,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct HdrSettings {
+    pub tonemapper: Tonemapper,
+    // scales the scene before tone mapping
+    pub exposure: f32,
+    pub bloom: bool,
+    // brightness above which pixels start to glow
+    pub bloom_threshold: f32,
+    // how much of the blurred glow is added to the scene
+    pub bloom_intensity: f32,
+}
+
+impl Default for HdrSettings {
+    fn default() -> Self {
+        Self {
+            tonemapper: Tonemapper::Aces,
+            exposure: 1f32,
+            bloom: true,
+            bloom_threshold: 1f32,
+            bloom_intensity: 0.4,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HdrUniform {
+    // x: exposure, y: bloom intensity, z: bloom threshold, w: tonemapper
+    params: [f32; 4],
+}
+
+impl HdrUniform {
+    pub fn new(settings: &HdrSettings) -> Self {
+        let bloom_intensity = if settings.bloom {
+            settings.bloom_intensity
+        } else {
+            0f32
+        };
+        let tonemapper = match settings.tonemapper {
+            Tonemapper::Aces => 0f32,
+            Tonemapper::Reinhard => 1f32,
+        };
+        Self {
+            params: [
+                settings.exposure,
+                bloom_intensity,
+                settings.bloom_threshold,
+                tonemapper,
+            ],
+        }
+    }
+}
+
+// textures sized to the window, recreated on resize
+struct HdrTargets {
+    scene: Texture,
+    // drawn into instead of the scene with msaa on, the last scene pass resolves it
+    multisampled_scene: Option<Texture>,
+    // half resolution, blurred back and forth between the two
+    bloom: [Texture; 2],
+    threshold_bind_group: wgpu::BindGroup,
+    // reads bloom[0] into bloom[1] and back
+    blur_bind_groups: [wgpu::BindGroup; 2],
+    tonemap_bind_group: wgpu::BindGroup,
+}
+
+// scene target followed by bloom and tone mapping into the swap chain
+pub struct HdrPipeline {
+    pub settings: HdrSettings,
+    uniform_buffer: wgpu::Buffer,
+    blur_buffers: [wgpu::Buffer; 2],
+    sample_count: u32,
+    threshold_pass: PostProcessPass,
+    blur_pass: PostProcessPass,
+    tonemap_pass: PostProcessPass,
+    targets: HdrTargets,
+}
+
+impl HdrPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        sample_count: u32,
+        settings: HdrSettings,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("hdr_uniform_buffer"),
+            contents: bytemuck::cast_slice(&[HdrUniform::new(&settings)]),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let create_blur_buffer = |label, direction: [f32; 4]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(&[direction]),
+                usage: wgpu::BufferUsage::UNIFORM,
+            })
+        };
+        let blur_buffers = [
+            create_blur_buffer("bloom_blur_horizontal", [1f32, 0f32, 0f32, 0f32]),
+            create_blur_buffer("bloom_blur_vertical", [0f32, 1f32, 0f32, 0f32]),
+        ];
+
+        let threshold_pass = PostProcessPass::new(
+            device,
+            "bloom_threshold",
+            include_str!("bloom_threshold.wgsl"),
+            &[PostInput::Color],
+            true,
+            HDR_FORMAT,
+        );
+        let blur_pass = PostProcessPass::new(
+            device,
+            "bloom_blur",
+            include_str!("bloom_blur.wgsl"),
+            &[PostInput::Color],
+            true,
+            HDR_FORMAT,
+        );
+        let tonemap_pass = PostProcessPass::new(
+            device,
+            "tonemap",
+            include_str!("tonemap.wgsl"),
+            &[PostInput::Color, PostInput::Color],
+            true,
+            sc_desc.format,
+        );
+
+        let targets = Self::create_targets(
+            device,
+            sc_desc,
+            sample_count,
+            &threshold_pass,
+            &blur_pass,
+            &tonemap_pass,
+            &uniform_buffer,
+            &blur_buffers,
+        );
+
+        Self {
+            settings,
+            uniform_buffer,
+            blur_buffers,
+            sample_count,
+            threshold_pass,
+            blur_pass,
+            tonemap_pass,
+            targets,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_targets(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        sample_count: u32,
+        threshold_pass: &PostProcessPass,
+        blur_pass: &PostProcessPass,
+        tonemap_pass: &PostProcessPass,
+        uniform_buffer: &wgpu::Buffer,
+        blur_buffers: &[wgpu::Buffer; 2],
+    ) -> HdrTargets {
+        let scene = Texture::create_render_target(
+            device,
+            sc_desc.width,
+            sc_desc.height,
+            HDR_FORMAT,
+            "hdr_scene",
+        );
+        let multisampled_scene = if sample_count > 1 {
+            Some(Texture::create_render_target_with_samples(
+                device,
+                sc_desc.width,
+                sc_desc.height,
+                HDR_FORMAT,
+                sample_count,
+                "hdr_scene_multisampled",
+            ))
+        } else {
+            None
+        };
+        let (bloom_width, bloom_height) = (sc_desc.width / 2, sc_desc.height / 2);
+        let bloom = [
+            Texture::create_render_target(device, bloom_width, bloom_height, HDR_FORMAT, "bloom_0"),
+            Texture::create_render_target(device, bloom_width, bloom_height, HDR_FORMAT, "bloom_1"),
+        ];
+        let threshold_bind_group =
+            threshold_pass.create_bind_group(device, &[&scene.view], Some(uniform_buffer));
+        let blur_bind_groups = [
+            blur_pass.create_bind_group(device, &[&bloom[0].view], Some(&blur_buffers[0])),
+            blur_pass.create_bind_group(device, &[&bloom[1].view], Some(&blur_buffers[1])),
+        ];
+        let tonemap_bind_group = tonemap_pass.create_bind_group(
+            device,
+            &[&scene.view, &bloom[0].view],
+            Some(uniform_buffer),
+        );
+        HdrTargets {
+            scene,
+            multisampled_scene,
+            bloom,
+            threshold_bind_group,
+            blur_bind_groups,
+            tonemap_bind_group,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
+        self.targets = Self::create_targets(
+            device,
+            sc_desc,
+            self.sample_count,
+            &self.threshold_pass,
+            &self.blur_pass,
+            &self.tonemap_pass,
+            &self.uniform_buffer,
+            &self.blur_buffers,
+        );
+    }
+
+    // the color attachment of every pass drawing the scene
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        match &self.targets.multisampled_scene {
+            Some(multisampled) => &multisampled.view,
+            None => &self.targets.scene.view,
+        }
+    }
+
+    // the resolve target of the last pass drawing the scene, None without msaa
+    pub fn scene_resolve_target(&self) -> Option<&wgpu::TextureView> {
+        self.targets
+            .multisampled_scene
+            .as_ref()
+            .map(|_| &self.targets.scene.view)
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let uniform = HdrUniform::new(&self.settings);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // run after the scene is drawn, overwrites the whole frame
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, frame: &wgpu::TextureView) {
+        let targets = &self.targets;
+        if self.settings.bloom {
+            self.threshold_pass.render(
+                encoder,
+                &targets.bloom[0].view,
+                &targets.threshold_bind_group,
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                None,
+            );
+            for _ in 0..BLOOM_BLUR_PASSES {
+                self.blur_pass.render(
+                    encoder,
+                    &targets.bloom[1].view,
+                    &targets.blur_bind_groups[0],
+                    wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    None,
+                );
+                self.blur_pass.render(
+                    encoder,
+                    &targets.bloom[0].view,
+                    &targets.blur_bind_groups[1],
+                    wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    None,
+                );
+            }
+        }
+        self.tonemap_pass.render(
+            encoder,
+            frame,
+            &targets.tonemap_bind_group,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            None,
+        );
+    }
+}
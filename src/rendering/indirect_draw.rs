@@ -0,0 +1,110 @@
+use super::mesh_buffer_pool::MeshBufferPool;
+
+// layout expected by draw_indexed_indirect, tightly packed
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndexedIndirectArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub base_index: u32,
+    pub vertex_offset: i32,
+    pub base_instance: u32,
+}
+
+const ARGS_SIZE: u64 = std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+
+// consecutive draws that read from the same slab of the mesh pool
+struct IndirectBatch {
+    slab: usize,
+    first: u32,
+    count: u32,
+}
+
+// draw arguments for many meshes in one buffer, rebuilt every frame
+pub struct IndirectDrawBuffer {
+    buffer: wgpu::Buffer,
+    // in draws
+    capacity: u64,
+    args: Vec<DrawIndexedIndirectArgs>,
+    batches: Vec<IndirectBatch>,
+    // Features::MULTI_DRAW_INDIRECT, issue a whole batch with one call
+    multi_draw: bool,
+}
+
+pub const DEFAULT_INDIRECT_CAPACITY: u64 = 1024;
+
+impl IndirectDrawBuffer {
+    pub fn new(device: &wgpu::Device, multi_draw: bool) -> Self {
+        Self {
+            buffer: create_indirect_buffer(device, DEFAULT_INDIRECT_CAPACITY),
+            capacity: DEFAULT_INDIRECT_CAPACITY,
+            args: Vec::with_capacity(DEFAULT_INDIRECT_CAPACITY as usize),
+            batches: Vec::new(),
+            multi_draw,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.args.clear();
+        self.batches.clear();
+    }
+
+    pub fn draw_count(&self) -> usize {
+        self.args.len()
+    }
+
+    // push draws sorted by slab, so a slab's vertex and index buffers are bound once
+    pub fn push(&mut self, slab: usize, args: DrawIndexedIndirectArgs) {
+        let draw_index = self.args.len() as u32;
+        self.args.push(args);
+        match self.batches.last_mut() {
+            Some(batch) if batch.slab == slab => batch.count += 1,
+            _ => self.batches.push(IndirectBatch {
+                slab,
+                first: draw_index,
+                count: 1,
+            }),
+        }
+    }
+
+    // copy the pushed draws to the gpu, must happen before the render pass starts
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.args.is_empty() {
+            return;
+        }
+        let needed = self.args.len() as u64;
+        if needed > self.capacity {
+            self.capacity = needed.next_power_of_two();
+            self.buffer = create_indirect_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.args));
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, pool: &'a MeshBufferPool) {
+        for batch in self.batches.iter() {
+            let slab = match pool.slab(batch.slab) {
+                Some(slab) => slab,
+                None => continue,
+            };
+            render_pass.set_vertex_buffer(0, slab.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(slab.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            let offset = batch.first as u64 * ARGS_SIZE;
+            if self.multi_draw {
+                render_pass.multi_draw_indexed_indirect(&self.buffer, offset, batch.count);
+            } else {
+                for i in 0..batch.count as u64 {
+                    render_pass.draw_indexed_indirect(&self.buffer, offset + i * ARGS_SIZE);
+                }
+            }
+        }
+    }
+}
+
+fn create_indirect_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("indirect_draw_buffer"),
+        size: capacity * ARGS_SIZE,
+        usage: wgpu::BufferUsage::INDIRECT | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,277 @@
+use std::ops::Range;
+
+use super::range_allocator::{AllocationId, RangeAllocator, RangeMove};
+
+// where a mesh lives inside the pool, ranges are looked up through the ids
+// so meshes stay valid when a slab is defragmented
+#[derive(Debug, Clone, Copy)]
+pub struct MeshAllocation {
+    pub slab: usize,
+    pub vertices: AllocationId,
+    pub indices: AllocationId,
+}
+
+// one pair of large vertex and index buffers that many meshes are suballocated from
+pub struct MeshSlab {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    vertex_allocator: RangeAllocator,
+    index_allocator: RangeAllocator,
+}
+
+impl MeshSlab {
+    // index range to draw, indices are local to the mesh so the vertex range start
+    // is used as base vertex
+    pub fn draw_range(&self, allocation: &MeshAllocation) -> Option<(Range<u32>, i32)> {
+        let vertices = self.vertex_allocator.get(allocation.vertices)?;
+        let indices = self.index_allocator.get(allocation.indices)?;
+        Some((
+            indices.start as u32..indices.end as u32,
+            vertices.start as i32,
+        ))
+    }
+}
+
+// vertices are counted in vertices and indices in u32 indices
+pub const DEFAULT_SLAB_VERTICES: u64 = 1 << 19;
+pub const DEFAULT_SLAB_INDICES: u64 = DEFAULT_SLAB_VERTICES * 3 / 2;
+
+pub struct MeshBufferPool {
+    label: String,
+    vertex_size: u64,
+    slabs: Vec<MeshSlab>,
+}
+
+impl MeshBufferPool {
+    pub fn new(label: &str, vertex_size: u64) -> Self {
+        Self {
+            label: label.to_string(),
+            vertex_size,
+            slabs: Vec::new(),
+        }
+    }
+
+    pub fn slab(&self, index: usize) -> Option<&MeshSlab> {
+        self.slabs.get(index)
+    }
+
+    // bytes of gpu memory held by the pool, and how many of them are in use
+    pub fn memory_usage(&self) -> (u64, u64) {
+        self.slabs.iter().fold((0, 0), |(total, used), slab| {
+            (
+                total
+                    + slab.vertex_allocator.capacity() * self.vertex_size
+                    + slab.index_allocator.capacity() * INDEX_SIZE,
+                used + slab.vertex_allocator.allocated() * self.vertex_size
+                    + slab.index_allocator.allocated() * INDEX_SIZE,
+            )
+        })
+    }
+
+    // copy the mesh into a free range, defragmenting or adding slabs when needed
+    // returns None for empty meshes
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertices: &[u8],
+        indices: &[u32],
+    ) -> Option<MeshAllocation> {
+        let num_vertices = vertices.len() as u64 / self.vertex_size;
+        let num_indices = indices.len() as u64;
+        if num_vertices == 0 || num_indices == 0 {
+            return None;
+        }
+
+        let allocation = self
+            .try_allocate(num_vertices, num_indices)
+            .or_else(|| self.defragment_and_allocate(device, queue, num_vertices, num_indices))
+            .unwrap_or_else(|| {
+                let slab = self.add_slab(device, num_vertices, num_indices);
+                self.try_allocate_in(slab, num_vertices, num_indices)
+                    .expect("new slab fits the mesh")
+            });
+
+        let slab = &self.slabs[allocation.slab];
+        let (index_range, base_vertex) = slab.draw_range(&allocation)?;
+        queue.write_buffer(
+            &slab.vertex_buffer,
+            base_vertex as u64 * self.vertex_size,
+            vertices,
+        );
+        queue.write_buffer(
+            &slab.index_buffer,
+            index_range.start as u64 * INDEX_SIZE,
+            bytemuck::cast_slice(indices),
+        );
+        Some(allocation)
+    }
+
+    pub fn free(&mut self, allocation: MeshAllocation) {
+        if let Some(slab) = self.slabs.get_mut(allocation.slab) {
+            slab.vertex_allocator.free(allocation.vertices);
+            slab.index_allocator.free(allocation.indices);
+        }
+    }
+
+    fn try_allocate(&mut self, num_vertices: u64, num_indices: u64) -> Option<MeshAllocation> {
+        (0..self.slabs.len()).find_map(|slab| self.try_allocate_in(slab, num_vertices, num_indices))
+    }
+
+    fn try_allocate_in(
+        &mut self,
+        slab_index: usize,
+        num_vertices: u64,
+        num_indices: u64,
+    ) -> Option<MeshAllocation> {
+        let slab = self.slabs.get_mut(slab_index)?;
+        let vertices = slab.vertex_allocator.allocate(num_vertices)?;
+        let indices = match slab.index_allocator.allocate(num_indices) {
+            Some(indices) => indices,
+            None => {
+                slab.vertex_allocator.free(vertices);
+                return None;
+            }
+        };
+        Some(MeshAllocation {
+            slab: slab_index,
+            vertices,
+            indices,
+        })
+    }
+
+    // the first slab with enough total free space, but not in one piece, is compacted
+    fn defragment_and_allocate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        num_vertices: u64,
+        num_indices: u64,
+    ) -> Option<MeshAllocation> {
+        let slab_index = self.slabs.iter().position(|slab| {
+            slab.vertex_allocator.free_space() >= num_vertices
+                && slab.index_allocator.free_space() >= num_indices
+        })?;
+        self.defragment_slab(device, queue, slab_index);
+        self.try_allocate_in(slab_index, num_vertices, num_indices)
+    }
+
+    // buffers can't copy onto themselves, so live ranges are copied to new buffers
+    fn defragment_slab(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, slab_index: usize) {
+        let vertex_size = self.vertex_size;
+        let label = self.label.clone();
+        let slab = &mut self.slabs[slab_index];
+        let vertex_moves = slab.vertex_allocator.defragment();
+        let index_moves = slab.index_allocator.defragment();
+        log::debug!(
+            "defragmenting {} slab {}: {} vertex and {} index ranges moved",
+            label,
+            slab_index,
+            vertex_moves.len(),
+            index_moves.len()
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("mesh_buffer_pool.defragment"),
+        });
+        let vertex_buffer = create_slab_buffer(
+            device,
+            &label,
+            slab.vertex_allocator.capacity() * vertex_size,
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buffer = create_slab_buffer(
+            device,
+            &label,
+            slab.index_allocator.capacity() * INDEX_SIZE,
+            wgpu::BufferUsage::INDEX,
+        );
+        // ranges that were not moved are kept by copying the whole buffer first,
+        // moved ranges are then copied from their old location
+        copy_with_moves(
+            &mut encoder,
+            &slab.vertex_buffer,
+            &vertex_buffer,
+            slab.vertex_allocator.capacity() * vertex_size,
+            &vertex_moves,
+            vertex_size,
+        );
+        copy_with_moves(
+            &mut encoder,
+            &slab.index_buffer,
+            &index_buffer,
+            slab.index_allocator.capacity() * INDEX_SIZE,
+            &index_moves,
+            INDEX_SIZE,
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+        // the old buffers are dropped here, wgpu frees them once the copy is done
+        slab.vertex_buffer = vertex_buffer;
+        slab.index_buffer = index_buffer;
+    }
+
+    fn add_slab(&mut self, device: &wgpu::Device, num_vertices: u64, num_indices: u64) -> usize {
+        let vertex_capacity = DEFAULT_SLAB_VERTICES.max(num_vertices);
+        let index_capacity = DEFAULT_SLAB_INDICES.max(num_indices);
+        log::info!(
+            "adding {} slab {} ({} vertices, {} indices)",
+            self.label,
+            self.slabs.len(),
+            vertex_capacity,
+            index_capacity
+        );
+        self.slabs.push(MeshSlab {
+            vertex_buffer: create_slab_buffer(
+                device,
+                &self.label,
+                vertex_capacity * self.vertex_size,
+                wgpu::BufferUsage::VERTEX,
+            ),
+            index_buffer: create_slab_buffer(
+                device,
+                &self.label,
+                index_capacity * INDEX_SIZE,
+                wgpu::BufferUsage::INDEX,
+            ),
+            vertex_allocator: RangeAllocator::new(vertex_capacity),
+            index_allocator: RangeAllocator::new(index_capacity),
+        });
+        self.slabs.len() - 1
+    }
+}
+
+const INDEX_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+fn create_slab_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    size: u64,
+    usage: wgpu::BufferUsage,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: usage | wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+fn copy_with_moves(
+    encoder: &mut wgpu::CommandEncoder,
+    source: &wgpu::Buffer,
+    destination: &wgpu::Buffer,
+    size: u64,
+    moves: &[RangeMove],
+    element_size: u64,
+) {
+    encoder.copy_buffer_to_buffer(source, 0, destination, 0, size);
+    for range_move in moves {
+        encoder.copy_buffer_to_buffer(
+            source,
+            range_move.from.start * element_size,
+            destination,
+            range_move.to.start * element_size,
+            (range_move.to.end - range_move.to.start) * element_size,
+        );
+    }
+}
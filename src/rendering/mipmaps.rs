@@ -0,0 +1,129 @@
+use crate::texture::ColorSpace;
+
+// levels in a full mip chain, down to 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// every level including the image itself, each half the size of the previous
+pub fn generate_mip_chain(
+    image: &image::RgbaImage,
+    color_space: ColorSpace,
+) -> Vec<image::RgbaImage> {
+    let level_count = mip_level_count(image.width(), image.height());
+    let mut levels = Vec::with_capacity(level_count as usize);
+    levels.push(image.clone());
+    for _ in 1..level_count {
+        let next = downsample(levels.last().unwrap(), color_space);
+        levels.push(next);
+    }
+    levels
+}
+
+// 2x2 box filter, the last row/column of odd sized images is reused at the edge
+// srgb colors are averaged in linear space, alpha is always linear
+pub fn downsample(image: &image::RgbaImage, color_space: ColorSpace) -> image::RgbaImage {
+    let (width, height) = image.dimensions();
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+    image::RgbaImage::from_fn(new_width, new_height, |x, y| {
+        let mut sum = [0f32; 4];
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            let source_x = (x * 2 + dx).min(width - 1);
+            let source_y = (y * 2 + dy).min(height - 1);
+            let pixel = image.get_pixel(source_x, source_y);
+            for channel in 0..4 {
+                sum[channel] += to_linear(pixel[channel], channel, color_space);
+            }
+        }
+        let mut pixel = [0u8; 4];
+        for channel in 0..4 {
+            pixel[channel] = from_linear(sum[channel] / 4f32, channel, color_space);
+        }
+        image::Rgba(pixel)
+    })
+}
+
+const ALPHA_CHANNEL: usize = 3;
+
+fn to_linear(value: u8, channel: usize, color_space: ColorSpace) -> f32 {
+    let value = value as f32 / 255f32;
+    match color_space {
+        ColorSpace::Srgb if channel != ALPHA_CHANNEL => srgb_to_linear(value),
+        _ => value,
+    }
+}
+
+fn from_linear(value: f32, channel: usize, color_space: ColorSpace) -> u8 {
+    let value = match color_space {
+        ColorSpace::Srgb if channel != ALPHA_CHANNEL => linear_to_srgb(value),
+        _ => value,
+    };
+    (value.clamp(0f32, 1f32) * 255f32).round() as u8
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1f32 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_counts() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(2, 2), 2);
+        assert_eq!(mip_level_count(256, 256), 9);
+        // the larger side decides, non power of two sizes round down
+        assert_eq!(mip_level_count(300, 17), 9);
+        assert_eq!(mip_level_count(5, 3), 3);
+        assert_eq!(mip_level_count(1, 64), 7);
+        assert_eq!(mip_level_count(0, 0), 1);
+    }
+
+    #[test]
+    fn chains_end_at_one_pixel() {
+        let image = image::RgbaImage::new(5, 3);
+        let sizes = generate_mip_chain(&image, ColorSpace::Linear)
+            .iter()
+            .map(|level| level.dimensions())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![(5, 3), (2, 1), (1, 1)]);
+
+        let sizes = generate_mip_chain(&image::RgbaImage::new(1, 1), ColorSpace::Srgb)
+            .iter()
+            .map(|level| level.dimensions())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn srgb_colors_are_averaged_in_linear_space() {
+        // black and white checkerboard, transparent and opaque
+        let image = image::RgbaImage::from_fn(2, 2, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgba([0, 0, 0, 0])
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        });
+        // half the linear intensity is brighter than half the srgb value
+        let srgb = downsample(&image, ColorSpace::Srgb);
+        assert_eq!(srgb.dimensions(), (1, 1));
+        assert_eq!(*srgb.get_pixel(0, 0), image::Rgba([188, 188, 188, 128]));
+        let linear = downsample(&image, ColorSpace::Linear);
+        assert_eq!(*linear.get_pixel(0, 0), image::Rgba([128, 128, 128, 128]));
+    }
+}
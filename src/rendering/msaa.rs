@@ -0,0 +1,180 @@
+use anyhow::{bail, Result};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::rendering::capabilities::Capabilities;
+
+// MSAA_SAMPLES=1 turns multisampling off
+pub const SAMPLE_COUNT_ENV: &str = "MSAA_SAMPLES";
+pub const DEFAULT_SAMPLE_COUNT: u32 = 4;
+// software adapters can't afford multisampling by default
+pub const SOFTWARE_SAMPLE_COUNT: u32 = 1;
+// 4 works everywhere, 2 and 8 depend on the adapter
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+// samples per pixel of the scene color and depth targets, set for the whole run
+// counts the adapter isn't known to support are tried with try_sample_count first
+pub fn sample_count_from_env(
+    device: &wgpu::Device,
+    capabilities: &Capabilities,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+) -> Result<u32> {
+    let requested = match std::env::var(SAMPLE_COUNT_ENV) {
+        Ok(value) => Some(parse_sample_count(&value)?),
+        Err(_) => None,
+    };
+    Ok(select_sample_count(requested, capabilities, |count| {
+        try_sample_count(device, color_format, depth_format, count)
+    }))
+}
+
+// the requested count, or the default for the adapter if none was requested
+// requested counts outside of the supported ones are only used if is_supported says so,
+// otherwise they are lowered to the highest supported count below them
+pub fn select_sample_count(
+    requested: Option<u32>,
+    capabilities: &Capabilities,
+    is_supported: impl Fn(u32) -> bool,
+) -> u32 {
+    let count = requested.unwrap_or(if capabilities.software {
+        SOFTWARE_SAMPLE_COUNT
+    } else {
+        DEFAULT_SAMPLE_COUNT
+    });
+    if capabilities.sample_counts.contains(&count) {
+        return count;
+    }
+    if is_supported(count) {
+        log::info!("msaa {}x works on the adapter", count);
+        return count;
+    }
+    let fallback = capabilities
+        .sample_counts
+        .iter()
+        .copied()
+        .filter(|supported| *supported < count)
+        .max()
+        .unwrap_or(1);
+    log::warn!(
+        "msaa {}x isn't supported by the adapter, using {}x",
+        count,
+        fallback
+    );
+    fallback
+}
+
+// wgpu only checks the count against the adapter when a pass starts, so this clears small
+// color and depth targets with it and sees if that raises an error
+// replaces the uncaptured error handler with one that panics like the default afterwards
+pub fn try_sample_count(
+    device: &wgpu::Device,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    count: u32,
+) -> bool {
+    let failed = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&failed);
+    device.on_uncaptured_error(move |error| {
+        log::debug!("msaa {}x failed: {}", count, error);
+        flag.store(true, Ordering::SeqCst);
+    });
+
+    let target = |format, label| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: 4,
+                    height: 4,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    };
+    let color = target(color_format, "msaa_probe_color");
+    let depth = target(depth_format, "msaa_probe_depth");
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("msaa_probe"),
+    });
+    drop(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("msaa_probe"),
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view: &color,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: false,
+            },
+        }],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: &depth,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: false,
+            }),
+            stencil_ops: None,
+        }),
+    }));
+    // never submitted, only recording the pass validates it
+    drop(encoder.finish());
+
+    device.on_uncaptured_error(|error| panic!("wgpu error: {}", error));
+    !failed.load(Ordering::SeqCst)
+}
+
+pub fn parse_sample_count(value: &str) -> Result<u32> {
+    let value = value.trim();
+    let count = value.strip_suffix('x').unwrap_or(value);
+    match count.parse::<u32>() {
+        Ok(count) if SAMPLE_COUNTS.contains(&count) => Ok(count),
+        _ => bail!(
+            "invalid {}={:?}, expected one of {:?}",
+            SAMPLE_COUNT_ENV,
+            value,
+            SAMPLE_COUNTS
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(software: bool) -> Capabilities {
+        Capabilities {
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            sample_counts: vec![1, 4],
+            software,
+        }
+    }
+
+    fn never(count: u32) -> bool {
+        panic!("{}x shouldn't need to be tried", count)
+    }
+
+    #[test]
+    fn software_adapters_default_to_one_sample() {
+        assert_eq!(select_sample_count(None, &capabilities(false), never), 4);
+        assert_eq!(select_sample_count(None, &capabilities(true), never), 1);
+        assert_eq!(select_sample_count(Some(4), &capabilities(true), never), 4);
+    }
+
+    #[test]
+    fn other_counts_are_tried_before_falling_back() {
+        let works = |count| count == 8;
+        assert_eq!(select_sample_count(Some(8), &capabilities(false), works), 8);
+        assert_eq!(select_sample_count(Some(2), &capabilities(false), works), 1);
+        let fails = |_| false;
+        assert_eq!(select_sample_count(Some(8), &capabilities(false), fails), 4);
+        assert_eq!(select_sample_count(Some(1), &capabilities(false), never), 1);
+    }
+}
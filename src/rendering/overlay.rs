@@ -0,0 +1,308 @@
+use wgpu::util::DeviceExt;
+
+use crate::{
+    rendering::{
+        bitmap_font::{FontAtlas, CELL_HEIGHT, CELL_WIDTH, GLYPH_HEIGHT, GLYPH_WIDTH},
+        render_utils::{self, PipelineOptions},
+        vertex_desc::VertexDesc,
+    },
+    texture::SamplerOptions,
+};
+
+// screen pixels per font pixel
+pub const TEXT_SCALE: f32 = 2f32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+    // pixels from the top left of the target
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+impl VertexDesc for OverlayVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                // position
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                // uv in the font atlas
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                },
+                // color
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                },
+            ],
+        }
+    }
+}
+
+// text and solid rectangles drawn on top of the frame, collected anew every frame
+pub struct OverlayRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    atlas: FontAtlas,
+    vertices: Vec<OverlayVertex>,
+    vertex_buffer: Option<wgpu::Buffer>,
+    // in vertices
+    vertex_capacity: u64,
+}
+
+impl OverlayRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sc_desc: &wgpu::SwapChainDescriptor,
+    ) -> Self {
+        let atlas = FontAtlas::generate();
+        let size = wgpu::Extent3d {
+            width: atlas.width,
+            height: atlas.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("font_atlas"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &atlas.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(atlas.width),
+                rows_per_image: std::num::NonZeroU32::new(atlas.height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // text is drawn at whole multiples of the font size
+        let sampler = SamplerOptions::default().create_sampler(device, Some("font_atlas"));
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("overlay_uniform_buffer"),
+            contents: bytemuck::cast_slice(&[Self::screen_size(sc_desc)]),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("overlay_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("overlay_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader_module =
+            render_utils::create_shader_module(device, include_str!("overlay.wgsl"), "overlay");
+        let layout = render_utils::create_pipeline_layout(device, "overlay", &[&bind_group_layout]);
+        let pipeline = render_utils::create_render_pipeline_with_options(
+            device,
+            &layout,
+            sc_desc.format,
+            None,
+            &[OverlayVertex::desc()],
+            shader_module,
+            "overlay_pipeline",
+            PipelineOptions {
+                blend: wgpu::BlendState::ALPHA_BLENDING,
+                ..PipelineOptions::background()
+            },
+        );
+
+        Self {
+            pipeline,
+            bind_group,
+            uniform_buffer,
+            atlas,
+            vertices: Vec::new(),
+            vertex_buffer: None,
+            vertex_capacity: 0,
+        }
+    }
+
+    fn screen_size(sc_desc: &wgpu::SwapChainDescriptor) -> [f32; 4] {
+        [sc_desc.width as f32, sc_desc.height as f32, 0f32, 0f32]
+    }
+
+    pub fn resize(&self, queue: &wgpu::Queue, sc_desc: &wgpu::SwapChainDescriptor) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[Self::screen_size(sc_desc)]),
+        );
+    }
+
+    // height of one line of text in pixels, including the spacing
+    pub fn line_height() -> f32 {
+        CELL_HEIGHT as f32 * TEXT_SCALE
+    }
+
+    // width of a text in pixels
+    pub fn text_width(text: &str) -> f32 {
+        text.chars().count() as f32 * CELL_WIDTH as f32 * TEXT_SCALE
+    }
+
+    fn push_quad(
+        &mut self,
+        min: [f32; 2],
+        max: [f32; 2],
+        uv_min: [f32; 2],
+        uv_max: [f32; 2],
+        color: [f32; 4],
+    ) {
+        let corners = [
+            ([min[0], min[1]], [uv_min[0], uv_min[1]]),
+            ([max[0], min[1]], [uv_max[0], uv_min[1]]),
+            ([max[0], max[1]], [uv_max[0], uv_max[1]]),
+            ([min[0], max[1]], [uv_min[0], uv_max[1]]),
+        ];
+        for index in [0, 1, 2, 0, 2, 3].iter() {
+            let (position, uv) = corners[*index];
+            self.vertices.push(OverlayVertex {
+                position,
+                uv,
+                color,
+            });
+        }
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        let uv = self.atlas.solid_uv();
+        self.push_quad([x, y], [x + width, y + height], uv, uv, color);
+    }
+
+    // one line from the top left corner, newlines are not handled
+    pub fn text(&mut self, x: f32, y: f32, text: &str, color: [f32; 4]) {
+        let glyph_size = [
+            GLYPH_WIDTH as f32 * TEXT_SCALE,
+            GLYPH_HEIGHT as f32 * TEXT_SCALE,
+        ];
+        for (column, character) in text.chars().enumerate() {
+            if character == ' ' {
+                continue;
+            }
+            let left = x + column as f32 * CELL_WIDTH as f32 * TEXT_SCALE;
+            let (uv_min, uv_max) = self.atlas.glyph_uv(FontAtlas::cell(character));
+            self.push_quad(
+                [left, y],
+                [left + glyph_size[0], y + glyph_size[1]],
+                uv_min,
+                uv_max,
+                color,
+            );
+        }
+    }
+
+    // draws and clears everything collected since the last render
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let needed = self.vertices.len() as u64;
+        if self.vertex_buffer.is_none() || needed > self.vertex_capacity {
+            self.vertex_capacity = needed.next_power_of_two();
+            self.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("overlay_vertex_buffer"),
+                size: self.vertex_capacity * std::mem::size_of::<OverlayVertex>() as u64,
+                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        let vertex_buffer = self.vertex_buffer.as_ref().expect("created above");
+        queue.write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("overlay"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+        drop(render_pass);
+        self.vertices.clear();
+    }
+}
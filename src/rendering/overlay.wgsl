@@ -0,0 +1,41 @@
+[[block]]
+struct OverlayUniform {
+    // xy: target size in pixels
+    screen_size: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> u_overlay: OverlayUniform;
+[[group(0), binding(1)]]
+var t_font: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_font: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] builtin_position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+// positions are in pixels from the top left of the target
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec2<f32>,
+    [[location(1)]] uv: vec2<f32>,
+    [[location(2)]] color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = position / u_overlay.screen_size.xy * 2.0 - vec2<f32>(1.0);
+    out.builtin_position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.uv = uv;
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    let coverage = textureSample(t_font, s_font, in.uv).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,233 @@
+use crate::{
+    rendering::render_utils::{self, PipelineOptions},
+    texture::SamplerOptions,
+};
+
+// vertex stage shared by every post process shader, fragment shaders read `in.uv`
+const FULLSCREEN_VERTEX: &str = include_str!("fullscreen.wgsl");
+// t_depth at binding 0 with depth_size() and load_depth(texel) to read it
+const DEPTH_INPUT: &str = include_str!("depth_input.wgsl");
+const DEPTH_INPUT_MULTISAMPLED: &str = include_str!("depth_input_multisampled.wgsl");
+
+// what a post process pass reads at one texture binding
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PostInput {
+    // filterable, read with textureSample and the pass sampler
+    Color,
+    // read with textureLoad, the sampler doesn't apply
+    Depth,
+    // the depth of a multisampled pass, read as float with textureLoad
+    MultisampledDepth,
+}
+
+// the input and the source declaring it for passes reading the depth buffer of the main pass
+// at binding 0, prepend the source to the fragment source and read with load_depth
+pub fn depth_input(sample_count: u32) -> (PostInput, &'static str) {
+    if sample_count > 1 {
+        (PostInput::MultisampledDepth, DEPTH_INPUT_MULTISAMPLED)
+    } else {
+        (PostInput::Depth, DEPTH_INPUT)
+    }
+}
+
+// one fragment shader run over a whole target by a fullscreen triangle
+// the inputs are bound at 0.., followed by a linear sampler and then the optional uniform
+pub struct PostProcessPass {
+    label: String,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    input_count: u32,
+}
+
+impl PostProcessPass {
+    // the fragment source only needs fs_main and its bindings, the vertex stage is prepended
+    pub fn new(
+        device: &wgpu::Device,
+        label: &str,
+        fragment_source: &str,
+        inputs: &[PostInput],
+        has_uniform: bool,
+        target_format: wgpu::TextureFormat,
+    ) -> Self {
+        Self::new_with_blend(
+            device,
+            label,
+            fragment_source,
+            inputs,
+            has_uniform,
+            target_format,
+            wgpu::BlendState::REPLACE,
+        )
+    }
+
+    // blends the output with what is already in the target instead of replacing it
+    pub fn new_with_blend(
+        device: &wgpu::Device,
+        label: &str,
+        fragment_source: &str,
+        inputs: &[PostInput],
+        has_uniform: bool,
+        target_format: wgpu::TextureFormat,
+        blend: wgpu::BlendState,
+    ) -> Self {
+        Self::new_with_options(
+            device,
+            label,
+            fragment_source,
+            inputs,
+            has_uniform,
+            target_format,
+            PipelineOptions {
+                blend,
+                ..PipelineOptions::background()
+            },
+        )
+    }
+
+    // for targets that need more than the blend changed, e.g. multisampled ones
+    pub fn new_with_options(
+        device: &wgpu::Device,
+        label: &str,
+        fragment_source: &str,
+        inputs: &[PostInput],
+        has_uniform: bool,
+        target_format: wgpu::TextureFormat,
+        options: PipelineOptions,
+    ) -> Self {
+        let input_count = inputs.len() as u32;
+        let mut entries = inputs
+            .iter()
+            .enumerate()
+            .map(|(binding, input)| wgpu::BindGroupLayoutEntry {
+                binding: binding as u32,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: match input {
+                        PostInput::Color => wgpu::TextureSampleType::Float { filterable: true },
+                        PostInput::Depth => wgpu::TextureSampleType::Depth,
+                        PostInput::MultisampledDepth => {
+                            wgpu::TextureSampleType::Float { filterable: false }
+                        }
+                    },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: *input == PostInput::MultisampledDepth,
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>();
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: input_count,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Sampler {
+                filtering: true,
+                comparison: false,
+            },
+            count: None,
+        });
+        if has_uniform {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: input_count + 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+        }
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{}.bind_group_layout", label)),
+            entries: &entries,
+        });
+
+        let source = format!("{}\n{}", FULLSCREEN_VERTEX, fragment_source);
+        let shader_module = render_utils::create_shader_module(device, &source, label);
+        let layout = render_utils::create_pipeline_layout(device, label, &[&bind_group_layout]);
+        let pipeline = render_utils::create_render_pipeline_with_options(
+            device,
+            &layout,
+            target_format,
+            None,
+            &[],
+            shader_module,
+            label,
+            options,
+        );
+
+        let sampler = SamplerOptions {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        }
+        .create_sampler(device, Some(label));
+
+        Self {
+            label: label.to_string(),
+            pipeline,
+            bind_group_layout,
+            sampler,
+            input_count,
+        }
+    }
+
+    // inputs in the order they were declared in new
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        inputs: &[&wgpu::TextureView],
+        uniform: Option<&wgpu::Buffer>,
+    ) -> wgpu::BindGroup {
+        assert_eq!(inputs.len() as u32, self.input_count, "{}", self.label);
+        let mut entries = inputs
+            .iter()
+            .enumerate()
+            .map(|(binding, view)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: wgpu::BindingResource::TextureView(view),
+            })
+            .collect::<Vec<_>>();
+        entries.push(wgpu::BindGroupEntry {
+            binding: self.input_count,
+            resource: wgpu::BindingResource::Sampler(&self.sampler),
+        });
+        if let Some(uniform) = uniform {
+            entries.push(wgpu::BindGroupEntry {
+                binding: self.input_count + 1,
+                resource: uniform.as_entire_binding(),
+            });
+        }
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{}.bind_group", self.label)),
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        })
+    }
+
+    // viewport is x, y, width and height in pixels, the whole target if None
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        bind_group: &wgpu::BindGroup,
+        load: wgpu::LoadOp<wgpu::Color>,
+        viewport: Option<[f32; 4]>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.label),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            }],
+            depth_stencil_attachment: None,
+        });
+        if let Some([x, y, width, height]) = viewport {
+            render_pass.set_viewport(x, y, width, height, 0f32, 1f32);
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
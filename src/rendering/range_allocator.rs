@@ -0,0 +1,249 @@
+use std::{collections::HashMap, ops::Range};
+
+// handle to an allocated range, stays valid when the range is moved by defragment()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AllocationId(u64);
+
+// a live allocation that defragment() moved, the data has to be copied from -> to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeMove {
+    pub from: Range<u64>,
+    pub to: Range<u64>,
+}
+
+// first fit free-list allocator over [0, capacity), units are up to the caller
+// (vertices, indices, bytes), no gpu calls so the bookkeeping can be used anywhere
+pub struct RangeAllocator {
+    capacity: u64,
+    // sorted by start, adjacent ranges are always merged
+    free_ranges: Vec<Range<u64>>,
+    allocations: HashMap<AllocationId, Range<u64>>,
+    next_id: u64,
+}
+
+impl RangeAllocator {
+    pub fn new(capacity: u64) -> Self {
+        let mut free_ranges = Vec::new();
+        if capacity > 0 {
+            free_ranges.push(0..capacity);
+        }
+        Self {
+            capacity,
+            free_ranges,
+            allocations: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn allocated(&self) -> u64 {
+        self.capacity - self.free_space()
+    }
+
+    pub fn free_space(&self) -> u64 {
+        self.free_ranges.iter().map(|r| r.end - r.start).sum()
+    }
+
+    pub fn get(&self, id: AllocationId) -> Option<Range<u64>> {
+        self.allocations.get(&id).cloned()
+    }
+
+    // None when no single free range is large enough, see defragment()
+    pub fn allocate(&mut self, size: u64) -> Option<AllocationId> {
+        if size == 0 {
+            return None;
+        }
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|r| r.end - r.start >= size)?;
+        let free = &mut self.free_ranges[index];
+        let range = free.start..free.start + size;
+        free.start += size;
+        if free.start == free.end {
+            self.free_ranges.remove(index);
+        }
+
+        let id = AllocationId(self.next_id);
+        self.next_id += 1;
+        self.allocations.insert(id, range);
+        Some(id)
+    }
+
+    // returns the range that was freed
+    pub fn free(&mut self, id: AllocationId) -> Option<Range<u64>> {
+        let range = self.allocations.remove(&id)?;
+        self.insert_free_range(range.clone());
+        Some(range)
+    }
+
+    // pack all allocations to the start, leaving one free range at the end
+    // moves are returned in ascending order, copying them in order never overwrites
+    // data that still has to be moved
+    pub fn defragment(&mut self) -> Vec<RangeMove> {
+        let mut live = self
+            .allocations
+            .iter()
+            .map(|(id, range)| (*id, range.clone()))
+            .collect::<Vec<_>>();
+        live.sort_by_key(|(_id, range)| range.start);
+
+        let mut moves = Vec::new();
+        let mut cursor = 0u64;
+        for (id, range) in live {
+            let size = range.end - range.start;
+            let to = cursor..cursor + size;
+            if to != range {
+                self.allocations.insert(id, to.clone());
+                moves.push(RangeMove { from: range, to });
+            }
+            cursor += size;
+        }
+        self.free_ranges.clear();
+        if cursor < self.capacity {
+            self.free_ranges.push(cursor..self.capacity);
+        }
+        moves
+    }
+
+    fn insert_free_range(&mut self, range: Range<u64>) {
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|r| r.start > range.start)
+            .unwrap_or(self.free_ranges.len());
+        self.free_ranges.insert(index, range);
+
+        // merge with next
+        if index + 1 < self.free_ranges.len()
+            && self.free_ranges[index].end == self.free_ranges[index + 1].start
+        {
+            self.free_ranges[index].end = self.free_ranges[index + 1].end;
+            self.free_ranges.remove(index + 1);
+        }
+        // merge with previous
+        if index > 0 && self.free_ranges[index - 1].end == self.free_ranges[index].start {
+            self.free_ranges[index - 1].end = self.free_ranges[index].end;
+            self.free_ranges.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_first_fit() {
+        let mut allocator = RangeAllocator::new(100);
+        let a = allocator.allocate(10).unwrap();
+        let b = allocator.allocate(20).unwrap();
+        assert_eq!(allocator.get(a), Some(0..10));
+        assert_eq!(allocator.get(b), Some(10..30));
+        allocator.free(a);
+        // the hole at the start is used first, as long as it fits
+        let c = allocator.allocate(5).unwrap();
+        assert_eq!(allocator.get(c), Some(0..5));
+        let d = allocator.allocate(10).unwrap();
+        assert_eq!(allocator.get(d), Some(30..40));
+        let e = allocator.allocate(5).unwrap();
+        assert_eq!(allocator.get(e), Some(5..10));
+        assert_eq!(allocator.allocated(), 40);
+        assert_eq!(allocator.free_space(), 60);
+    }
+
+    #[test]
+    fn free_merges_adjacent_ranges() {
+        let mut allocator = RangeAllocator::new(40);
+        let ids = (0..4)
+            .map(|_| allocator.allocate(10).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(allocator.free(ids[0]), Some(0..10));
+        assert_eq!(allocator.free(ids[2]), Some(20..30));
+        assert_eq!(allocator.free_ranges, vec![0..10, 20..30]);
+        // merges with both neighbours
+        allocator.free(ids[1]);
+        assert_eq!(allocator.free_ranges, vec![0..30]);
+        // merges with the previous range
+        allocator.free(ids[3]);
+        assert_eq!(allocator.free_ranges, vec![0..40]);
+        // already freed
+        assert_eq!(allocator.free(ids[3]), None);
+        assert_eq!(allocator.get(ids[3]), None);
+        assert!(allocator.allocate(40).is_some());
+    }
+
+    #[test]
+    fn fails_when_no_range_fits() {
+        let mut allocator = RangeAllocator::new(30);
+        let ids = (0..3)
+            .map(|_| allocator.allocate(10).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(allocator.allocate(1), None);
+        allocator.free(ids[0]);
+        allocator.free(ids[2]);
+        // enough free space in total, but split in two ranges
+        assert_eq!(allocator.free_space(), 20);
+        assert_eq!(allocator.allocate(15), None);
+        assert_eq!(allocator.allocate(0), None);
+        assert_eq!(RangeAllocator::new(0).allocate(1), None);
+    }
+
+    #[test]
+    fn defragment_packs_allocations_and_reports_moves() {
+        let mut allocator = RangeAllocator::new(100);
+        let sizes = [10, 20, 30, 10];
+        let ids = sizes
+            .iter()
+            .map(|size| allocator.allocate(*size).unwrap())
+            .collect::<Vec<_>>();
+        // every unit of the buffer holds the index of the allocation it belongs to
+        let mut buffer = vec![u64::MAX; 100];
+        for (index, id) in ids.iter().enumerate() {
+            let range = allocator.get(*id).unwrap();
+            buffer[range.start as usize..range.end as usize].fill(index as u64);
+        }
+        allocator.free(ids[0]);
+        allocator.free(ids[2]);
+        let allocated = allocator.allocated();
+
+        let moves = allocator.defragment();
+        assert_eq!(
+            moves,
+            vec![
+                RangeMove {
+                    from: 10..30,
+                    to: 0..20
+                },
+                RangeMove {
+                    from: 60..70,
+                    to: 20..30
+                },
+            ]
+        );
+        assert_eq!(allocator.get(ids[1]), Some(0..20));
+        assert_eq!(allocator.get(ids[3]), Some(20..30));
+        assert_eq!(allocator.allocated(), allocated);
+        assert_eq!(allocator.free_ranges, vec![30..100]);
+
+        // copying in the returned order moves the data of every allocation into place
+        for range_move in moves.iter() {
+            buffer.copy_within(
+                range_move.from.start as usize..range_move.from.end as usize,
+                range_move.to.start as usize,
+            );
+        }
+        for index in [1, 3].iter() {
+            let range = allocator.get(ids[*index]).unwrap();
+            assert!(buffer[range.start as usize..range.end as usize]
+                .iter()
+                .all(|unit| *unit == *index as u64));
+        }
+        assert!(allocator.allocate(70).is_some());
+        // nothing left to move
+        assert!(allocator.defragment().is_empty());
+    }
+}
@@ -1,5 +1,76 @@
 use wgpu::{BindGroupLayout, ShaderModule};
 
+use crate::rendering::ssao::AMBIENT_FORMAT;
+
+// the second color target of the scene passes, ssao darkens the ambient light drawn into it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AmbientTarget {
+    // not drawn in the scene passes, only one color target
+    None,
+    // drawn in the scene passes without writing the ambient light
+    Keep,
+    // fragment output 1 is the part of output 0 lit by the ambient light
+    Write,
+}
+
+// the parts of a pipeline that differ between otherwise identical pipelines
+#[derive(Copy, Clone, Debug)]
+pub struct PipelineOptions {
+    pub blend: wgpu::BlendState,
+    pub depth_write: bool,
+    pub depth_compare: wgpu::CompareFunction,
+    pub cull_mode: Option<wgpu::Face>,
+    // anything but Fill needs Features::NON_FILL_POLYGON_MODE
+    pub polygon_mode: wgpu::PolygonMode,
+    pub topology: wgpu::PrimitiveTopology,
+    // the shader variant, several fragment stages can share one shader module
+    pub fragment_entry: &'static str,
+    // must match the color and depth attachments of the passes the pipeline is used in
+    pub sample_count: u32,
+    pub ambient_target: AmbientTarget,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            blend: wgpu::BlendState::REPLACE,
+            depth_write: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            fragment_entry: "fs_main",
+            sample_count: 1,
+            ambient_target: AmbientTarget::None,
+        }
+    }
+}
+
+impl PipelineOptions {
+    // blended on top of what is already drawn, still depth tested but not written
+    // so translucent surfaces behind each other all show up
+    pub fn translucent() -> Self {
+        Self {
+            blend: wgpu::BlendState::ALPHA_BLENDING,
+            depth_write: false,
+            depth_compare: wgpu::CompareFunction::Less,
+            cull_mode: None,
+            ..Self::default()
+        }
+    }
+
+    // fills the screen before anything else is drawn, ignores and keeps the depth buffer
+    pub fn background() -> Self {
+        Self {
+            blend: wgpu::BlendState::REPLACE,
+            depth_write: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            cull_mode: None,
+            ..Self::default()
+        }
+    }
+}
+
 pub fn create_render_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
@@ -9,6 +80,44 @@ pub fn create_render_pipeline(
     shader_module: wgpu::ShaderModule,
     label: &str,
 ) -> wgpu::RenderPipeline {
+    create_render_pipeline_with_options(
+        device,
+        layout,
+        color_format,
+        depth_format,
+        vertex_layouts,
+        shader_module,
+        label,
+        PipelineOptions::default(),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_render_pipeline_with_options(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader_module: wgpu::ShaderModule,
+    label: &str,
+    options: PipelineOptions,
+) -> wgpu::RenderPipeline {
+    let mut targets = vec![wgpu::ColorTargetState {
+        format: color_format,
+        blend: Some(options.blend),
+        write_mask: wgpu::ColorWrite::ALL,
+    }];
+    if options.ambient_target != AmbientTarget::None {
+        targets.push(wgpu::ColorTargetState {
+            format: AMBIENT_FORMAT,
+            blend: Some(options.blend),
+            write_mask: match options.ambient_target {
+                AmbientTarget::Write => wgpu::ColorWrite::ALL,
+                _ => wgpu::ColorWrite::empty(),
+            },
+        });
+    }
     let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some(label),
         layout: Some(layout),
@@ -19,32 +128,27 @@ pub fn create_render_pipeline(
         },
         fragment: Some(wgpu::FragmentState {
             module: &shader_module,
-            entry_point: "fs_main",
-            targets: &[wgpu::ColorTargetState {
-                format: color_format,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrite::ALL,
-            }],
+            entry_point: options.fragment_entry,
+            targets: &targets,
         }),
         primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
+            topology: options.topology,
             strip_index_format: None,
             front_face: wgpu::FrontFace::Ccw,
             clamp_depth: false,
-            cull_mode: Some(wgpu::Face::Back),
+            cull_mode: options.cull_mode,
             conservative: false,
-            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-            polygon_mode: wgpu::PolygonMode::Fill,
+            polygon_mode: options.polygon_mode,
         },
         depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
             format: format,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
+            depth_write_enabled: options.depth_write,
+            depth_compare: options.depth_compare,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: options.sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
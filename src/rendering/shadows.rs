@@ -0,0 +1,510 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+
+use crate::{
+    camera::{Camera, OPENGL_TO_WGPU_MATRIX},
+    rendering::{frustum::Frustum, gpu_resources::GpuResources, render_utils},
+    texture::Texture,
+    voxel_tools::chunks::Chunks,
+};
+
+pub const SHADOW_CASCADES: usize = 3;
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowSettings {
+    // no shadows further than this from the camera
+    pub distance: f32,
+    // 0 = evenly spaced splits, 1 = logarithmic splits
+    pub split_lambda: f32,
+    // how far towards the sun casters in front of a cascade are still rendered
+    pub caster_distance: f32,
+    // lookups are pushed this many texels along the surface normal, fights shadow acne
+    pub normal_offset: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            distance: 96f32,
+            split_lambda: 0.75,
+            caster_distance: 64f32,
+            normal_offset: 1.5,
+        }
+    }
+}
+
+// one slice of the view frustum and the light matrix that covers it
+#[derive(Copy, Clone, Debug)]
+pub struct Cascade {
+    pub view_proj: Matrix4<f32>,
+    // distances along the view direction covered by this cascade
+    pub near: f32,
+    pub far: f32,
+    // world units covered by one shadow map texel
+    pub texel_size: f32,
+}
+
+impl Default for Cascade {
+    fn default() -> Self {
+        Self {
+            view_proj: Matrix4::from_scale(1f32),
+            near: 0f32,
+            far: 0f32,
+            texel_size: 0f32,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniform {
+    light_view_proj: [[[f32; 4]; 4]; SHADOW_CASCADES],
+    // world size of a texel for every cascade, scales the normal offset
+    texel_sizes: [f32; 4],
+    // x: size of a texel in uv, y: normal offset in texels
+    params: [f32; 4],
+}
+
+impl ShadowUniform {
+    pub fn new(cascades: &[Cascade; SHADOW_CASCADES], settings: &ShadowSettings) -> Self {
+        let mut uniform = Self {
+            light_view_proj: [[[0f32; 4]; 4]; SHADOW_CASCADES],
+            texel_sizes: [0f32; 4],
+            params: [
+                1f32 / SHADOW_MAP_SIZE as f32,
+                settings.normal_offset,
+                0f32,
+                0f32,
+            ],
+        };
+        for (i, cascade) in cascades.iter().enumerate() {
+            uniform.light_view_proj[i] = cascade.view_proj.into();
+            uniform.texel_sizes[i] = cascade.texel_size;
+        }
+        uniform
+    }
+}
+
+// distances along the view direction where the cascades start and end, count + 1 values
+// blends evenly spaced and logarithmic splits, so close cascades stay small
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (0..=count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            let uniform = near + (far - near) * t;
+            let logarithmic = near * (far / near).powf(t);
+            uniform + (logarithmic - uniform) * lambda
+        })
+        .collect()
+}
+
+// corners of the view frustum between two distances along the view direction
+pub fn frustum_slice_corners(
+    position: Point3<f32>,
+    direction: Vector3<f32>,
+    fovy: cgmath::Rad<f32>,
+    aspect: f32,
+    near: f32,
+    far: f32,
+) -> [Point3<f32>; 8] {
+    let forward = direction.normalize();
+    // looking straight up or down has no horizontal right vector
+    let right = if forward.y.abs() > 0.999 {
+        Vector3::unit_x()
+    } else {
+        forward.cross(Vector3::unit_y()).normalize()
+    };
+    let up = right.cross(forward);
+    let tan_half_fovy = (fovy.0 * 0.5).tan();
+
+    let mut corners = [position; 8];
+    for (i, distance) in [near, far].iter().enumerate() {
+        let half_height = distance * tan_half_fovy;
+        let half_width = half_height * aspect;
+        let center = position + forward * *distance;
+        corners[i * 4] = center - right * half_width - up * half_height;
+        corners[i * 4 + 1] = center + right * half_width - up * half_height;
+        corners[i * 4 + 2] = center + right * half_width + up * half_height;
+        corners[i * 4 + 3] = center - right * half_width + up * half_height;
+    }
+    corners
+}
+
+// orthographic matrix looking along the sunlight that contains the bounding sphere of the corners
+// the sphere keeps its size when the camera rotates and its center is snapped to whole texels,
+// so shadow edges don't shimmer while moving
+// returns the matrix and the world size of a texel
+pub fn fit_light_matrix(
+    corners: &[Point3<f32>; 8],
+    sun_direction: Vector3<f32>,
+    map_size: u32,
+    caster_distance: f32,
+) -> (Matrix4<f32>, f32) {
+    let center = Point3::centroid(corners);
+    let radius = corners
+        .iter()
+        .map(|corner| (corner - center).magnitude())
+        .fold(0f32, f32::max);
+    // rounding hides float noise in the radius, which would change the texel size every frame
+    let radius = (radius * 16f32).ceil() / 16f32;
+    // snapping moves the center by up to a texel, one texel of padding on each side keeps
+    // the whole sphere inside
+    let texel_size = radius * 2f32 / (map_size - 2) as f32;
+    let half_size = radius + texel_size;
+
+    let sun_direction = sun_direction.normalize();
+    let up = if sun_direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    };
+    let view = Matrix4::look_to_rh(Point3::origin(), -sun_direction, up);
+    let light_center = view * center.to_homogeneous();
+    let x = (light_center.x / texel_size).floor() * texel_size;
+    let y = (light_center.y / texel_size).floor() * texel_size;
+    // view space looks down -z, the near plane is pulled towards the sun for casters
+    // outside of the slice
+    let projection = cgmath::ortho(
+        x - half_size,
+        x + half_size,
+        y - half_size,
+        y + half_size,
+        -light_center.z - radius - caster_distance,
+        -light_center.z + radius,
+    );
+    (OPENGL_TO_WGPU_MATRIX * projection * view, texel_size)
+}
+
+// splits the camera view up to the shadow distance and fits a light matrix to every slice
+pub fn fit_cascades(
+    camera: &Camera,
+    sun_direction: Vector3<f32>,
+    settings: &ShadowSettings,
+    map_size: u32,
+) -> [Cascade; SHADOW_CASCADES] {
+    let far = settings.distance.min(camera.z_far);
+    let splits = cascade_splits(camera.z_near, far, SHADOW_CASCADES, settings.split_lambda);
+    let fovy: cgmath::Rad<f32> = cgmath::Deg(camera.fovy).into();
+
+    let mut cascades = [Cascade::default(); SHADOW_CASCADES];
+    for (i, cascade) in cascades.iter_mut().enumerate() {
+        let corners = frustum_slice_corners(
+            camera.position,
+            camera.direction(),
+            fovy,
+            camera.aspect,
+            splits[i],
+            splits[i + 1],
+        );
+        let (view_proj, texel_size) =
+            fit_light_matrix(&corners, sun_direction, map_size, settings.caster_distance);
+        *cascade = Cascade {
+            view_proj,
+            near: splits[i],
+            far: splits[i + 1],
+            texel_size,
+        };
+    }
+    cascades
+}
+
+// depth maps of the sun for a few slices of the view, sampled by the voxel shader
+pub struct ShadowMaps {
+    pub settings: ShadowSettings,
+    pub cascades: [Cascade; SHADOW_CASCADES],
+    #[allow(dead_code)]
+    texture: wgpu::Texture,
+    // one layer of the texture each, rendered to separately
+    cascade_views: Vec<wgpu::TextureView>,
+    cascade_buffers: Vec<wgpu::Buffer>,
+    cascade_bind_groups: Vec<wgpu::BindGroup>,
+    uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ShadowMaps {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        cascade_layout: &wgpu::BindGroupLayout,
+        settings: ShadowSettings,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow_maps"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: SHADOW_CASCADES as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        });
+        let cascade_views = (0..SHADOW_CASCADES as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("shadow_cascade_view"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("shadow_maps_view"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = Texture::create_comparison_sampler(device, "shadow_maps_sampler");
+
+        let cascade_buffers = (0..SHADOW_CASCADES)
+            .map(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("shadow_cascade_buffer"),
+                    size: std::mem::size_of::<[[f32; 4]; 4]>() as u64,
+                    usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect::<Vec<_>>();
+        let cascade_bind_groups = cascade_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("shadow_cascade_bind_group"),
+                    layout: cascade_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                })
+            })
+            .collect();
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shadow_uniform_buffer"),
+            size: std::mem::size_of::<ShadowUniform>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shadow_maps_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        Self {
+            settings,
+            cascades: [Cascade::default(); SHADOW_CASCADES],
+            texture,
+            cascade_views,
+            cascade_buffers,
+            cascade_bind_groups,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    // uniform, depth texture array and comparison sampler, as read by voxel.wgsl
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("shadow_maps_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: true,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    // light matrix of the cascade being rendered
+    pub fn create_cascade_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        render_utils::create_bind_group_layout(
+            device,
+            "shadow_cascade_bind_group_layout",
+            0,
+            wgpu::ShaderStage::VERTEX,
+        )
+    }
+
+    // refits the cascades to the camera, call once per frame before rendering
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, sun_direction: Vector3<f32>) {
+        self.cascades = fit_cascades(camera, sun_direction, &self.settings, SHADOW_MAP_SIZE);
+        for (cascade, buffer) in self.cascades.iter().zip(self.cascade_buffers.iter()) {
+            let view_proj: [[f32; 4]; 4] = cascade.view_proj.into();
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[view_proj]));
+        }
+        let uniform = ShadowUniform::new(&self.cascades, &self.settings);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // the volumes chunks have to be in to cast a shadow into the view
+    pub fn caster_frustums(&self) -> Vec<Frustum> {
+        self.cascades
+            .iter()
+            .map(|cascade| Frustum::from_view_projection(cascade.view_proj))
+            .collect()
+    }
+
+    // renders the chunk meshes picked by Chunks::prepare_shadow_draw into every cascade
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        chunks: &Chunks,
+        gpu_resources: &GpuResources,
+    ) {
+        for (view, bind_group) in self
+            .cascade_views
+            .iter()
+            .zip(self.cascade_bind_groups.iter())
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("shadow render pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            render_pass.set_pipeline(pipeline);
+            chunks.draw_shadows(&mut render_pass, bind_group, gpu_resources);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() <= b.abs() * 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn splits_are_monotonic_and_cover_near_to_far() {
+        let (near, far) = (0.1, 96.0);
+        for lambda in [0.0, 0.5, 0.75, 1.0].iter() {
+            let splits = cascade_splits(near, far, SHADOW_CASCADES, *lambda);
+            assert_eq!(splits.len(), SHADOW_CASCADES + 1);
+            assert_close(splits[0], near);
+            assert_close(splits[SHADOW_CASCADES], far);
+            assert!(
+                splits.windows(2).all(|pair| pair[0] < pair[1]),
+                "{:?}",
+                splits
+            );
+        }
+        // evenly spaced
+        let splits = cascade_splits(near, far, SHADOW_CASCADES, 0.0);
+        let step = (far - near) / SHADOW_CASCADES as f32;
+        for (i, split) in splits.iter().enumerate() {
+            assert_close(*split, near + step * i as f32);
+        }
+        // every cascade covers the same ratio of distances
+        let splits = cascade_splits(near, far, SHADOW_CASCADES, 1.0);
+        let ratio = (far / near).powf(1.0 / SHADOW_CASCADES as f32);
+        for pair in splits.windows(2) {
+            assert_close(pair[1] / pair[0], ratio);
+        }
+    }
+
+    // deterministic pseudo random numbers in -1..1
+    fn random_numbers(mut state: u64) -> impl FnMut() -> f32 {
+        move || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        }
+    }
+
+    #[test]
+    fn slices_project_inside_the_light_matrix() {
+        let mut random = random_numbers(7);
+        let fovy = cgmath::Rad::from(cgmath::Deg(45.0));
+        let splits = cascade_splits(0.1, 96.0, SHADOW_CASCADES, 0.75);
+        // snapping to texels moves the light matrix, corners right at the edge of the
+        // bounding sphere are rare so many views are tried
+        for view in 0..20_000 {
+            let position = Point3::new(random() * 5000.0, random() * 200.0, random() * 5000.0);
+            let direction = Vector3::new(random(), random(), random());
+            // straight down and low sun included
+            let sun_direction = match view {
+                0 => Vector3::new(0.0, -1.0, 0.0),
+                1 => Vector3::new(-1.0, -0.05, 0.0),
+                _ => Vector3::new(random(), -random().abs() - 0.01, random()),
+            };
+            for slice in splits.windows(2) {
+                let corners = frustum_slice_corners(
+                    position,
+                    direction,
+                    fovy,
+                    16.0 / 9.0,
+                    slice[0],
+                    slice[1],
+                );
+                let (view_proj, texel_size) =
+                    fit_light_matrix(&corners, sun_direction, SHADOW_MAP_SIZE, 64.0);
+                assert!(texel_size > 0.0);
+                for corner in corners.iter() {
+                    let clip = view_proj * corner.to_homogeneous();
+                    let ndc = clip.truncate() / clip.w;
+                    assert!(
+                        ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0,
+                        "{:?} is outside of the light matrix at {:?}",
+                        corner,
+                        ndc
+                    );
+                    assert!(ndc.z >= 0.0 && ndc.z <= 1.0, "{:?} depth {}", corner, ndc.z);
+                }
+            }
+        }
+    }
+}
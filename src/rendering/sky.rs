@@ -0,0 +1,110 @@
+use cgmath::SquareMatrix;
+
+use crate::{
+    camera::Camera,
+    rendering::render_utils::{self, AmbientTarget, PipelineOptions},
+    texture,
+    time_of_day::Sky,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    inverse_projection_view: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+    // the sun or the moon, drawn as a disc, w: star visibility
+    sun_direction: [f32; 4],
+    sun_color: [f32; 4],
+    zenith_color: [f32; 4],
+    horizon_color: [f32; 4],
+}
+
+impl SkyUniform {
+    pub fn new(camera: &Camera, sky: &Sky) -> Self {
+        let inverse_projection_view = camera
+            .build_view_projection_matrix()
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity);
+        Self {
+            inverse_projection_view: inverse_projection_view.into(),
+            camera_position: camera.position.to_homogeneous().into(),
+            sun_direction: sky.light_direction.extend(sky.star_visibility).into(),
+            sun_color: sky.light_color.extend(1f32).into(),
+            zenith_color: sky.zenith_color.extend(1f32).into(),
+            horizon_color: sky.horizon_color.extend(1f32).into(),
+        }
+    }
+}
+
+// gradient from horizon to zenith with a sun or moon disc and stars, drawn behind everything
+pub struct SkyPass {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SkyPass {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let bind_group_layout = render_utils::create_bind_group_layout(
+            device,
+            "sky_bind_group_layout",
+            0,
+            wgpu::ShaderStage::FRAGMENT,
+        );
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sky_uniform_buffer"),
+            size: std::mem::size_of::<SkyUniform>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sky_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader_module =
+            render_utils::create_shader_module(device, include_str!("sky.wgsl"), "sky_shader");
+        let layout =
+            render_utils::create_pipeline_layout(device, "sky_pipeline", &[&bind_group_layout]);
+        let pipeline = render_utils::create_render_pipeline_with_options(
+            device,
+            &layout,
+            color_format,
+            Some(texture::Texture::DEPTH_FORMAT),
+            &[],
+            shader_module,
+            "sky_pipeline",
+            PipelineOptions {
+                sample_count,
+                ambient_target: AmbientTarget::Keep,
+                ..PipelineOptions::background()
+            },
+        );
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera, sky: &Sky) {
+        let uniform = SkyUniform::new(camera, sky);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // draw first in the main pass, it doesn't touch the depth buffer
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,60 @@
+[[block]]
+struct SkyUniform {
+    inverse_projection_view: mat4x4<f32>;
+    camera_position: vec4<f32>;
+    // the sun or the moon, w: star visibility
+    sun_direction: vec4<f32>;
+    sun_color: vec4<f32>;
+    zenith_color: vec4<f32>;
+    horizon_color: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> u_sky: SkyUniform;
+
+struct VertexOutput {
+    [[builtin(position)]] builtin_position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+// one triangle covering the screen, no vertex buffer needed
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index % 2u) * 2u), f32((index / 2u) * 2u));
+    out.ndc = uv * 2.0 - vec2<f32>(1.0);
+    out.builtin_position = vec4<f32>(out.ndc, 1.0, 1.0);
+    return out;
+}
+
+fn hash(cell: vec3<f32>) -> f32 {
+    return fract(sin(dot(cell, vec3<f32>(12.9898, 78.233, 45.164))) * 43758.5453);
+}
+
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    let far = u_sky.inverse_projection_view * vec4<f32>(in.ndc, 1.0, 1.0);
+    let dir = normalize(far.xyz / far.w - u_sky.camera_position.xyz);
+
+    let height = clamp(dir.y, 0.0, 1.0);
+    var color: vec3<f32> = mix(u_sky.horizon_color.xyz, u_sky.zenith_color.xyz, vec3<f32>(sqrt(height)));
+    // the ground below the horizon is darker
+    if (dir.y < 0.0) {
+        color = u_sky.horizon_color.xyz * mix(1.0, 0.5, clamp(-dir.y * 4.0, 0.0, 1.0));
+    }
+
+    let sun_dot = dot(dir, u_sky.sun_direction.xyz);
+    let disc = clamp((sun_dot - 0.9995) / 0.0003, 0.0, 1.0);
+    let glow = pow(max(sun_dot, 0.0), 64.0) * 0.3;
+    color = color + u_sky.sun_color.xyz * (disc + glow);
+
+    // a random sparse subset of direction cells lights up, fading out towards the horizon
+    let star = step(0.998, hash(floor(dir * 200.0)));
+    color = color + vec3<f32>(star * u_sky.sun_direction.w * clamp(dir.y * 5.0, 0.0, 1.0));
+
+    return vec4<f32>(color, 1.0);
+}
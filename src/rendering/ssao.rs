@@ -0,0 +1,367 @@
+use cgmath::{InnerSpace, SquareMatrix, Vector3};
+
+use crate::{
+    camera::Camera,
+    rendering::{
+        post_process::{self, PostInput, PostProcessPass},
+        render_utils::PipelineOptions,
+    },
+    texture::Texture,
+};
+
+// the size of the kernel array in ssao.wgsl
+pub const MAX_SSAO_SAMPLES: usize = 32;
+// occlusion only needs one channel
+pub const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+// the ambient light is hdr like the scene it is part of
+pub const AMBIENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SsaoQuality {
+    Off,
+    // 8 samples at half resolution
+    Low,
+    // 16 samples at half resolution
+    Medium,
+    // 32 samples at full resolution
+    High,
+}
+
+impl SsaoQuality {
+    pub fn sample_count(&self) -> u32 {
+        match self {
+            SsaoQuality::Off => 0,
+            SsaoQuality::Low => 8,
+            SsaoQuality::Medium => 16,
+            SsaoQuality::High => MAX_SSAO_SAMPLES as u32,
+        }
+    }
+
+    // the window size is divided by this for the occlusion targets
+    pub fn resolution_divisor(&self) -> u32 {
+        match self {
+            SsaoQuality::High => 1,
+            _ => 2,
+        }
+    }
+
+    // cycles through all levels, wrapping to off
+    pub fn next(&self) -> Self {
+        match self {
+            SsaoQuality::Off => SsaoQuality::Low,
+            SsaoQuality::Low => SsaoQuality::Medium,
+            SsaoQuality::Medium => SsaoQuality::High,
+            SsaoQuality::High => SsaoQuality::Off,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SsaoSettings {
+    pub quality: SsaoQuality,
+    // world units around a point searched for occluders
+    pub radius: f32,
+    // keeps flat surfaces from occluding themselves
+    pub bias: f32,
+    // the occlusion is raised to this power, higher is darker
+    pub intensity: f32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            quality: SsaoQuality::Medium,
+            radius: 1f32,
+            bias: 0.05,
+            intensity: 1.5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SsaoUniform {
+    projection: [[f32; 4]; 4],
+    inverse_projection: [[f32; 4]; 4],
+    // x: radius, y: bias, z: intensity, w: sample count
+    params: [f32; 4],
+    kernel: [[f32; 4]; MAX_SSAO_SAMPLES],
+}
+
+impl SsaoUniform {
+    pub fn new(camera: &Camera, settings: &SsaoSettings) -> Self {
+        let projection = camera.build_projection_matrix();
+        let inverse_projection = projection
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity);
+        let mut kernel = [[0f32; 4]; MAX_SSAO_SAMPLES];
+        let sample_count = settings.quality.sample_count() as usize;
+        for (slot, sample) in kernel.iter_mut().zip(ssao_kernel(sample_count)) {
+            *slot = sample.extend(0f32).into();
+        }
+        Self {
+            projection: projection.into(),
+            inverse_projection: inverse_projection.into(),
+            params: [
+                settings.radius,
+                settings.bias,
+                settings.intensity,
+                sample_count as f32,
+            ],
+            kernel,
+        }
+    }
+}
+
+// points in the unit hemisphere around +z, spread evenly on a spiral
+// later points lie further out so most samples test the geometry close by
+pub fn ssao_kernel(count: usize) -> Vec<Vector3<f32>> {
+    let golden_angle = std::f32::consts::PI * (3f32 - 5f32.sqrt());
+    (0..count)
+        .map(|i| {
+            let t = (i as f32 + 0.5) / count as f32;
+            // keep away from the tangent plane, those samples only find the surface itself
+            let z = 0.15 + 0.85 * (1f32 - t);
+            let ring = (1f32 - z * z).sqrt();
+            let (sin, cos) = (i as f32 * golden_angle).sin_cos();
+            let scale = 0.1 + 0.9 * t * t;
+            Vector3::new(cos * ring, sin * ring, z).normalize() * scale
+        })
+        .collect()
+}
+
+// textures sized to the window and the quality, recreated on resize
+struct SsaoTargets {
+    // the second color attachment of the scene passes, see AmbientTarget
+    ambient: Texture,
+    // drawn into instead of ambient with msaa on, the main pass resolves it
+    multisampled_ambient: Option<Texture>,
+    occlusion: Texture,
+    blurred: Texture,
+    occlusion_bind_group: wgpu::BindGroup,
+    blur_bind_group: wgpu::BindGroup,
+    apply_bind_group: wgpu::BindGroup,
+}
+
+// ambient occlusion from the depth of the opaque geometry, the occluded part of the ambient
+// light is subtracted from the hdr scene before anything translucent is drawn
+// so voxels and models are both darkened in corners, but direct and emitted light are not
+pub struct Ssao {
+    pub settings: SsaoSettings,
+    sample_count: u32,
+    uniform_buffer: wgpu::Buffer,
+    occlusion_pass: PostProcessPass,
+    blur_pass: PostProcessPass,
+    apply_pass: PostProcessPass,
+    targets: SsaoTargets,
+}
+
+impl Ssao {
+    pub fn new(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        depth_view: &wgpu::TextureView,
+        scene_format: wgpu::TextureFormat,
+        sample_count: u32,
+        settings: SsaoSettings,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ssao_uniform_buffer"),
+            size: std::mem::size_of::<SsaoUniform>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (depth_input, depth_source) = post_process::depth_input(sample_count);
+        let occlusion_pass = PostProcessPass::new(
+            device,
+            "ssao",
+            &format!("{}\n{}", depth_source, include_str!("ssao.wgsl")),
+            &[depth_input],
+            true,
+            OCCLUSION_FORMAT,
+        );
+        let blur_pass = PostProcessPass::new(
+            device,
+            "ssao_blur",
+            include_str!("ssao_blur.wgsl"),
+            &[PostInput::Color],
+            false,
+            OCCLUSION_FORMAT,
+        );
+        // scene - occluded ambient light
+        let subtract = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::ReverseSubtract,
+        };
+        let keep = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        // every sample of a multisampled scene loses the occluded ambient light of its pixel
+        let apply_pass = PostProcessPass::new_with_options(
+            device,
+            "ssao_apply",
+            include_str!("ssao_apply.wgsl"),
+            &[PostInput::Color, PostInput::Color],
+            false,
+            scene_format,
+            PipelineOptions {
+                blend: wgpu::BlendState {
+                    color: subtract,
+                    alpha: keep,
+                },
+                sample_count,
+                ..PipelineOptions::background()
+            },
+        );
+        let targets = Self::create_targets(
+            device,
+            sc_desc,
+            depth_view,
+            sample_count,
+            settings.quality,
+            &occlusion_pass,
+            &blur_pass,
+            &apply_pass,
+            &uniform_buffer,
+        );
+
+        Self {
+            settings,
+            sample_count,
+            uniform_buffer,
+            occlusion_pass,
+            blur_pass,
+            apply_pass,
+            targets,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_targets(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        depth_view: &wgpu::TextureView,
+        sample_count: u32,
+        quality: SsaoQuality,
+        occlusion_pass: &PostProcessPass,
+        blur_pass: &PostProcessPass,
+        apply_pass: &PostProcessPass,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> SsaoTargets {
+        let ambient = Texture::create_render_target(
+            device,
+            sc_desc.width,
+            sc_desc.height,
+            AMBIENT_FORMAT,
+            "ssao_ambient",
+        );
+        let multisampled_ambient = if sample_count > 1 {
+            Some(Texture::create_render_target_with_samples(
+                device,
+                sc_desc.width,
+                sc_desc.height,
+                AMBIENT_FORMAT,
+                sample_count,
+                "ssao_ambient_multisampled",
+            ))
+        } else {
+            None
+        };
+        let divisor = quality.resolution_divisor();
+        let (width, height) = (sc_desc.width / divisor, sc_desc.height / divisor);
+        let occlusion =
+            Texture::create_render_target(device, width, height, OCCLUSION_FORMAT, "ssao");
+        let blurred =
+            Texture::create_render_target(device, width, height, OCCLUSION_FORMAT, "ssao_blurred");
+        SsaoTargets {
+            occlusion_bind_group: occlusion_pass.create_bind_group(
+                device,
+                &[depth_view],
+                Some(uniform_buffer),
+            ),
+            blur_bind_group: blur_pass.create_bind_group(device, &[&occlusion.view], None),
+            apply_bind_group: apply_pass.create_bind_group(
+                device,
+                &[&blurred.view, &ambient.view],
+                None,
+            ),
+            ambient,
+            multisampled_ambient,
+            occlusion,
+            blurred,
+        }
+    }
+
+    // also after changing the quality, the resolution depends on it
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.targets = Self::create_targets(
+            device,
+            sc_desc,
+            depth_view,
+            self.sample_count,
+            self.settings.quality,
+            &self.occlusion_pass,
+            &self.blur_pass,
+            &self.apply_pass,
+            &self.uniform_buffer,
+        );
+    }
+
+    // the ambient attachment of the scene passes, cleared to black by the first one
+    pub fn ambient_view(&self) -> &wgpu::TextureView {
+        match &self.targets.multisampled_ambient {
+            Some(multisampled) => &multisampled.view,
+            None => &self.targets.ambient.view,
+        }
+    }
+
+    // the main pass resolves the ambient light before render reads it
+    pub fn ambient_resolve_target(&self) -> Option<&wgpu::TextureView> {
+        self.targets
+            .multisampled_ambient
+            .as_ref()
+            .map(|_| &self.targets.ambient.view)
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        let uniform = SsaoUniform::new(camera, &self.settings);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // run between the opaque and the translucent geometry, the depth must not be attached
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, scene: &wgpu::TextureView) {
+        if self.settings.quality == SsaoQuality::Off {
+            return;
+        }
+        let targets = &self.targets;
+        self.occlusion_pass.render(
+            encoder,
+            &targets.occlusion.view,
+            &targets.occlusion_bind_group,
+            wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+            None,
+        );
+        self.blur_pass.render(
+            encoder,
+            &targets.blurred.view,
+            &targets.blur_bind_group,
+            wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+            None,
+        );
+        self.apply_pass.render(
+            encoder,
+            scene,
+            &targets.apply_bind_group,
+            wgpu::LoadOp::Load,
+            None,
+        );
+    }
+}
@@ -0,0 +1,97 @@
+// t_depth, depth_size and load_depth are prepended, see post_process::depth_input
+[[group(0), binding(1)]]
+var s_post: sampler;
+
+[[block]]
+struct SsaoUniform {
+    projection: mat4x4<f32>;
+    inverse_projection: mat4x4<f32>;
+    // x: radius, y: bias, z: intensity, w: sample count
+    params: vec4<f32>;
+    // hemisphere samples around +z, denser towards the center
+    kernel: [[stride(16)]] array<vec4<f32>, 32>;
+};
+
+[[group(0), binding(2)]]
+var<uniform> u_ssao: SsaoUniform;
+
+fn view_position(texel: vec2<i32>) -> vec3<f32> {
+    let size = depth_size();
+    let clamped = max(min(texel, size - vec2<i32>(1)), vec2<i32>(0));
+    let depth = load_depth(clamped);
+    let uv = (vec2<f32>(clamped) + vec2<f32>(0.5)) / vec2<f32>(size);
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let view = u_ssao.inverse_projection * ndc;
+    return view.xyz / view.w;
+}
+
+// of the two neighbours the one on the same surface, so edges don't get bent normals
+fn closest_difference(center: vec3<f32>, before: vec3<f32>, after: vec3<f32>) -> vec3<f32> {
+    if (abs(after.z - center.z) < abs(center.z - before.z)) {
+        return after - center;
+    }
+    return center - before;
+}
+
+fn hash(cell: vec2<f32>) -> f32 {
+    return fract(sin(dot(cell, vec2<f32>(12.9898, 78.233))) * 43758.5453);
+}
+
+fn smooth_range(x: f32) -> f32 {
+    let t = clamp(x, 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+// 1 = open, 0 = fully occluded
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    let size = depth_size();
+    let texel = vec2<i32>(in.uv * vec2<f32>(size));
+    let depth = load_depth(max(min(texel, size - vec2<i32>(1)), vec2<i32>(0)));
+    // the sky
+    if (depth >= 1.0) {
+        return vec4<f32>(1.0);
+    }
+
+    // normals are rebuilt from the neighbouring depths, in view space
+    let center = view_position(texel);
+    let dx = closest_difference(center, view_position(texel - vec2<i32>(1, 0)), view_position(texel + vec2<i32>(1, 0)));
+    let dy = closest_difference(center, view_position(texel - vec2<i32>(0, 1)), view_position(texel + vec2<i32>(0, 1)));
+    let normal = normalize(cross(dy, dx));
+
+    // a random rotation repeating every 4x4 pixels, removed again by the blur
+    let tile = floor(in.builtin_position.xy % vec2<f32>(4.0));
+    let angle = hash(tile) * 6.2831853;
+    let random = vec3<f32>(cos(angle), sin(angle), 0.0);
+    let tangent = normalize(random - normal * dot(random, normal));
+    let bitangent = cross(normal, tangent);
+
+    let radius = u_ssao.params.x;
+    let bias = u_ssao.params.y;
+    let sample_count = i32(u_ssao.params.w);
+    var occlusion: f32 = 0.0;
+    var i: i32 = 0;
+    loop {
+        if (i >= sample_count) {
+            break;
+        }
+        let k = u_ssao.kernel[i].xyz;
+        let sample_position = center + (tangent * k.x + bitangent * k.y + normal * k.z) * radius;
+        let clip = u_ssao.projection * vec4<f32>(sample_position, 1.0);
+        let ndc = clip.xy / clip.w;
+        let sample_uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        let occluder = view_position(vec2<i32>(sample_uv * vec2<f32>(size)));
+        // geometry far in front of the sample doesn't occlude it
+        let range = smooth_range(radius / max(abs(center.z - occluder.z), 0.0001));
+        if (occluder.z >= sample_position.z + bias) {
+            occlusion = occlusion + range;
+        }
+        continuing {
+            i = i + 1;
+        }
+    }
+    let open = 1.0 - occlusion / f32(max(sample_count, 1));
+    return vec4<f32>(vec3<f32>(pow(open, u_ssao.params.z)), 1.0);
+}
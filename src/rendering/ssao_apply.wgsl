@@ -0,0 +1,16 @@
+[[group(0), binding(0)]]
+var t_occlusion: texture_2d<f32>;
+[[group(0), binding(1)]]
+var t_ambient: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_post: sampler;
+
+// subtracted from the scene by the blend state, the occlusion is upscaled by the sampler
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    let occlusion = textureSample(t_occlusion, s_post, in.uv).r;
+    let ambient = textureSample(t_ambient, s_post, in.uv).rgb;
+    return vec4<f32>(ambient * (1.0 - occlusion), 0.0);
+}
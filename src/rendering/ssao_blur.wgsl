@@ -0,0 +1,25 @@
+[[group(0), binding(0)]]
+var t_occlusion: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_post: sampler;
+
+// 4x4 box, the size of the noise tile
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    let texel = vec2<f32>(1.0) / vec2<f32>(textureDimensions(t_occlusion));
+    var result: f32 = 0.0;
+    var i: i32 = 0;
+    loop {
+        if (i >= 16) {
+            break;
+        }
+        let offset = vec2<f32>(f32(i % 4) - 1.5, f32(i / 4) - 1.5) * texel;
+        result = result + textureSample(t_occlusion, s_post, in.uv + offset).r;
+        continuing {
+            i = i + 1;
+        }
+    }
+    return vec4<f32>(vec3<f32>(result / 16.0), 1.0);
+}
@@ -0,0 +1,39 @@
+[[group(0), binding(0)]]
+var t_scene: texture_2d<f32>;
+[[group(0), binding(1)]]
+var t_bloom: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_post: sampler;
+
+[[block]]
+struct HdrUniform {
+    // x: exposure, y: bloom intensity, z: bloom threshold, w: tonemapper (0 aces, 1 reinhard)
+    params: vec4<f32>;
+};
+
+[[group(0), binding(3)]]
+var<uniform> u_hdr: HdrUniform;
+
+// fitted curve by Krzysztof Narkowicz
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let mapped = (x * (2.51 * x + vec3<f32>(0.03))) / (x * (2.43 * x + vec3<f32>(0.59)) + vec3<f32>(0.14));
+    return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+    return x / (vec3<f32>(1.0) + x);
+}
+
+// the target is srgb, so the result stays linear
+[[stage(fragment)]]
+fn fs_main(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    let scene = textureSample(t_scene, s_post, in.uv).rgb;
+    let bloom = textureSample(t_bloom, s_post, in.uv).rgb;
+    let hdr = (scene + bloom * u_hdr.params.y) * u_hdr.params.x;
+    if (u_hdr.params.w < 0.5) {
+        return vec4<f32>(aces(hdr), 1.0);
+    }
+    return vec4<f32>(reinhard(hdr), 1.0);
+}
@@ -64,14 +64,17 @@ impl VertexDesc for VertexInstanceRaw {
 pub struct VertexInstance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
+    // uniform, so the inverse transpose still works for normals
+    pub scale: f32,
 }
 
 impl VertexInstance {
     pub fn to_raw(&self) -> VertexInstanceRaw {
         // needed to make invert() and transpose() available
         use cgmath::{Matrix, SquareMatrix};
-        let model =
-            cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation);
+        let model = cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_scale(self.scale);
         let normal_matrix = model
             .invert()
             .expect("can't inverse model matrix")
@@ -54,6 +54,9 @@ fn vs_main(
 struct LightUniform {
     position: vec3<f32>;
     color: vec3<f32>;
+    sun_direction: vec3<f32>;
+    sun_color: vec3<f32>;
+    ambient_color: vec3<f32>;
 };
 
 [[group(2), binding(0)]]
@@ -62,31 +65,37 @@ var<uniform> u_light: LightUniform;
 [[group(1), binding(0)]] var t_diffuse: texture_2d<f32>;
 [[group(1), binding(1)]] var s_diffuse: sampler;
 
+struct SceneOutput {
+    [[location(0)]] color: vec4<f32>;
+    // the part of color lit by the ambient light, darkened by ssao
+    [[location(1)]] ambient: vec4<f32>;
+};
+
 [[stage(fragment), early_depth_test]]
 fn fs_main(
     in: VertexOutput,
-) -> [[location(0)]] vec4<f32> {
-    let ambient_strength = 0.01;
-    let ambient_color = u_light.color * ambient_strength;
+) -> SceneOutput {
+    let ambient_color = u_light.ambient_color;
 
     let normal = normalize(in.normal);
-    let light_dir = normalize(u_light.position - in.position);
-    let diffuse_strength = max(dot(normal, light_dir), 0.0);
-    let diffuse_color = u_light.color * diffuse_strength;
 
     //let view_dir = normalize(u_camera.position - vec3<f32>(in.builtin_position.xyz));
     let view_dir = normalize(u_camera.position - in.position);
-    let half_dir = normalize(view_dir + light_dir);
-
-    let specular_strength = pow(max(dot(normal, half_dir), 0.0), 32.0);
-    let specular_color = specular_strength * u_light.color;
-    //let specular_color = 0.0;
+    let point_color = point_lighting(in.position, normal, view_dir);
 
     var surface_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.vex_coords);
     surface_color = surface_color * vec4<f32>(in.diffuse_color, 1.0);
 
-    let result = (diffuse_color) * surface_color.xyz;
+    let sun_color = u_light.sun_color * max(dot(normal, u_light.sun_direction), 0.0);
 
-    return vec4<f32>(result, surface_color.a);
+    let result = (ambient_color + point_color + sun_color) * surface_color.xyz;
+
+    let view_distance = distance(in.position, u_camera.position);
+    var out: SceneOutput;
+    out.color = vec4<f32>(apply_fog(result, view_distance), surface_color.a);
+    // the fog covers the ambient light like the rest
+    let clear = 1.0 - fog_amount(view_distance);
+    out.ambient = vec4<f32>(ambient_color * surface_color.xyz * clear, surface_color.a);
+    return out;
     //return surface_color * vec4<f32>(light_ambient_color, 1.0);
 }
\ No newline at end of file
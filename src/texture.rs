@@ -1,9 +1,103 @@
 use anyhow::*;
 use image::GenericImageView;
 use std::path::Path;
-use wgpu::util::DeviceExt;
 
-use crate::color::Color;
+use crate::{color::Color, rendering::mipmaps};
+
+// how the texel values should be interpreted
+// colors are usually authored in srgb, data like normal maps is linear
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerOptions {
+    pub address_mode: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // 1 turns anisotropic filtering off, otherwise a power of two up to 16
+    // ignored by wgpu when the adapter doesn't support it
+    pub anisotropy: u8,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl SamplerOptions {
+    pub fn trilinear(anisotropy: u8) -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy,
+        }
+    }
+
+    pub fn create_sampler(&self, device: &wgpu::Device, label: Option<&str>) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: std::num::NonZeroU8::new(self.anisotropy).filter(|a| a.get() > 1),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TextureOptions {
+    pub color_space: ColorSpace,
+    // generate the full mip chain on the cpu
+    pub mipmaps: bool,
+    pub sampler: SamplerOptions,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            color_space: ColorSpace::Srgb,
+            mipmaps: false,
+            sampler: SamplerOptions::default(),
+        }
+    }
+}
+
+impl TextureOptions {
+    // for textures seen at a distance, avoids shimmering
+    pub fn mipmapped() -> Self {
+        Self {
+            color_space: ColorSpace::Srgb,
+            mipmaps: true,
+            sampler: SamplerOptions::trilinear(16),
+        }
+    }
+}
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -52,62 +146,59 @@ impl Texture {
         height: u32,
         label: Option<&str>,
     ) -> Result<Self> {
-        let texture_size = wgpu::Extent3d {
+        Self::from_bytes_with_options(
+            device,
+            queue,
+            bytes,
             width,
             height,
-            depth_or_array_layers: 1,
-        };
-
-        let texture_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label,
-            contents: bytes,
-            usage: wgpu::BufferUsage::COPY_SRC,
-        });
+            &TextureOptions::default(),
+        )
+    }
 
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("temp texture encoder"),
-        });
+    // bytes are tightly packed rgba8
+    pub fn from_bytes_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        width: u32,
+        height: u32,
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Result<Self> {
+        let levels = if options.mipmaps {
+            let image = image::RgbaImage::from_raw(width, height, bytes.to_vec())
+                .context("texture bytes don't match its size")?;
+            mipmaps::generate_mip_chain(&image, options.color_space)
+                .into_iter()
+                .map(|level| level.into_raw())
+                .collect()
+        } else {
+            vec![bytes.to_vec()]
+        };
 
+        let texture_size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             size: texture_size,
-            mip_level_count: 1,
+            mip_level_count: levels.len() as u32,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: options.color_space.format(),
             // sampled: use in shader
             // copy dst, we want to copy data to this texture
             usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
-            label: Some("my texture"),
+            label,
         });
-        encoder.copy_buffer_to_texture(
-            wgpu::ImageCopyBuffer {
-                buffer: &texture_buffer,
-                layout: wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: std::num::NonZeroU32::new(4 * width),
-                    rows_per_image: std::num::NonZeroU32::new(height),
-                },
-            },
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            texture_size,
-        );
-        queue.submit(std::iter::once(encoder.finish()));
+        for (mip_level, level) in levels.iter().enumerate() {
+            write_mip_level(queue, &texture, mip_level as u32, level, width, height, 0);
+        }
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let address_mode = wgpu::AddressMode::ClampToEdge;
-        let filter_mode = wgpu::FilterMode::Nearest;
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: address_mode,
-            address_mode_v: address_mode,
-            address_mode_w: address_mode,
-            mag_filter: filter_mode,
-            min_filter: filter_mode,
-            mipmap_filter: filter_mode,
-            ..Default::default()
-        });
+        let sampler = options.sampler.create_sampler(device, label);
         Ok(Self {
             texture,
             view,
@@ -120,16 +211,27 @@ impl Texture {
         queue: &wgpu::Queue,
         image: &image::DynamicImage,
         label: Option<&str>,
+    ) -> Result<Self> {
+        Self::from_image_with_options(device, queue, image, label, &TextureOptions::default())
+    }
+
+    pub fn from_image_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &image::DynamicImage,
+        label: Option<&str>,
+        options: &TextureOptions,
     ) -> Result<Self> {
         let diffuse_rgba = image.to_rgba8();
         let dimensions = image.dimensions();
-        Self::from_bytes(
+        Self::from_bytes_with_options(
             device,
             queue,
             &diffuse_rgba,
             dimensions.0,
             dimensions.1,
             label,
+            options,
         )
     }
 
@@ -138,6 +240,16 @@ impl Texture {
         device: &wgpu::Device,
         sc_desc: &wgpu::SwapChainDescriptor,
         label: &str,
+    ) -> Self {
+        Self::create_depth_texture_with_samples(device, sc_desc, 1, label)
+    }
+
+    // a multisampled depth texture can only be read with textureLoad, the sampler is unused
+    pub fn create_depth_texture_with_samples(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+        sample_count: u32,
+        label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
             width: sc_desc.width,
@@ -148,14 +260,73 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
         };
         let texture = device.create_texture(&desc);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        let sampler = Self::create_comparison_sampler(device, label);
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    // color texture that is rendered to and then sampled by a later pass
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        Self::create_render_target_with_samples(device, width, height, format, 1, label)
+    }
+
+    // multisampled targets are resolved into a single sampled one instead of being sampled
+    pub fn create_render_target_with_samples(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = SamplerOptions {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        }
+        .create_sampler(device, Some(label));
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    // linear filtering of a comparison sampler blends the results of the 4 nearest
+    // depth tests, which already softens shadow edges a little
+    pub fn create_comparison_sampler(device: &wgpu::Device, label: &str) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
@@ -166,25 +337,57 @@ impl Texture {
             lod_min_clamp: -100.0,
             lod_max_clamp: 100.0,
             ..Default::default()
-        });
-        Self {
-            texture,
-            view,
-            sampler,
-        }
+        })
     }
 
     pub fn load<P: AsRef<Path>>(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         path: P,
+        options: &TextureOptions,
     ) -> Result<Self> {
         // uuh borrow checker pls...
         let path_copy = path.as_ref().to_path_buf();
         let label = path_copy.to_str();
-        println!("loading image from: {:?}", label);
+        log::debug!("loading image from: {:?}", label);
         let img = image::open(path)?;
-        Self::from_image(device, queue, &img, label)
+        Self::from_image_with_options(device, queue, &img, label, options)
         //let path_copy =
     }
 }
+
+// one mip level of rgba8 data, level sizes halve down to 1
+pub fn write_mip_level(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    mip_level: u32,
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    array_layer: u32,
+) {
+    let level_width = (width >> mip_level).max(1);
+    let level_height = (height >> mip_level).max(1);
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: 0,
+                z: array_layer,
+            },
+        },
+        bytes,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(4 * level_width),
+            rows_per_image: std::num::NonZeroU32::new(level_height),
+        },
+        wgpu::Extent3d {
+            width: level_width,
+            height: level_height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
@@ -0,0 +1,139 @@
+use cgmath::{InnerSpace, Vector3};
+
+// seconds for a full day
+pub const DEFAULT_DAY_LENGTH: f32 = 600f32;
+// start in the morning
+pub const DEFAULT_TIME: f32 = 0.3;
+// how far the sun path leans away from passing straight overhead, in radians
+pub const SUN_PATH_TILT: f32 = 0.4;
+// one hour per scrub step
+pub const SCRUB_STEP: f32 = 1f32 / 24f32;
+
+const DAY_AMBIENT: [f32; 3] = [0.25, 0.28, 0.35];
+const NIGHT_AMBIENT: [f32; 3] = [0.03, 0.04, 0.08];
+const DAY_ZENITH: [f32; 3] = [0.15, 0.35, 0.75];
+const NIGHT_ZENITH: [f32; 3] = [0.005, 0.008, 0.03];
+const DAY_HORIZON: [f32; 3] = [0.6, 0.75, 0.9];
+const NIGHT_HORIZON: [f32; 3] = [0.02, 0.03, 0.07];
+const SUNSET_HORIZON: [f32; 3] = [0.9, 0.45, 0.25];
+const NOON_SUN: [f32; 3] = [1.0, 0.95, 0.85];
+const SUNSET_SUN: [f32; 3] = [1.0, 0.45, 0.2];
+const MOON: [f32; 3] = [0.08, 0.1, 0.16];
+
+// light and sky colors for one moment of the day
+#[derive(Copy, Clone, Debug)]
+pub struct Sky {
+    // normalized, from the surface towards the sun
+    pub sun_direction: Vector3<f32>,
+    // the light that casts shadows, the sun during the day and the moon at night
+    pub light_direction: Vector3<f32>,
+    pub light_color: Vector3<f32>,
+    pub ambient_color: Vector3<f32>,
+    pub zenith_color: Vector3<f32>,
+    pub horizon_color: Vector3<f32>,
+    // 0 during the day, 1 at night
+    pub star_visibility: f32,
+}
+
+impl Sky {
+    // the horizon color, anything not covered by the sky pass blends in
+    pub fn clear_color(&self) -> wgpu::Color {
+        wgpu::Color {
+            r: self.horizon_color.x as f64,
+            g: self.horizon_color.y as f64,
+            b: self.horizon_color.z as f64,
+            a: 1.0,
+        }
+    }
+}
+
+pub struct TimeOfDay {
+    // 0..1, 0 is midnight, 0.25 sunrise, 0.5 noon and 0.75 sunset
+    time: f32,
+    // seconds for a full day
+    pub day_length: f32,
+    pub paused: bool,
+}
+
+impl TimeOfDay {
+    pub fn new(day_length: f32) -> Self {
+        Self {
+            time: DEFAULT_TIME,
+            day_length,
+            paused: false,
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    // wraps around, so 1.25 is the next sunrise
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.rem_euclid(1f32);
+    }
+
+    // moves time forwards or backwards, also while paused
+    pub fn scrub(&mut self, amount: f32) {
+        self.set_time(self.time + amount);
+    }
+
+    pub fn update(&mut self, dt: std::time::Duration) {
+        if self.paused || self.day_length <= 0f32 {
+            return;
+        }
+        self.set_time(self.time + dt.as_secs_f32() / self.day_length);
+    }
+
+    pub fn sky(&self) -> Sky {
+        sky_at(self.time)
+    }
+}
+
+// the sun rises in +x, is highest at noon and sets in -x
+pub fn sun_direction(time: f32) -> Vector3<f32> {
+    let angle = (time - 0.25) * std::f32::consts::PI * 2f32;
+    let (sin, cos) = angle.sin_cos();
+    Vector3::new(cos, sin * SUN_PATH_TILT.cos(), sin * SUN_PATH_TILT.sin()).normalize()
+}
+
+pub fn sky_at(time: f32) -> Sky {
+    let sun_direction = sun_direction(time);
+    let elevation = sun_direction.y;
+
+    let daylight = smoothstep(-0.1, 0.2, elevation);
+    // strongest with the sun right at the horizon
+    let sunset = 1f32 - smoothstep(0f32, 0.35, elevation.abs());
+
+    let sun_color = lerp(SUNSET_SUN, NOON_SUN, smoothstep(0f32, 0.4, elevation))
+        * smoothstep(0f32, 0.2, elevation);
+    let moon_color = Vector3::from(MOON) * smoothstep(0f32, 0.2, -elevation);
+    let (light_direction, light_color) = if elevation >= 0f32 {
+        (sun_direction, sun_color)
+    } else {
+        (-sun_direction, moon_color)
+    };
+
+    let horizon_color = lerp(NIGHT_HORIZON, DAY_HORIZON, daylight);
+    let horizon_color = horizon_color + (Vector3::from(SUNSET_HORIZON) - horizon_color) * sunset;
+
+    Sky {
+        sun_direction,
+        light_direction,
+        light_color,
+        ambient_color: lerp(NIGHT_AMBIENT, DAY_AMBIENT, daylight),
+        zenith_color: lerp(NIGHT_ZENITH, DAY_ZENITH, daylight),
+        horizon_color,
+        star_visibility: 1f32 - smoothstep(-0.2, 0.05, elevation),
+    }
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> Vector3<f32> {
+    let a = Vector3::from(a);
+    a + (Vector3::from(b) - a) * t
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0f32, 1f32);
+    t * t * (3f32 - 2f32 * t)
+}
@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+// log target of the per span timings, enable with RUST_LOG=timing=trace
+pub const TIMING_TARGET: &str = "timing";
+
+// aggregated durations of every span with the same name
+#[derive(Copy, Clone, Debug)]
+pub struct SpanStats {
+    pub count: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl SpanStats {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            total: Duration::from_secs(0),
+            min: Duration::from_secs(u64::MAX),
+            max: Duration::from_secs(0),
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.min = self.min.min(duration);
+        self.max = self.max.max(duration);
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::from_secs(0);
+        }
+        self.total / self.count as u32
+    }
+}
+
+// a running measurement, ended into Timings
+// doesn't borrow the timings, so the measured code is free to use its owner
+#[must_use = "a span only records when it is ended"]
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Span {
+    pub fn start(name: &'static str) -> Self {
+        Self {
+            name,
+            start: Instant::now(),
+        }
+    }
+
+    // what is measured, e.g. the chunk position, only formatted when trace logging is on
+    pub fn end(self, timings: &mut Timings, subject: impl std::fmt::Debug) -> Duration {
+        let duration = self.start.elapsed();
+        log::trace!(target: TIMING_TARGET, "{} {:?} took {:?}", self.name, subject, duration);
+        timings.record(self.name, duration);
+        duration
+    }
+}
+
+// named durations collected over the whole run, in the order they first appeared
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+    spans: Vec<(&'static str, SpanStats)>,
+}
+
+impl Timings {
+    pub fn record(&mut self, name: &'static str, duration: Duration) {
+        match self.spans.iter_mut().find(|(span, _)| *span == name) {
+            Some((_, stats)) => stats.add(duration),
+            None => {
+                let mut stats = SpanStats::new();
+                stats.add(duration);
+                self.spans.push((name, stats));
+            }
+        }
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        self.spans
+            .iter()
+            .map(|(name, stats)| {
+                format!(
+                    "{}: {} spans, total {:?}, mean {:?}, min {:?}, max {:?}",
+                    name,
+                    stats.count,
+                    stats.total,
+                    stats.mean(),
+                    stats.min,
+                    stats.max
+                )
+            })
+            .collect()
+    }
+
+    pub fn log_summary(&self) {
+        for line in self.summary() {
+            log::info!(target: TIMING_TARGET, "{}", line);
+        }
+    }
+}
@@ -1,7 +1,13 @@
+pub mod block;
 pub mod chunk;
 pub mod chunks;
 pub mod direction;
+pub mod generator;
 pub mod mesh_builder;
+pub mod mesh_cache;
 pub mod quad;
 pub mod rendering;
+pub mod spawn;
+pub mod streaming;
 pub mod voxel;
+pub mod world_settings;
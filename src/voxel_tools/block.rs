@@ -0,0 +1,125 @@
+use crate::color::Color;
+use rand::Rng;
+
+// how a block lets light (and the view) through
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Transparency {
+    // no block at all, nothing is drawn and nothing behind it is hidden
+    Empty,
+    // hides everything behind it
+    Opaque,
+    // has holes, drawn with the opaque geometry but doesn't hide its neighbours
+    Cutout,
+    // blended, drawn after the opaque geometry sorted back to front
+    Translucent,
+}
+
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlockType {
+    Air,
+    Grass,
+    Leaves,
+    Glass,
+    Water,
+    // glows on its own, bright enough to bloom
+    Glowstone,
+}
+
+impl BlockType {
+    pub const COUNT: usize = 6;
+    pub const ALL: [BlockType; BlockType::COUNT] = [
+        BlockType::Air,
+        BlockType::Grass,
+        BlockType::Leaves,
+        BlockType::Glass,
+        BlockType::Water,
+        BlockType::Glowstone,
+    ];
+
+    // file name without extension in res/blocks, None for blocks that are never drawn
+    pub fn texture_name(&self) -> Option<&'static str> {
+        match self {
+            BlockType::Air => None,
+            BlockType::Grass => Some("grass"),
+            BlockType::Leaves => Some("leaves"),
+            BlockType::Glass => Some("glass"),
+            BlockType::Water => Some("water"),
+            BlockType::Glowstone => Some("glowstone"),
+        }
+    }
+
+    pub fn transparency(&self) -> Transparency {
+        match self {
+            BlockType::Air => Transparency::Empty,
+            BlockType::Grass => Transparency::Opaque,
+            BlockType::Leaves => Transparency::Cutout,
+            BlockType::Glass => Transparency::Translucent,
+            BlockType::Water => Transparency::Translucent,
+            BlockType::Glowstone => Transparency::Opaque,
+        }
+    }
+
+    // light the block gives off, multiplied with its color and added after lighting
+    // values above 1 stay bright in the dark and end up in the bloom
+    pub fn emission(&self) -> f32 {
+        match self {
+            BlockType::Glowstone => 4f32,
+            _ => 0f32,
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.transparency() == Transparency::Opaque
+    }
+
+    // should the face of this block that touches the neighbour be drawn
+    // faces are hidden by opaque neighbours and between two blocks of the same type,
+    // so water next to water or leaves next to leaves produce no inner faces
+    pub fn is_face_visible(&self, neighbour: BlockType) -> bool {
+        self.transparency() != Transparency::Empty && !neighbour.is_opaque() && *self != neighbour
+    }
+
+    // tints the block texture, slightly randomized so every face doesn't look the same
+    pub fn color(&self) -> Color {
+        let mut rng = rand::thread_rng();
+        match self {
+            BlockType::Air => Color::new(0., 0., 0., 0.),
+            BlockType::Grass => {
+                let green_range = 0.4f32;
+                Color::new(
+                    rng.gen_range(0f32..0.1f32),
+                    (1. - green_range) + rng.gen_range(0f32..green_range),
+                    rng.gen_range(0f32..0.1f32),
+                    1.,
+                )
+            }
+            BlockType::Leaves => Color::new(
+                rng.gen_range(0.05f32..0.15f32),
+                rng.gen_range(0.35f32..0.5f32),
+                rng.gen_range(0.05f32..0.1f32),
+                1.,
+            ),
+            BlockType::Glass => Color::new(0.8, 0.9, 0.95, 0.3),
+            BlockType::Water => Color::new(0.1, 0.3, 0.8, 0.6),
+            BlockType::Glowstone => Color::new(1.0, 0.75, rng.gen_range(0.35f32..0.45f32), 1.),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn air_has_no_faces_and_hides_none() {
+        assert_eq!(BlockType::Air.transparency(), Transparency::Empty);
+        for block in BlockType::ALL.iter() {
+            assert!(!BlockType::Air.is_face_visible(*block));
+            assert_eq!(
+                block.is_face_visible(BlockType::Air),
+                *block != BlockType::Air
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use super::voxel::Voxel;
+use super::{generator::WorldGenerator, voxel::Voxel};
+use crate::rendering::mesh_buffer_pool::MeshAllocation;
 
 // argument-flavor struct
 #[derive(Debug, Clone, Copy)]
@@ -16,33 +17,45 @@ lazy_static! {
     pub static ref BIT_SIZE: i32 = (SIZE as f32).log2() as i32;
 }
 
+// empty space below this world height is filled with water
+pub const WATER_LEVEL: f32 = -8f32;
+
 pub struct ChunkMesh {
-    pub vertex_buffer: Option<generational_arena::Index>,
-    pub index_buffer: Option<generational_arena::Index>,
+    // location in GpuResources::chunk_buffers, None for empty meshes
+    pub allocation: Option<MeshAllocation>,
+    // blended faces (water, glass), drawn after all opaque geometry
+    pub translucent_allocation: Option<MeshAllocation>,
+    // of both meshes
     pub num_indices: u32,
     // debug info
     pub num_vertices: u32,
 }
 
+impl Default for ChunkMesh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ChunkMesh {
     pub fn new() -> Self {
         Self {
-            vertex_buffer: None,
-            index_buffer: None,
+            allocation: None,
+            translucent_allocation: None,
             num_indices: 0,
             num_vertices: 0,
         }
     }
 
-    pub fn update_vertex_buffers(
+    pub fn update_allocation(
         &mut self,
-        vertex_buffer: generational_arena::Index,
-        index_buffer: generational_arena::Index,
+        allocation: Option<MeshAllocation>,
+        translucent_allocation: Option<MeshAllocation>,
         num_indices: u32,
         num_vertices: u32,
     ) {
-        self.vertex_buffer = Some(vertex_buffer);
-        self.index_buffer = Some(index_buffer);
+        self.allocation = allocation;
+        self.translucent_allocation = translucent_allocation;
         self.num_indices = num_indices;
         self.num_vertices = num_vertices;
     }
@@ -54,9 +67,10 @@ impl lifeguard::Recycleable for ChunkMesh {
     }
 
     fn reset(&mut self) {
-        self.vertex_buffer = None;
-        self.index_buffer = None;
+        self.allocation = None;
+        self.translucent_allocation = None;
         self.num_indices = 0u32;
+        self.num_vertices = 0u32;
     }
 }
 
@@ -71,11 +85,17 @@ impl lifeguard::Recycleable for Chunk {
 
     fn reset(&mut self) {
         for voxel in self.voxels.iter_mut() {
-            voxel.set_density_fraciton(0f32);
+            *voxel = Voxel::new_empty();
         }
     }
 }
 
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Chunk {
     // convert 3d coordinate to array index
     pub fn get_index(coordinate: LocalCoordinate) -> usize {
@@ -112,23 +132,18 @@ impl Chunk {
         chunk
     }
 
-    pub fn build_voxel_data(&mut self, chunk_world_pos: &cgmath::Vector3<f32>) {
-        use noise::{NoiseFn, Perlin, Seedable};
-        let perlin = Perlin::new();
-        perlin.set_seed(484);
+    pub fn build_voxel_data(
+        &mut self,
+        generator: &WorldGenerator,
+        chunk_world_pos: &cgmath::Vector3<f32>,
+    ) {
+        let origin = chunk_world_pos
+            .cast::<i32>()
+            .expect("chunk positions fit in i32");
         for (index, voxel) in self.voxels.iter_mut().enumerate() {
             let local_coord = Self::get_local_coordinate(index as i32);
-            let (l_x, l_y, l_z) = (local_coord.0, local_coord.1, local_coord.2);
-
-            // convert noise to world
-            let down_scale = 0.027f64;
-            let x = (chunk_world_pos.x as f64 + l_x as f64) * down_scale;
-            let y = (chunk_world_pos.y as f64 + l_y as f64) * down_scale;
-            let z = (chunk_world_pos.z as f64 + l_z as f64) * down_scale;
-            let density = perlin.get([x, y, z]);
-            if density > 0.3f64 {
-                voxel.set_density_fraciton(1f32);
-            }
+            let local = cgmath::Vector3::new(local_coord.0, local_coord.1, local_coord.2);
+            *voxel = generator.voxel(origin + local);
         }
     }
 }
@@ -3,15 +3,24 @@ use anyhow::*;
 use cgmath::InnerSpace;
 use lifeguard::*;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
 };
 
-use crate::rendering::gpu_resources::GpuResources;
+use crate::rendering::{
+    frustum::Frustum,
+    gpu_resources::GpuResources,
+    indirect_draw::{DrawIndexedIndirectArgs, IndirectDrawBuffer},
+    mesh_buffer_pool::{MeshAllocation, MeshBufferPool},
+};
+use crate::timing::{Span, Timings};
 
+use super::generator::WorldGenerator;
 use super::mesh_builder;
+use super::mesh_cache::{self, MeshCache};
+use super::streaming::{FrameBudget, StreamingPolicy, DEFAULT_DATA_BUDGET, DEFAULT_MESH_BUDGET};
 use super::{
     chunk::Chunk,
-    rendering::voxel_rendering::{self},
+    rendering::{block_textures::BlockLayers, voxel_rendering},
 };
 use super::{
     chunk::{ChunkMesh, LocalCoordinate, SIZE},
@@ -23,12 +32,28 @@ pub const DEFAULT_MAX_CHUNK_DATAS: usize = 10000;
 // max amount of per-chunk meshes we can load
 pub const DEFAULT_MAX_MESH_DATAS: usize = 10000;
 pub const RENDER_DIST_RADIUS: i32 = 8;
+// the data unload radius still fits DEFAULT_MAX_CHUNK_DATAS chunks
+pub const MAX_RENDER_DISTANCE: i32 = 10;
+
+// starting capacity of the load and unload queues
+pub const QUEUE_CAPACITY: usize = 1024;
 
-pub const MAX_DATA_QUEUE: usize = 16;
-pub const MAX_MESH_QUEUE: usize = 16;
+// how much the view direction shrinks the load priority of chunks in front of the camera
+// 0 = only distance matters, 1 = chunks behind the camera are twice as far away
+pub const VIEW_DIRECTION_BIAS: f32 = 0.5;
+// re-sort the load order when the view direction changed more than this (dot product)
+pub const VIEW_DIRECTION_RESORT_DOT: f32 = 0.95;
 
-pub const MAX_DATA_UNLOAD_QUEUE: usize = 16;
-pub const MAX_MESH_UNLOAD_QUEUE: usize = 16;
+// how many chunks are loaded and waiting, for diagnostics
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ChunkStats {
+    pub data_count: usize,
+    pub mesh_count: usize,
+    pub data_load_queue: usize,
+    pub mesh_load_queue: usize,
+    pub data_unload_queue: usize,
+    pub mesh_unload_queue: usize,
+}
 
 pub struct Chunks {
     // chunk_map owns the current chunks, but when unloaded puts them back to chunk_pool
@@ -40,40 +65,260 @@ pub struct Chunks {
     chunk_mesh_pool: Pool<ChunkMesh>,
 
     // chunk data are put in queue due to heavy data processing
+    // the queues hold every pending chunk, the frame budgets limit how many are processed
     chunk_data_load_queue: VecDeque<cgmath::Vector3<i32>>,
     chunk_mesh_load_queue: VecDeque<cgmath::Vector3<i32>>,
+    // the positions in chunk_mesh_load_queue, for fast lookups
+    chunk_mesh_load_set: HashSet<cgmath::Vector3<i32>>,
+    // meshes were queued since the mesh load queue was last sorted
+    chunk_mesh_load_queue_unsorted: bool,
 
     chunk_data_unload_queue: VecDeque<cgmath::Vector3<i32>>,
     chunk_mesh_unload_queue: VecDeque<cgmath::Vector3<i32>>,
+    // the positions in the unload queues, for fast lookups
+    chunk_data_unload_set: HashSet<cgmath::Vector3<i32>>,
+    chunk_mesh_unload_set: HashSet<cgmath::Vector3<i32>>,
 
     pub position: cgmath::Vector3<f32>,
+    // normalized view direction, chunks in front of the camera are loaded first
+    pub direction: cgmath::Vector3<f32>,
+
+    // load and unload radii, derived from the render distance
+    policy: StreamingPolicy,
+
+    // limits the amount of chunk generation and meshing done per frame
+    pub data_budget: FrameBudget,
+    pub mesh_budget: FrameBudget,
+
+    // decides the voxels of every chunk that is loaded
+    pub generator: WorldGenerator,
+
+    // texture array layer of each block type, set once the block textures are loaded
+    pub block_layers: BlockLayers,
+
+    // chunk position and policy the load and unload queues were built for
+    streaming_center: Option<(cgmath::Vector3<i32>, StreamingPolicy)>,
 
-    render_distance: i32,
+    // chunk positions in range, sorted by load priority (closest first)
+    load_order: Vec<cgmath::Vector3<i32>>,
+    // chunk position and direction load_order was sorted for
+    load_order_center: Option<(cgmath::Vector3<i32>, cgmath::Vector3<f32>, i32)>,
+
+    // time spent generating, meshing and uploading chunks, summarized on exit
+    pub timings: Timings,
+
+    // built meshes kept on disk across runs, None to always build them
+    pub mesh_cache: Option<MeshCache>,
+}
+
+impl Default for Chunks {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chunks {
     pub fn new() -> Self {
-        let chunks = Self {
+        Self {
             chunk_data_map: HashMap::with_capacity(DEFAULT_MAX_CHUNK_DATAS),
             chunk_mesh_map: HashMap::with_capacity(DEFAULT_MAX_MESH_DATAS),
             chunk_pool: pool().with(StartingSize(DEFAULT_MAX_CHUNK_DATAS)).build(),
             chunk_mesh_pool: pool().with(StartingSize(DEFAULT_MAX_MESH_DATAS)).build(),
             // position of chunks to load in
-            chunk_data_load_queue: VecDeque::with_capacity(MAX_DATA_QUEUE),
-            chunk_mesh_load_queue: VecDeque::with_capacity(MAX_MESH_QUEUE),
-            chunk_data_unload_queue: VecDeque::with_capacity(MAX_DATA_QUEUE),
-            chunk_mesh_unload_queue: VecDeque::with_capacity(MAX_MESH_QUEUE),
+            chunk_data_load_queue: VecDeque::with_capacity(QUEUE_CAPACITY),
+            chunk_mesh_load_queue: VecDeque::with_capacity(QUEUE_CAPACITY),
+            chunk_mesh_load_set: HashSet::with_capacity(QUEUE_CAPACITY),
+            chunk_mesh_load_queue_unsorted: false,
+            chunk_data_unload_queue: VecDeque::with_capacity(QUEUE_CAPACITY),
+            chunk_mesh_unload_queue: VecDeque::with_capacity(QUEUE_CAPACITY),
+            chunk_data_unload_set: HashSet::with_capacity(QUEUE_CAPACITY),
+            chunk_mesh_unload_set: HashSet::with_capacity(QUEUE_CAPACITY),
             position: cgmath::Vector3::<f32>::new(0., 0., 0.),
-            render_distance: RENDER_DIST_RADIUS,
+            direction: cgmath::Vector3::<f32>::new(0., 0., -1.),
+            generator: WorldGenerator::default(),
+            block_layers: BlockLayers::default(),
+            policy: StreamingPolicy::from_render_distance(RENDER_DIST_RADIUS),
+            data_budget: DEFAULT_DATA_BUDGET,
+            mesh_budget: DEFAULT_MESH_BUDGET,
+            streaming_center: None,
+            load_order: Vec::new(),
+            load_order_center: None,
+            timings: Timings::default(),
+            mesh_cache: None,
+        }
+    }
+
+    pub fn render_distance(&self) -> i32 {
+        self.policy.mesh_load_radius
+    }
+
+    // the load and unload queues are rebuilt on the next update_streaming call
+    pub fn set_render_distance(&mut self, render_distance: i32) {
+        self.policy = StreamingPolicy::from_render_distance(render_distance);
+    }
+
+    pub fn policy(&self) -> StreamingPolicy {
+        self.policy
+    }
+
+    // chunks inside a load radius but outside the unload radius would be loaded and unloaded
+    // again every time the queues are updated
+    pub fn set_policy(&mut self, policy: StreamingPolicy) -> Result<()> {
+        if policy.mesh_unload_radius < policy.mesh_load_radius
+            || policy.data_unload_radius < policy.data_load_radius
+        {
+            bail!(
+                "unload radii must not be smaller than load radii: {:?}",
+                policy
+            );
+        }
+        self.policy = policy;
+        Ok(())
+    }
+
+    // recompute what to load and unload, only when the camera crossed a chunk boundary
+    // or the render distance changed
+    pub fn update_streaming(&mut self) {
+        let center = self.current_chunk_pos();
+        if self.streaming_center == Some((center, self.policy)) {
+            // the view direction may still have changed
+            self.update_load_order();
+        } else {
+            self.streaming_center = Some((center, self.policy));
+            self.update_load_data_queue();
+            self.update_load_mesh_queue();
+            self.update_unload_mesh_queue();
+            self.update_unload_data_queue();
+        }
+        // meshes queued by newly loaded chunk data during the last frame
+        if self.chunk_mesh_load_queue_unsorted {
+            self.sort_mesh_load_queue();
+        }
+    }
+
+    // is the chunk inside the sphere of the given radius around the camera chunk
+    pub fn in_range(&self, chunk_pos: cgmath::Vector3<i32>, radius: i32) -> bool {
+        StreamingPolicy::in_radius(chunk_pos - self.current_chunk_pos(), radius)
+    }
+
+    // chunk position the given world position is inside of
+    pub fn world_to_chunk(world_pos: cgmath::Vector3<f32>) -> cgmath::Vector3<i32> {
+        cgmath::Vector3::<i32>::new(
+            (world_pos.x / SIZE as f32).floor() as i32,
+            (world_pos.y / SIZE as f32).floor() as i32,
+            (world_pos.z / SIZE as f32).floor() as i32,
+        )
+    }
+
+    // chunk position the camera is currently inside of
+    pub fn current_chunk_pos(&self) -> cgmath::Vector3<i32> {
+        Self::world_to_chunk(self.position)
+    }
+
+    // lower value is loaded first
+    // distance in chunks from the center, shrunk for chunks in the view direction
+    pub fn load_priority(offset: cgmath::Vector3<i32>, direction: cgmath::Vector3<f32>) -> f32 {
+        let offset = cgmath::Vector3::<f32>::new(offset.x as f32, offset.y as f32, offset.z as f32);
+        let distance = offset.magnitude();
+        if distance == 0f32 {
+            return 0f32;
+        }
+        let facing = offset.dot(direction) / distance;
+        distance * (1f32 - VIEW_DIRECTION_BIAS * facing)
+    }
+
+    // all chunk offsets inside the sphere, sorted by load priority
+    pub fn sorted_load_offsets(
+        radius: i32,
+        direction: cgmath::Vector3<f32>,
+    ) -> Vec<cgmath::Vector3<i32>> {
+        let mut offsets = Vec::new();
+        for y in -radius..=radius {
+            for z in -radius..=radius {
+                for x in -radius..=radius {
+                    let offset = cgmath::Vector3::<i32>::new(x, y, z);
+                    if StreamingPolicy::in_radius(offset, radius) {
+                        offsets.push(offset);
+                    }
+                }
+            }
+        }
+        offsets.sort_by(|a, b| {
+            Self::load_priority(*a, direction)
+                .partial_cmp(&Self::load_priority(*b, direction))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        offsets
+    }
+
+    // rebuild the load order when the camera entered a new chunk or turned around,
+    // chunks already waiting in the load queues are re-prioritized as well
+    pub fn update_load_order(&mut self) {
+        let center = self.current_chunk_pos();
+        let direction = if self.direction.magnitude2() > 0f32 {
+            self.direction.normalize()
+        } else {
+            self.direction
         };
-        chunks
+        let radius = self.policy.data_load_radius;
+        if let Some((old_center, old_direction, old_radius)) = self.load_order_center {
+            if old_center == center
+                && old_radius == radius
+                && old_direction.dot(direction) >= VIEW_DIRECTION_RESORT_DOT
+            {
+                return;
+            }
+        }
+        self.load_order = Self::sorted_load_offsets(radius, direction)
+            .into_iter()
+            .map(|offset| center + offset)
+            .collect();
+        self.load_order_center = Some((center, direction, radius));
+
+        let by_priority = |a: &cgmath::Vector3<i32>, b: &cgmath::Vector3<i32>| {
+            Self::compare_priority(*a, *b, center, direction)
+        };
+        self.chunk_data_load_queue
+            .make_contiguous()
+            .sort_by(by_priority);
+        self.sort_mesh_load_queue();
+    }
+
+    fn sort_mesh_load_queue(&mut self) {
+        let center = self.current_chunk_pos();
+        let direction = match self.load_order_center {
+            Some((_center, direction, _radius)) => direction,
+            None => self.direction,
+        };
+        self.chunk_mesh_load_queue
+            .make_contiguous()
+            .sort_by(|a, b| Self::compare_priority(*a, *b, center, direction));
+        self.chunk_mesh_load_queue_unsorted = false;
+    }
+
+    fn compare_priority(
+        a: cgmath::Vector3<i32>,
+        b: cgmath::Vector3<i32>,
+        center: cgmath::Vector3<i32>,
+        direction: cgmath::Vector3<f32>,
+    ) -> std::cmp::Ordering {
+        Self::load_priority(a - center, direction)
+            .partial_cmp(&Self::load_priority(b - center, direction))
+            .unwrap_or(std::cmp::Ordering::Equal)
     }
 
     pub fn build_chunk_data_in_queue(
         &mut self,
     ) {
-        while let Some(chunk_pos) = self.chunk_data_load_queue.pop_front() {
+        let mut budget = self.data_budget.start();
+        while budget.has_budget() {
+            let chunk_pos = match self.chunk_data_load_queue.pop_front() {
+                Some(chunk_pos) => chunk_pos,
+                None => return,
+            };
             self.build_chunk_data(chunk_pos);
+            self.queue_meshes_around(chunk_pos);
+            budget.spend();
         }
     }
 
@@ -136,46 +381,112 @@ impl Chunks {
         let mut chunk = self.chunk_pool.detached();
         let chunk_world_pos = Self::chunk_to_world(chunk_pos);
 
-        chunk.build_voxel_data(&chunk_world_pos);
-        println!("loaded chunk data at world pos: {:?}", chunk_world_pos);
+        let span = Span::start("generation");
+        chunk.build_voxel_data(&self.generator, &chunk_world_pos);
+        span.end(&mut self.timings, chunk_pos);
+        log::trace!("loaded chunk data at world pos: {:?}", chunk_world_pos);
         self.chunk_data_map.insert(chunk_pos, chunk);
     }
 
     pub fn build_chunk_meshes_in_queue(
         &mut self,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         gpu_resources: &mut GpuResources,
     ) {
-        while let Some(chunk_pos) = self.chunk_mesh_load_queue.pop_front() {
-            if self.chunk_mesh_map.len() >= DEFAULT_MAX_CHUNK_DATAS {
-                return;
+        let mut budget = self.mesh_budget.start();
+        while budget.has_budget() {
+            let chunk_pos = match self.next_mesh_in_queue() {
+                Some(chunk_pos) => chunk_pos,
+                None => return,
+            };
+            let chunk_world_pos = Self::chunk_to_world(chunk_pos);
+            if self.mesh_cache.is_some() {
+                self.build_cached_chunk_mesh(device, queue, gpu_resources, chunk_pos);
+            } else {
+                mesh_builder::build_chunk_mesh(
+                    self,
+                    device,
+                    queue,
+                    gpu_resources,
+                    &chunk_pos,
+                    &chunk_world_pos,
+                );
             }
-            let chunk_mesh = self.chunk_mesh_pool.detached();
-            self.chunk_mesh_map.insert(chunk_pos, chunk_mesh);
+            budget.spend();
+        }
+    }
 
-            println!("building chunk mesh at: {:?}", chunk_pos);
-            let chunk_world_pos = Self::chunk_to_world(chunk_pos);
-            if mesh_builder::build_chunk_mesh(
-                self,
-                device,
-                gpu_resources,
-                &chunk_pos,
-                &chunk_world_pos,
-            ) {
-                // successfully built return for now, 'only one per frame'
-                return;
+    // loads the mesh from the cache if it was built from the same voxels, otherwise builds
+    // and caches it
+    fn build_cached_chunk_mesh(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        gpu_resources: &mut GpuResources,
+        chunk_pos: cgmath::Vector3<i32>,
+    ) {
+        let span = Span::start("mesh cache load");
+        let hash = mesh_cache::mesh_input_hash(self, chunk_pos);
+        let cached = self
+            .mesh_cache
+            .as_mut()
+            .and_then(|cache| cache.load(chunk_pos, hash));
+        span.end(&mut self.timings, chunk_pos);
+
+        let mesh_data = match cached {
+            Some(mesh_data) => mesh_data,
+            None => {
+                let span = Span::start("meshing");
+                let mesh_data = mesh_builder::build_chunk_mesh_data(
+                    self,
+                    &chunk_pos,
+                    &Self::chunk_to_world(chunk_pos),
+                );
+                span.end(&mut self.timings, chunk_pos);
+                if let Some(cache) = &self.mesh_cache {
+                    cache.store(chunk_pos, hash, &mesh_data);
+                }
+                mesh_data
             }
+        };
+        mesh_builder::upload_chunk_mesh(self, device, queue, gpu_resources, &chunk_pos, &mesh_data);
+    }
+
+    // takes the next chunk from the mesh load queue and gives it an empty mesh to build into
+    pub fn next_mesh_in_queue(&mut self) -> Option<cgmath::Vector3<i32>> {
+        if self.chunk_mesh_map.len() >= DEFAULT_MAX_MESH_DATAS {
+            return None;
         }
+        let chunk_pos = self.chunk_mesh_load_queue.pop_front()?;
+        self.chunk_mesh_load_set.remove(&chunk_pos);
+        let chunk_mesh = self.chunk_mesh_pool.detached();
+        self.chunk_mesh_map.insert(chunk_pos, chunk_mesh);
+        log::trace!("building chunk mesh at: {:?}", chunk_pos);
+        Some(chunk_pos)
     }
 
-    pub fn is_chunk_processing(&self, chunk_pos: &cgmath::Vector3<i32>) -> bool {
-        self.chunk_data_map.contains_key(chunk_pos)
-            || self.chunk_data_load_queue.contains(chunk_pos)
+    // voxel data built elsewhere, replaces what was loaded at the position
+    pub fn insert_chunk_data(&mut self, chunk_pos: cgmath::Vector3<i32>, chunk: Chunk) {
+        if let Some(old) = self.chunk_data_map.insert(chunk_pos, chunk) {
+            self.chunk_pool.attach(old);
+        }
     }
 
     pub fn is_mesh_processing(&self, chunk_pos: &cgmath::Vector3<i32>) -> bool {
-        self.chunk_mesh_map.contains_key(chunk_pos)
-            || self.chunk_mesh_load_queue.contains(chunk_pos)
+        self.chunk_mesh_map.contains_key(chunk_pos) || self.chunk_mesh_load_set.contains(chunk_pos)
+    }
+
+    pub fn get_chunk(&self, chunk_pos: cgmath::Vector3<i32>) -> Option<&Chunk> {
+        self.chunk_data_map.get(&chunk_pos)
+    }
+
+    pub fn has_mesh(&self, chunk_pos: cgmath::Vector3<i32>) -> bool {
+        self.chunk_mesh_map.contains_key(&chunk_pos)
+    }
+
+    pub fn mesh_positions(&self) -> impl Iterator<Item = &cgmath::Vector3<i32>> {
+        self.chunk_mesh_map.keys()
     }
 
     pub fn chunk_to_world(chunk_pos: cgmath::Vector3<i32>) -> cgmath::Vector3<f32> {
@@ -186,244 +497,345 @@ impl Chunks {
         )
     }
 
-    pub fn in_range(&self, chunk_pos: cgmath::Vector3<i32>) -> bool {
-        // convert from i32 postion to world f32 pos
-        let chunk_real_pos = Self::chunk_to_world(chunk_pos);
-        let delta = self.position - chunk_real_pos;
-        let distance_sq: f32 = delta.magnitude2().into();
-        let render_dist = (self.render_distance as f32) * SIZE as f32;
-        let render_distance_sq = render_dist * render_dist;
-        distance_sq < render_distance_sq
+    // meshes need the voxel data of all adjacent chunks
+    pub fn can_build_mesh(&self, chunk_pos: cgmath::Vector3<i32>) -> bool {
+        use cgmath::Vector3 as vec;
+        self.chunk_data_map.contains_key(&chunk_pos)
+            && [
+                -vec::<i32>::unit_x(),
+                vec::<i32>::unit_x(),
+                -vec::<i32>::unit_y(),
+                vec::<i32>::unit_y(),
+                -vec::<i32>::unit_z(),
+                vec::<i32>::unit_z(),
+            ]
+            .iter()
+            .map(|v| *v + chunk_pos)
+            .all(|v| self.chunk_data_map.contains_key(&v))
     }
 
-    // based on current position load all meshes
+    // based on current position queue all meshes, closest chunks first
     pub fn update_load_mesh_queue(&mut self) {
-        if self.chunk_mesh_map.len() >= DEFAULT_MAX_MESH_DATAS
-            || self.chunk_mesh_load_queue.len() >= MAX_MESH_QUEUE
-        {
-            return;
-        }
-        for y in -self.render_distance..self.render_distance {
-            //for y in 0..1 {
-            for z in -self.render_distance..self.render_distance {
-                for x in -self.render_distance..self.render_distance {
-                    let current_chunk_pos = cgmath::Vector3::<i32>::new(
-                        (self.position.x / SIZE as f32) as i32,
-                        (self.position.y / SIZE as f32) as i32,
-                        (self.position.z / SIZE as f32) as i32,
-                    );
-                    let chunk_pos = current_chunk_pos + cgmath::Vector3::<i32>::new(x, y, z);
-
-                    // chunk is already being loaded, or is loaded
-                    let is_mesh_proccessing = self.is_mesh_processing(&chunk_pos);
-                    if is_mesh_proccessing {
-                        continue;
-                    }
+        self.update_load_order();
+        self.chunk_mesh_load_queue.clear();
+        self.chunk_mesh_load_set.clear();
+        // rebuilt in load order
+        self.chunk_mesh_load_queue_unsorted = false;
+        for i in 0..self.load_order.len() {
+            let chunk_pos = self.load_order[i];
+            if self.chunk_mesh_load_queue.len() + self.chunk_mesh_map.len()
+                >= DEFAULT_MAX_MESH_DATAS
+            {
+                return;
+            }
+            // chunk mesh is already loaded, or data is loaded further out than meshes
+            if self.chunk_mesh_map.contains_key(&chunk_pos)
+                || !self.in_range(chunk_pos, self.policy.mesh_load_radius)
+            {
+                continue;
+            }
+            if self.can_build_mesh(chunk_pos) {
+                self.queue_mesh(chunk_pos);
+            }
+        }
+    }
 
-                    let in_range = self.in_range(current_chunk_pos);
-                    // check if adjacent chunks are loaded
-
-                    use cgmath::Vector3 as vec;
-                    // check if all adjacent chunks data are loaded
-                    let adj_chunk_data_bad = [
-                        -vec::<i32>::unit_x(),
-                        vec::<i32>::unit_x(),
-                        -vec::<i32>::unit_y(),
-                        vec::<i32>::unit_y(),
-                        -vec::<i32>::unit_z(),
-                        vec::<i32>::unit_z(),
-                    ]
-                    .iter_mut()
-                    .map(|v| *v + chunk_pos)
-                    .any(|v| !self.chunk_data_map.contains_key(&v));
-
-                    // queue chunk for mesh creation
-                    if in_range && !adj_chunk_data_bad {
-                        self.chunk_mesh_load_queue.push_back(chunk_pos);
-                        if self.chunk_mesh_load_queue.len() >= MAX_MESH_QUEUE {
-                            return;
-                        }
-                    }
-                }
+    fn queue_mesh(&mut self, chunk_pos: cgmath::Vector3<i32>) {
+        self.chunk_mesh_load_queue.push_back(chunk_pos);
+        self.chunk_mesh_load_set.insert(chunk_pos);
+    }
+
+    // newly loaded chunk data can complete the neighbourhood of itself and adjacent chunks,
+    // the queue is sorted again on the next update_streaming call
+    fn queue_meshes_around(&mut self, chunk_pos: cgmath::Vector3<i32>) {
+        use cgmath::Vector3 as vec;
+        for offset in [
+            vec::<i32>::new(0, 0, 0),
+            -vec::<i32>::unit_x(),
+            vec::<i32>::unit_x(),
+            -vec::<i32>::unit_y(),
+            vec::<i32>::unit_y(),
+            -vec::<i32>::unit_z(),
+            vec::<i32>::unit_z(),
+        ]
+        .iter()
+        {
+            let mesh_pos = chunk_pos + offset;
+            if self.in_range(mesh_pos, self.policy.mesh_load_radius)
+                && self.can_build_mesh(mesh_pos)
+                && !self.is_mesh_processing(&mesh_pos)
+            {
+                self.queue_mesh(mesh_pos);
+                self.chunk_mesh_load_queue_unsorted = true;
             }
         }
     }
 
+    // does a loaded mesh (or one waiting to be built) need this chunks voxel data
+    pub fn is_data_needed_by_mesh(&self, chunk_pos: cgmath::Vector3<i32>) -> bool {
+        use cgmath::Vector3 as vec;
+        [
+            vec::<i32>::new(0, 0, 0),
+            -vec::<i32>::unit_x(),
+            vec::<i32>::unit_x(),
+            -vec::<i32>::unit_y(),
+            vec::<i32>::unit_y(),
+            -vec::<i32>::unit_z(),
+            vec::<i32>::unit_z(),
+        ]
+        .iter()
+        .map(|v| *v + chunk_pos)
+        .any(|mesh_pos| {
+            (self.chunk_mesh_map.contains_key(&mesh_pos)
+                && !self.chunk_mesh_unload_set.contains(&mesh_pos))
+                || self.chunk_mesh_load_set.contains(&mesh_pos)
+        })
+    }
+
+    // queue chunk data outside the unload radius, that no mesh depends on
     pub fn update_unload_data_queue(&mut self) {
-        let current_chunk_pos = cgmath::Vector3::<i32>::new(
-            (self.position.x / SIZE as f32) as i32,
-            (self.position.y / SIZE as f32) as i32,
-            (self.position.z / SIZE as f32) as i32,
-        );
-        // find currently loaded meshes positions not contained in range
-        // BOX BOUND CHECK IS FAST
         let outside = self
-            .chunk_mesh_map
-            .iter()
-            .filter(|(p, _m)| {
-                p.x < current_chunk_pos.x - self.render_distance
-                    || p.x > current_chunk_pos.x + self.render_distance
-                    || p.y < current_chunk_pos.y - self.render_distance
-                    || p.y > current_chunk_pos.y + self.render_distance
-                    || p.z < current_chunk_pos.z - self.render_distance
-                    || p.z > current_chunk_pos.z + self.render_distance
-            })
-            .map(|(p, _m)| p)
+            .chunk_data_map
+            .keys()
+            .filter(|p| !self.in_range(**p, self.policy.data_unload_radius))
+            .filter(|p| !self.is_data_needed_by_mesh(**p))
+            .filter(|p| !self.chunk_data_unload_set.contains(p))
+            .copied()
             .collect::<Vec<_>>();
 
         for chunk_pos in outside {
-            // already proccessing skip
-            if self.chunk_data_unload_queue.contains(chunk_pos) {
-                continue;
-            }
-            println!("queueing chunk for data unload: {:?}", chunk_pos);
-            self.chunk_data_unload_queue.push_back(*chunk_pos);
-            if self.chunk_data_unload_queue.len() >= MAX_DATA_UNLOAD_QUEUE {
-                return;
-            }
+            log::trace!("queueing chunk for data unload: {:?}", chunk_pos);
+            self.chunk_data_unload_queue.push_back(chunk_pos);
+            self.chunk_data_unload_set.insert(chunk_pos);
         }
     }
 
-    // based on current position load all meshes
+    // queue meshes outside the unload radius
     pub fn update_unload_mesh_queue(&mut self) {
-        let current_chunk_pos = cgmath::Vector3::<i32>::new(
-            (self.position.x / SIZE as f32) as i32,
-            (self.position.y / SIZE as f32) as i32,
-            (self.position.z / SIZE as f32) as i32,
-        );
-        // find currently loaded meshes positions not contained in range
-        // BOX BOUND CHECK IS FAST
         let outside = self
             .chunk_mesh_map
-            .iter()
-            .filter(|(p, _m)| {
-                p.x < current_chunk_pos.x - self.render_distance
-                    || p.x > current_chunk_pos.x + self.render_distance
-                    || p.y < current_chunk_pos.y - self.render_distance
-                    || p.y > current_chunk_pos.y + self.render_distance
-                    || p.z < current_chunk_pos.z - self.render_distance
-                    || p.z > current_chunk_pos.z + self.render_distance
-            })
-            .map(|(p, _m)| p)
+            .keys()
+            .filter(|p| !self.in_range(**p, self.policy.mesh_unload_radius))
+            .filter(|p| !self.chunk_mesh_unload_set.contains(p))
+            .copied()
             .collect::<Vec<_>>();
 
         for chunk_pos in outside {
-            // already proccessing skip
-            if self.chunk_mesh_unload_queue.contains(chunk_pos) {
-                continue;
-            }
-            self.chunk_mesh_unload_queue.push_back(*chunk_pos);
-            println!("queueing chunk for mesh unload: {:?}", chunk_pos);
-            if self.chunk_mesh_unload_queue.len() >= MAX_MESH_UNLOAD_QUEUE {
-                return;
-            }
+            log::trace!("queueing chunk for mesh unload: {:?}", chunk_pos);
+            self.chunk_mesh_unload_queue.push_back(chunk_pos);
+            self.chunk_mesh_unload_set.insert(chunk_pos);
         }
     }
 
     pub fn unload_data_queue(&mut self) {
         while let Some(chunk_pos) = self.chunk_data_unload_queue.pop_front() {
+            self.chunk_data_unload_set.remove(&chunk_pos);
             // detach chunk data
             if let Some(chunk_data) = self.chunk_data_map.remove(&chunk_pos) {
-                println!("unloading data at: {:?}", chunk_pos);
+                log::trace!("unloading data at: {:?}", chunk_pos);
                 self.chunk_pool.attach(chunk_data);
             }
         }
     }
 
     // generate meshes queued up
-    pub fn unload_mesh_queue(&mut self, gpu_resources: &mut GpuResources) {
+    pub fn unload_mesh_queue(&mut self, chunk_buffers: &mut MeshBufferPool) {
         while let Some(chunk_pos) = self.chunk_mesh_unload_queue.pop_front() {
+            self.chunk_mesh_unload_set.remove(&chunk_pos);
             // detach mesh data
             if let Some(chunk_mesh) = self.chunk_mesh_map.remove(&chunk_pos) {
-                println!("unloading mesh at: {:?}", chunk_pos);
-                if let Some(v_buf_key) = chunk_mesh.vertex_buffer {
-                    if let Some(v_buffer) = gpu_resources.buffer_arena.get_mut(v_buf_key) {
-                        v_buffer.destroy();
-                    }
-                    gpu_resources.buffer_arena.remove(v_buf_key);
-                }
-                if let Some(i_buf_key) = chunk_mesh.index_buffer {
-                    if let Some(i_buffer) = gpu_resources.buffer_arena.get_mut(i_buf_key) {
-                        i_buffer.destroy();
-                    }
-                    gpu_resources.buffer_arena.remove(i_buf_key);
+                log::trace!("unloading mesh at: {:?}", chunk_pos);
+                for allocation in [chunk_mesh.allocation, chunk_mesh.translucent_allocation]
+                    .iter()
+                    .flatten()
+                {
+                    chunk_buffers.free(*allocation);
                 }
                 self.chunk_mesh_pool.attach(chunk_mesh);
             }
         }
     }
 
-    // based on current position load all meshes
+    // based on current position queue all chunk data, closest chunks first
     pub fn update_load_data_queue(&mut self) {
-        if self.chunk_data_map.len() >= DEFAULT_MAX_CHUNK_DATAS
-            || self.chunk_data_load_queue.len() >= MAX_DATA_QUEUE
-        {
-            return;
-        }
-        for y in -self.render_distance..self.render_distance {
-            //for y in 0..1 {
-            for z in -self.render_distance..self.render_distance {
-                for x in -self.render_distance..self.render_distance {
-                    let current_chunk_pos = cgmath::Vector3::<i32>::new(
-                        (self.position.x / SIZE as f32) as i32,
-                        (self.position.y / SIZE as f32) as i32,
-                        (self.position.z / SIZE as f32) as i32,
-                    );
-                    let chunk_pos = current_chunk_pos + cgmath::Vector3::<i32>::new(x, y, z);
-
-                    // chunk is already being loaded, or is loaded
-                    let is_chunk_proccessing = self.is_chunk_processing(&chunk_pos);
-                    if is_chunk_proccessing {
-                        continue;
-                    }
-
-                    let in_range = self.in_range(current_chunk_pos);
-                    if in_range {
-                        // load chunk
-                        self.chunk_data_load_queue.push_back(chunk_pos);
-                    }
-                    // check if we don't wan to load any more
-                    if self.chunk_data_load_queue.len() >= MAX_DATA_QUEUE {
-                        println!("done");
-                        return;
-                    }
-                }
+        self.update_load_order();
+        self.chunk_data_load_queue.clear();
+        for i in 0..self.load_order.len() {
+            let chunk_pos = self.load_order[i];
+            if self.chunk_data_load_queue.len() + self.chunk_data_map.len()
+                >= DEFAULT_MAX_CHUNK_DATAS
+            {
+                return;
             }
+            // chunk data is already loaded
+            if self.chunk_data_map.contains_key(&chunk_pos) {
+                continue;
+            }
+            self.chunk_data_load_queue.push_back(chunk_pos);
         }
     }
 
-    pub fn draw<'a, '_b>(
-        &mut self,
+    // world space bounds of a chunk, voxels are centered on their coordinate
+    pub fn chunk_bounds(
+        chunk_pos: cgmath::Vector3<i32>,
+    ) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        let min = Self::chunk_to_world(chunk_pos) - cgmath::Vector3::<f32>::new(0.5, 0.5, 0.5);
+        let max = min + cgmath::Vector3::<f32>::new(SIZE as f32, SIZE as f32, SIZE as f32);
+        (min, max)
+    }
+
+    // build the indirect draw arguments of all chunks inside the frustum
+    pub fn prepare_draw(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        gpu_resources: &mut GpuResources,
+        frustum: &Frustum,
+    ) {
+        let visible = self
+            .chunk_mesh_map
+            .iter()
+            .filter(|(pos, _m)| {
+                let (min, max) = Self::chunk_bounds(**pos);
+                frustum.intersects_aabb(min, max)
+            })
+            .collect::<Vec<_>>();
+
+        // empty meshes have nothing allocated
+        let mut opaque = visible
+            .iter()
+            .filter_map(|(_pos, m)| m.allocation)
+            .collect::<Vec<_>>();
+        // draws of the same slab are batched together
+        opaque.sort_by_key(|allocation| allocation.slab);
+
+        // blending needs the furthest chunks drawn first
+        let mut translucent = visible
+            .iter()
+            .filter_map(|(pos, m)| {
+                let (min, max) = Self::chunk_bounds(**pos);
+                let center = (min + max) * 0.5f32;
+                let distance = (center - self.position).magnitude2();
+                m.translucent_allocation
+                    .map(|allocation| (distance, allocation))
+            })
+            .collect::<Vec<_>>();
+        translucent.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+        let chunk_buffers = &gpu_resources.chunk_buffers;
+        push_draws(
+            &mut gpu_resources.chunk_draws,
+            chunk_buffers,
+            opaque.into_iter(),
+        );
+        push_draws(
+            &mut gpu_resources.chunk_translucent_draws,
+            chunk_buffers,
+            translucent
+                .into_iter()
+                .map(|(_distance, allocation)| allocation),
+        );
+        gpu_resources.chunk_draws.upload(device, queue);
+        gpu_resources.chunk_translucent_draws.upload(device, queue);
+    }
+
+    // collects the opaque meshes that can cast a shadow into one of the cascades
+    // translucent blocks don't cast shadows
+    pub fn prepare_shadow_draw(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        gpu_resources: &mut GpuResources,
+        cascades: &[Frustum],
+    ) {
+        let mut casters = self
+            .chunk_mesh_map
+            .iter()
+            .filter(|(pos, _m)| {
+                let (min, max) = Self::chunk_bounds(**pos);
+                cascades
+                    .iter()
+                    .any(|cascade| cascade.intersects_aabb(min, max))
+            })
+            .filter_map(|(_pos, m)| m.allocation)
+            .collect::<Vec<_>>();
+        casters.sort_by_key(|allocation| allocation.slab);
+
+        push_draws(
+            &mut gpu_resources.chunk_shadow_draws,
+            &gpu_resources.chunk_buffers,
+            casters.into_iter(),
+        );
+        gpu_resources.chunk_shadow_draws.upload(device, queue);
+    }
+
+    // draws the opaque meshes prepare_draw found visible
+    pub fn draw<'a>(
+        &self,
         render_pass: &mut wgpu::RenderPass<'a>,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
+        block_textures_bind_group: &'a wgpu::BindGroup,
+        shadow_bind_group: &'a wgpu::BindGroup,
         gpu_resources: &'a GpuResources,
-    ) -> anyhow::Result<()> {
-        for (_pos, chunk_mesh) in self.chunk_mesh_map.iter() {
-            let vertex_buffer_index = chunk_mesh.vertex_buffer.as_ref().context("no vertices")?;
-            let index_buffer_index = chunk_mesh.index_buffer.as_ref().context("no indices")?;
-            let num_indices = chunk_mesh.num_indices;
-            let vertex_buffer = gpu_resources
-                .buffer_arena
-                .get(*vertex_buffer_index)
-                .context("no vertex buf")?;
-            let index_buffer = gpu_resources
-                .buffer_arena
-                .get(*index_buffer_index)
-                .context("no vertex buf")?;
-            let _ = voxel_rendering::draw_chunk(
-                render_pass,
-                num_indices,
-                camera_bind_group,
-                light_bind_group,
-                vertex_buffer,
-                index_buffer,
-            );
+    ) {
+        voxel_rendering::draw_chunks(
+            render_pass,
+            camera_bind_group,
+            light_bind_group,
+            block_textures_bind_group,
+            shadow_bind_group,
+            &gpu_resources.chunk_draws,
+            &gpu_resources.chunk_buffers,
+        );
+    }
+
+    // draws the translucent meshes prepare_draw found visible, back to front
+    // expects the opaque geometry to be drawn already and a blending pipeline set
+    pub fn draw_translucent<'a>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+        block_textures_bind_group: &'a wgpu::BindGroup,
+        shadow_bind_group: &'a wgpu::BindGroup,
+        gpu_resources: &'a GpuResources,
+    ) {
+        voxel_rendering::draw_chunks(
+            render_pass,
+            camera_bind_group,
+            light_bind_group,
+            block_textures_bind_group,
+            shadow_bind_group,
+            &gpu_resources.chunk_translucent_draws,
+            &gpu_resources.chunk_buffers,
+        );
+    }
+
+    // depth only, expects the shadow pipeline to be set
+    pub fn draw_shadows<'a>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        cascade_bind_group: &'a wgpu::BindGroup,
+        gpu_resources: &'a GpuResources,
+    ) {
+        render_pass.set_bind_group(0, cascade_bind_group, &[]);
+        gpu_resources
+            .chunk_shadow_draws
+            .draw(render_pass, &gpu_resources.chunk_buffers);
+    }
+
+    pub fn stats(&self) -> ChunkStats {
+        ChunkStats {
+            data_count: self.chunk_data_map.len(),
+            mesh_count: self.chunk_mesh_map.len(),
+            data_load_queue: self.chunk_data_load_queue.len(),
+            mesh_load_queue: self.chunk_mesh_load_queue.len(),
+            data_unload_queue: self.chunk_data_unload_queue.len(),
+            mesh_unload_queue: self.chunk_mesh_unload_queue.len(),
         }
-        Ok(())
     }
 
-    #[allow(dead_code)]
     pub fn get_vertex_count(&self) -> u32 {
         self.chunk_mesh_map
             .iter()
@@ -431,8 +843,35 @@ impl Chunks {
             .sum()
     }
 }
+// draws are pushed in the given order
+fn push_draws(
+    draws: &mut IndirectDrawBuffer,
+    chunk_buffers: &MeshBufferPool,
+    allocations: impl Iterator<Item = MeshAllocation>,
+) {
+    draws.clear();
+    for allocation in allocations {
+        let slab = match chunk_buffers.slab(allocation.slab) {
+            Some(slab) => slab,
+            None => continue,
+        };
+        if let Some((index_range, base_vertex)) = slab.draw_range(&allocation) {
+            draws.push(
+                allocation.slab,
+                DrawIndexedIndirectArgs {
+                    index_count: index_range.end - index_range.start,
+                    instance_count: 1,
+                    base_index: index_range.start,
+                    vertex_offset: base_vertex,
+                    base_instance: 0,
+                },
+            );
+        }
+    }
+}
+
 pub fn adjacent_voxels<'a>(
-    chunks: &'a mut Chunks,
+    chunks: &'a Chunks,
     local_pos: (i32, i32, i32),
     chunk_pos: &cgmath::Vector3<i32>,
 ) -> Result<(&'a Voxel, &'a Voxel, &'a Voxel, &'a Voxel)> {
@@ -451,3 +890,319 @@ pub fn adjacent_voxels<'a>(
         .context("no down voxel")?;
     Ok((voxel, back, left, down))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_tools::{generator::GeneratorKind, rendering::voxel_vertex::VoxelVertex};
+    use cgmath::Vector3;
+
+    // one chunk generated per frame, so the load order can be observed
+    fn test_chunks(position: Vector3<f32>, direction: Vector3<f32>) -> Chunks {
+        let mut chunks = Chunks::new();
+        chunks.set_render_distance(2);
+        chunks.data_budget = FrameBudget::Count(1);
+        chunks.position = position;
+        chunks.direction = direction;
+        chunks
+    }
+
+    // the chunk the next frame of data work generates
+    fn generate_next(chunks: &mut Chunks) -> Option<Vector3<i32>> {
+        let loaded = chunks
+            .chunk_data_map
+            .keys()
+            .copied()
+            .collect::<HashSet<_>>();
+        chunks.update_streaming();
+        chunks.build_chunk_data_in_queue();
+        let generated = chunks
+            .chunk_data_map
+            .keys()
+            .copied()
+            .filter(|chunk_pos| !loaded.contains(chunk_pos))
+            .collect::<Vec<_>>();
+        assert!(generated.len() <= 1);
+        generated.first().copied()
+    }
+
+    #[test]
+    fn current_chunk_is_generated_first() {
+        let positions = [
+            Vector3::new(0f32, 0f32, 0f32),
+            Vector3::new(7.5, -3.0, 100.0),
+            Vector3::new(-40.2, 17.9, -0.1),
+        ];
+        let directions = [
+            Vector3::new(0f32, 0f32, -1f32),
+            Vector3::new(1f32, 0f32, 0f32),
+            Vector3::new(0f32, -1f32, 0f32),
+            Vector3::new(-0.6, 0.3, 0.7),
+            // not normalized, or no direction at all
+            Vector3::new(0f32, 5f32, 5f32),
+            Vector3::new(0f32, 0f32, 0f32),
+        ];
+        for position in positions.iter() {
+            for direction in directions.iter() {
+                let mut chunks = test_chunks(*position, *direction);
+                assert_eq!(
+                    generate_next(&mut chunks),
+                    Some(chunks.current_chunk_pos()),
+                    "camera at {:?} looking at {:?}",
+                    position,
+                    direction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn current_chunk_is_generated_first_after_moving() {
+        let mut chunks = test_chunks(
+            Vector3::new(0f32, 0f32, 0f32),
+            Vector3::new(0f32, 0f32, -1f32),
+        );
+        // partially loaded, with chunks of the old position still queued
+        for _ in 0..10 {
+            generate_next(&mut chunks);
+        }
+        let chunk_size = SIZE as f32;
+        let path = [
+            (Vector3::new(10.5, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)),
+            (Vector3::new(10.5, 5.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+            (Vector3::new(-3.0, 5.0, 8.0), Vector3::new(0.0, 0.0, 1.0)),
+            (Vector3::new(-3.0, -20.0, 8.0), Vector3::new(1.0, 1.0, 0.0)),
+        ];
+        for (chunk_offset, direction) in path.iter() {
+            chunks.position = chunk_offset * chunk_size;
+            chunks.direction = *direction;
+            assert_eq!(
+                generate_next(&mut chunks),
+                Some(chunks.current_chunk_pos()),
+                "camera at {:?}",
+                chunks.position
+            );
+        }
+    }
+
+    // the chunks that got their data and their mesh in one frame
+    type FrameWork = (Vec<Vector3<i32>>, Vec<Vector3<i32>>);
+
+    // the cpu side of a game frame, meshes are only reserved and never built
+    fn frame(chunks: &mut Chunks, chunk_buffers: &mut MeshBufferPool) -> FrameWork {
+        let loaded = chunks
+            .chunk_data_map
+            .keys()
+            .copied()
+            .collect::<HashSet<_>>();
+        chunks.update_streaming();
+        assert_queues_consistent(chunks);
+        chunks.build_chunk_data_in_queue();
+        let mut generated = chunks
+            .chunk_data_map
+            .keys()
+            .copied()
+            .filter(|chunk_pos| !loaded.contains(chunk_pos))
+            .collect::<Vec<_>>();
+        generated.sort_by_key(|p| (p.x, p.y, p.z));
+
+        let mut meshed = Vec::new();
+        let mut budget = chunks.mesh_budget.start();
+        while budget.has_budget() {
+            match chunks.next_mesh_in_queue() {
+                Some(chunk_pos) => meshed.push(chunk_pos),
+                None => break,
+            }
+            budget.spend();
+        }
+        chunks.unload_data_queue();
+        chunks.unload_mesh_queue(chunk_buffers);
+        (generated, meshed)
+    }
+
+    fn assert_sorted(chunks: &Chunks, queue: &VecDeque<Vector3<i32>>) {
+        let center = chunks.current_chunk_pos();
+        let (_center, direction, _radius) = chunks.load_order_center.unwrap();
+        for pair in queue.iter().collect::<Vec<_>>().windows(2) {
+            assert_ne!(
+                Chunks::compare_priority(*pair[0], *pair[1], center, direction),
+                std::cmp::Ordering::Greater,
+                "{:?} is queued before {:?}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    // the load queues hold exactly the missing chunks in range, closest first
+    fn assert_queues_consistent(chunks: &Chunks) {
+        let center = chunks.current_chunk_pos();
+        let policy = chunks.policy();
+        let mut missing_data = HashSet::new();
+        let mut missing_meshes = HashSet::new();
+        let radius = policy.data_load_radius;
+        for offset in Chunks::sorted_load_offsets(radius, Vector3::unit_z()) {
+            let chunk_pos = center + offset;
+            if chunks.get_chunk(chunk_pos).is_none() {
+                missing_data.insert(chunk_pos);
+            }
+            if StreamingPolicy::in_radius(offset, policy.mesh_load_radius)
+                && !chunks.has_mesh(chunk_pos)
+                && chunks.can_build_mesh(chunk_pos)
+            {
+                missing_meshes.insert(chunk_pos);
+            }
+        }
+
+        let data_queue = chunks
+            .chunk_data_load_queue
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        assert_eq!(data_queue.len(), chunks.chunk_data_load_queue.len());
+        assert_eq!(data_queue, missing_data);
+        assert_sorted(chunks, &chunks.chunk_data_load_queue);
+
+        let mesh_queue = chunks
+            .chunk_mesh_load_queue
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        assert_eq!(mesh_queue.len(), chunks.chunk_mesh_load_queue.len());
+        assert_eq!(mesh_queue, chunks.chunk_mesh_load_set);
+        assert_eq!(mesh_queue, missing_meshes);
+        assert_sorted(chunks, &chunks.chunk_mesh_load_queue);
+
+        for (queue, set) in [
+            (
+                &chunks.chunk_data_unload_queue,
+                &chunks.chunk_data_unload_set,
+            ),
+            (
+                &chunks.chunk_mesh_unload_queue,
+                &chunks.chunk_mesh_unload_set,
+            ),
+        ]
+        .iter()
+        {
+            assert_eq!(queue.len(), set.len());
+            assert!(queue.iter().all(|chunk_pos| set.contains(chunk_pos)));
+        }
+    }
+
+    // frames of a camera turning in place while the world loads, then flying off slowly
+    // enough that the mesh queue keeps up, positions in chunks
+    fn camera_path() -> Vec<(Vector3<f32>, Vector3<f32>)> {
+        (0..100)
+            .map(|frame| {
+                let t = frame as f32;
+                let flight = (t - 20.0).max(0.0);
+                let position = Vector3::new(flight * 0.1, (flight * 0.05).sin(), -flight * 0.04);
+                let direction = Vector3::new((t * 0.1).cos(), 0.0, (t * 0.1).sin());
+                (position * SIZE as f32, direction)
+            })
+            .collect()
+    }
+
+    fn stream_along(path: &[(Vector3<f32>, Vector3<f32>)]) -> Vec<FrameWork> {
+        let mut chunks = Chunks::new();
+        chunks.set_render_distance(2);
+        chunks.data_budget = FrameBudget::Count(16);
+        chunks.mesh_budget = FrameBudget::Count(2);
+        let mut chunk_buffers = MeshBufferPool::new(
+            "test_chunk_buffers",
+            std::mem::size_of::<VoxelVertex>() as u64,
+        );
+        path.iter()
+            .map(|(position, direction)| {
+                chunks.position = *position;
+                chunks.direction = *direction;
+                let work = frame(&mut chunks, &mut chunk_buffers);
+                assert!(work.0.len() <= 16 && work.1.len() <= 2);
+                work
+            })
+            .collect()
+    }
+
+    #[test]
+    fn streaming_along_a_camera_path_is_reproducible() {
+        let path = camera_path();
+        let first = stream_along(&path);
+        assert!(first.iter().any(|(generated, _)| !generated.is_empty()));
+        assert!(first.iter().any(|(_, meshed)| !meshed.is_empty()));
+        assert_eq!(first, stream_along(&path));
+    }
+
+    // chunks inside the sphere of the given radius around a center chunk
+    fn sphere_chunk_count(radius: i32) -> usize {
+        Chunks::sorted_load_offsets(radius, Vector3::unit_z()).len()
+    }
+
+    #[test]
+    fn long_walks_stay_within_the_unload_radii() {
+        let mut chunks = Chunks::new();
+        // streaming doesn't depend on the terrain, flat terrain is the fastest to generate
+        chunks.generator = WorldGenerator::new(GeneratorKind::Flat, 0);
+        chunks
+            .set_policy(StreamingPolicy::from_render_distance(2))
+            .unwrap();
+        let policy = chunks.policy();
+        let mut chunk_buffers = MeshBufferPool::new(
+            "test_chunk_buffers",
+            std::mem::size_of::<VoxelVertex>() as u64,
+        );
+        // hundreds of chunks forward, climbing and descending 50 chunks on the way
+        for step in 0..400 {
+            let t = step as f32 * 0.75;
+            let zigzag = (t % 100.0 - 50.0).abs();
+            chunks.position = Vector3::new(t, zigzag, -t * 0.25) * SIZE as f32;
+            chunks.direction = Vector3::new(1.0, 0.0, -0.25);
+            frame(&mut chunks, &mut chunk_buffers);
+
+            let center = chunks.current_chunk_pos();
+            assert!(chunks
+                .mesh_positions()
+                .all(|p| StreamingPolicy::in_radius(*p - center, policy.mesh_unload_radius)));
+            assert!(chunks
+                .chunk_data_map
+                .keys()
+                .all(|p| StreamingPolicy::in_radius(*p - center, policy.data_unload_radius)));
+            assert!(chunks.chunk_mesh_map.len() <= sphere_chunk_count(policy.mesh_unload_radius));
+            assert!(chunks.chunk_data_map.len() <= sphere_chunk_count(policy.data_unload_radius));
+        }
+        // the walk went far enough to unload everything it started with
+        assert!(chunks.get_chunk(Vector3::new(0, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn set_policy_rejects_unload_radii_inside_load_radii() {
+        let mut chunks = Chunks::new();
+        let policy = StreamingPolicy::from_render_distance(4);
+        for invalid in [
+            StreamingPolicy {
+                mesh_unload_radius: policy.mesh_load_radius - 1,
+                ..policy
+            },
+            StreamingPolicy {
+                data_unload_radius: policy.data_load_radius - 1,
+                ..policy
+            },
+        ]
+        .iter()
+        {
+            assert!(chunks.set_policy(*invalid).is_err());
+        }
+        assert_eq!(
+            chunks.policy(),
+            StreamingPolicy::from_render_distance(RENDER_DIST_RADIUS)
+        );
+        // no hysteresis is allowed
+        let tight = StreamingPolicy {
+            mesh_unload_radius: policy.mesh_load_radius,
+            data_unload_radius: policy.data_load_radius,
+            ..policy
+        };
+        chunks.set_policy(tight).unwrap();
+        assert_eq!(chunks.policy(), tight);
+    }
+}
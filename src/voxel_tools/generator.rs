@@ -0,0 +1,196 @@
+use anyhow::{bail, Result};
+use noise::{NoiseFn, Perlin, Seedable};
+
+use super::{block::BlockType, chunk::WATER_LEVEL, voxel::Voxel};
+
+// the world before seeds could be chosen, it meant to use 484 but always ran with perlin's 0
+pub const DEFAULT_SEED: u32 = 0;
+
+// how the voxels of the world are shaped
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GeneratorKind {
+    // floating 3d noise blobs, the original world
+    Caves,
+    // the caves with every block type, glass pockets, foliage, glowing veins and water
+    Crystals,
+    // a height map of rolling hills above a water level
+    Hills,
+    // grass below zero, air above, for testing
+    Flat,
+}
+
+impl GeneratorKind {
+    pub const ALL: [GeneratorKind; 4] = [
+        GeneratorKind::Caves,
+        GeneratorKind::Crystals,
+        GeneratorKind::Hills,
+        GeneratorKind::Flat,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeneratorKind::Caves => "caves",
+            GeneratorKind::Crystals => "crystals",
+            GeneratorKind::Hills => "hills",
+            GeneratorKind::Flat => "flat",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        let name = name.trim().to_lowercase();
+        match Self::ALL.iter().find(|kind| kind.name() == name) {
+            Some(kind) => Ok(*kind),
+            None => bail!(
+                "unknown generator {:?}, expected one of {:?}",
+                name,
+                Self::ALL.iter().map(|kind| kind.name()).collect::<Vec<_>>()
+            ),
+        }
+    }
+}
+
+// the voxel at every world position, the same for the same kind and seed
+#[derive(Clone)]
+pub struct WorldGenerator {
+    pub kind: GeneratorKind,
+    pub seed: u32,
+    perlin: Perlin,
+}
+
+impl Default for WorldGenerator {
+    fn default() -> Self {
+        Self::new(GeneratorKind::Caves, DEFAULT_SEED)
+    }
+}
+
+impl WorldGenerator {
+    pub fn new(kind: GeneratorKind, seed: u32) -> Self {
+        Self {
+            kind,
+            seed,
+            perlin: Perlin::new().set_seed(seed),
+        }
+    }
+
+    pub fn voxel(&self, world_pos: cgmath::Vector3<i32>) -> Voxel {
+        match self.kind {
+            GeneratorKind::Caves => self.caves_voxel(world_pos),
+            GeneratorKind::Crystals => self.crystals_voxel(world_pos),
+            GeneratorKind::Hills => self.hills_voxel(world_pos),
+            GeneratorKind::Flat => {
+                if world_pos.y < 0 {
+                    Voxel::new_block(BlockType::Grass)
+                } else {
+                    Voxel::new_empty()
+                }
+            }
+        }
+    }
+
+    // the noise coordinates of a world position and the density there, shared by the caves
+    fn caves_density(&self, world_pos: cgmath::Vector3<i32>) -> ([f64; 3], f64) {
+        // convert noise to world
+        let down_scale = 0.027f64;
+        let x = world_pos.x as f64 * down_scale;
+        let y = world_pos.y as f64 * down_scale;
+        let z = world_pos.z as f64 * down_scale;
+        ([x, y, z], self.perlin.get([x, y, z]))
+    }
+
+    fn caves_voxel(&self, world_pos: cgmath::Vector3<i32>) -> Voxel {
+        let (_, density) = self.caves_density(world_pos);
+        if density > 0.3f64 {
+            Voxel::new_block(BlockType::Grass)
+        } else {
+            Voxel::new_empty()
+        }
+    }
+
+    fn crystals_voxel(&self, world_pos: cgmath::Vector3<i32>) -> Voxel {
+        let ([x, y, z], density) = self.caves_density(world_pos);
+        if density > 0.6f64 {
+            // crystal pockets deep inside the rock
+            Voxel::new_block(BlockType::Glass)
+        } else if density > 0.3f64 {
+            // higher frequency noise decides where foliage grows
+            let foliage = self.perlin.get([x * 4f64, y * 4f64 + 100f64, z * 4f64]);
+            // rare glowing veins
+            let glow = self.perlin.get([x * 8f64 + 200f64, y * 8f64, z * 8f64]);
+            Voxel::new_block(if glow > 0.6f64 {
+                BlockType::Glowstone
+            } else if foliage > 0.35f64 {
+                BlockType::Leaves
+            } else {
+                BlockType::Grass
+            })
+        } else {
+            Self::water_or_air(world_pos)
+        }
+    }
+
+    fn hills_voxel(&self, world_pos: cgmath::Vector3<i32>) -> Voxel {
+        let (x, z) = (world_pos.x as f64, world_pos.z as f64);
+        // broad hills with smaller bumps on top
+        let height = self.perlin.get([x * 0.01, 0.5, z * 0.01]) * 24f64
+            + self.perlin.get([x * 0.05, 10.5, z * 0.05]) * 4f64;
+        let y = world_pos.y as f64;
+        if y < height - 3f64 {
+            let glow = self.perlin.get([x * 0.2, y * 0.2, z * 0.2 + 300f64]);
+            Voxel::new_block(if glow > 0.65f64 {
+                BlockType::Glowstone
+            } else {
+                BlockType::Grass
+            })
+        } else if y < height {
+            Voxel::new_block(BlockType::Grass)
+        } else {
+            Self::water_or_air(world_pos)
+        }
+    }
+
+    // empty space below the water level is filled with water
+    fn water_or_air(world_pos: cgmath::Vector3<i32>) -> Voxel {
+        if (world_pos.y as f32) < WATER_LEVEL {
+            Voxel::new_block(BlockType::Water)
+        } else {
+            Voxel::new_empty()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions() -> impl Iterator<Item = cgmath::Vector3<i32>> {
+        (-24..24).flat_map(|x| {
+            (-24..24).flat_map(move |y| (-24..24).map(move |z| cgmath::Vector3::new(x, y, z)))
+        })
+    }
+
+    #[test]
+    fn the_default_world_is_only_grass_and_air() {
+        let generator = WorldGenerator::default();
+        assert!(positions().all(|pos| matches!(
+            generator.voxel(pos).block_type(),
+            BlockType::Grass | BlockType::Air
+        )));
+    }
+
+    #[test]
+    fn crystals_keep_the_shape_of_the_caves() {
+        let caves = WorldGenerator::new(GeneratorKind::Caves, DEFAULT_SEED);
+        let crystals = WorldGenerator::new(GeneratorKind::Crystals, DEFAULT_SEED);
+        let mut decorated = 0;
+        for pos in positions() {
+            let (cave, crystal) = (caves.voxel(pos), crystals.voxel(pos));
+            let crystal_is_solid =
+                crystal.block_type() != BlockType::Water && crystal.block_type() != BlockType::Air;
+            assert_eq!(cave.is_solid(), crystal_is_solid, "{:?}", pos);
+            if crystal.block_type() != cave.block_type() {
+                decorated += 1;
+            }
+        }
+        assert!(decorated > 0);
+    }
+}
@@ -1,27 +1,50 @@
-use crate::rendering::gpu_resources::GpuResources;
+use crate::{rendering::gpu_resources::GpuResources, timing::Span};
 
 use super::{
     chunk,
     chunks::{adjacent_voxels, Chunks},
 };
 use super::{
+    block::{BlockType, Transparency},
     direction::Direction,
     quad::Quad,
-    rendering::voxel_vertex::VoxelVertex,
+    rendering::{block_textures::BlockLayers, voxel_vertex::VoxelVertex},
     voxel::Voxel,
 };
-use wgpu::util::DeviceExt;
 
-pub fn build_chunk_mesh(
-    chunks: &mut Chunks,
-    //chunk: &mut Chunk,
-    device: &wgpu::Device,
-    gpu_resources: &mut GpuResources,
+// bump whenever build_chunk_mesh_data produces different meshes for the same voxels,
+// cached meshes of other versions are rebuilt
+pub const MESHER_VERSION: u32 = 1;
+
+// vertices and indices of one chunk, built on the cpu before they are uploaded
+#[derive(Default)]
+pub struct ChunkMeshData {
+    pub vertices: Vec<VoxelVertex>,
+    pub indices: Vec<u32>,
+    pub translucent_vertices: Vec<VoxelVertex>,
+    pub translucent_indices: Vec<u32>,
+}
+
+impl ChunkMeshData {
+    // of both meshes
+    pub fn num_vertices(&self) -> u32 {
+        (self.vertices.len() + self.translucent_vertices.len()) as u32
+    }
+
+    pub fn num_indices(&self) -> u32 {
+        (self.indices.len() + self.translucent_indices.len()) as u32
+    }
+}
+
+// needs the voxel data of the chunk and its adjacent chunks, doesn't touch the gpu
+pub fn build_chunk_mesh_data(
+    chunks: &Chunks,
     chunk_pos: &cgmath::Vector3<i32>,
     chunk_world_pos: &cgmath::Vector3<f32>,
-) -> bool {
+) -> ChunkMeshData {
     let chunk_size = chunk::SIZE as i32;
-    let mut quads = Vec::<Quad>::new();
+    let block_layers = chunks.block_layers;
+    let mut quads = ChunkQuads::default();
     for x in 0..chunk_size {
         for y in 0..chunk_size {
             for z in 0..chunk_size {
@@ -29,14 +52,80 @@ pub fn build_chunk_mesh(
                 let voxel_pos_world = chunk_world_pos + voxel_pos_local;
                 if let Ok((voxel, back, left, down)) = adjacent_voxels(chunks, (x, y, z), chunk_pos)
                 {
-                    process_voxel(&voxel, voxel_pos_world, &left, &down, &back, &mut quads);
+                    process_voxel(
+                        voxel,
+                        voxel_pos_world,
+                        left,
+                        down,
+                        back,
+                        &block_layers,
+                        &mut quads,
+                    );
                 }
             }
         }
     }
-    if quads.is_empty() {
-        //panic!("ouuuuh woops");
+    let (vertices, indices) = build_vertices(quads.opaque);
+    let (translucent_vertices, translucent_indices) = build_vertices(quads.translucent);
+    ChunkMeshData {
+        vertices,
+        indices,
+        translucent_vertices,
+        translucent_indices,
     }
+}
+
+pub fn build_chunk_mesh(
+    chunks: &mut Chunks,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    gpu_resources: &mut GpuResources,
+    chunk_pos: &cgmath::Vector3<i32>,
+    chunk_world_pos: &cgmath::Vector3<f32>,
+) -> bool {
+    let span = Span::start("meshing");
+    let mesh_data = build_chunk_mesh_data(chunks, chunk_pos, chunk_world_pos);
+    span.end(&mut chunks.timings, chunk_pos);
+    upload_chunk_mesh(chunks, device, queue, gpu_resources, chunk_pos, &mesh_data)
+}
+
+// into the mesh given to the chunk by Chunks::next_mesh_in_queue, false for empty meshes
+pub fn upload_chunk_mesh(
+    chunks: &mut Chunks,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    gpu_resources: &mut GpuResources,
+    chunk_pos: &cgmath::Vector3<i32>,
+    mesh_data: &ChunkMeshData,
+) -> bool {
+    let span = Span::start("upload");
+    let chunk_mesh = match chunks.get_chunk_mesh_mut(chunk_pos) {
+        Some(chunk_mesh) => chunk_mesh,
+        None => return false,
+    };
+    let allocation = gpu_resources.chunk_buffers.upload(
+        device,
+        queue,
+        bytemuck::cast_slice(&mesh_data.vertices),
+        &mesh_data.indices,
+    );
+    let translucent_allocation = gpu_resources.chunk_buffers.upload(
+        device,
+        queue,
+        bytemuck::cast_slice(&mesh_data.translucent_vertices),
+        &mesh_data.translucent_indices,
+    );
+    chunk_mesh.update_allocation(
+        allocation,
+        translucent_allocation,
+        mesh_data.num_indices(),
+        mesh_data.num_vertices(),
+    );
+    span.end(&mut chunks.timings, chunk_pos);
+    mesh_data.num_vertices() != 0
+}
+
+fn build_vertices(quads: Vec<Quad>) -> (Vec<VoxelVertex>, Vec<u32>) {
     let mut voxel_vertices = Vec::<VoxelVertex>::new();
     let mut indices = Vec::<u32>::new();
     let mut vert_index = 0;
@@ -47,6 +136,8 @@ pub fn build_chunk_mesh(
                 position: quad.corners[index].into(),
                 normal: normal.into(),
                 color_diffuse: quad.color.into(),
+                tex_coords: quad.tex_coords(index),
+                emission: quad.emission,
             });
         });
         indices.push(vert_index);
@@ -57,68 +148,61 @@ pub fn build_chunk_mesh(
         indices.push(vert_index + 3);
         vert_index += 4;
     }
-    if let Some(chunk_mesh) = chunks.get_chunk_mesh_mut(chunk_pos) {
-        let num_indices = indices.len() as u32;
-        let num_vertices = voxel_vertices.len() as u32;
-        let (v_buf, i_buf) = construct_buffers(device, voxel_vertices, indices);
-        let v_buf = gpu_resources.buffer_arena.insert(v_buf);
-        let i_buf = gpu_resources.buffer_arena.insert(i_buf);
-        chunk_mesh.update_vertex_buffers(v_buf, i_buf, num_indices, num_vertices);
-        return num_vertices != 0;
-    }
-    false
+    (voxel_vertices, indices)
 }
 
+// every voxel only looks at its left, down and back neighbour, the faces in between
+// are emitted from both sides: the voxel's face towards the neighbour and the
+// neighbour's face towards the voxel, both lie at the same position
 fn process_voxel(
     voxel: &Voxel,
     voxel_pos: cgmath::Vector3<f32>,
     left: &Voxel,
     down: &Voxel,
     back: &Voxel,
-    quads: &mut Vec<Quad>,
+    block_layers: &BlockLayers,
+    quads: &mut ChunkQuads,
 ) {
-    match voxel.is_solid() {
-        true => {
-            // voxel is solid
-            if !left.is_solid() {
-                quads.push(Quad::from_direction(Direction::Left, voxel_pos));
-            }
-            if !down.is_solid() {
-                quads.push(Quad::from_direction(Direction::Down, voxel_pos));
-            }
-            if !back.is_solid() {
-                quads.push(Quad::from_direction(Direction::Back, voxel_pos));
-            }
+    let block = voxel.block_type();
+    let neighbours = [
+        (left.block_type(), Direction::Left, Direction::Right),
+        (down.block_type(), Direction::Down, Direction::Up),
+        (back.block_type(), Direction::Back, Direction::Forward),
+    ];
+    for (neighbour, towards_neighbour, towards_voxel) in neighbours.iter() {
+        if block.is_face_visible(*neighbour) {
+            let layer = block_layers.get(block);
+            let quad = Quad {
+                emission: block.emission(),
+                ..Quad::from_direction(*towards_neighbour, voxel_pos, block.color(), layer)
+            };
+            quads.push(block, quad);
         }
-        false => {
-            // voxel is not solid
-            if left.is_solid() {
-                quads.push(Quad::from_direction(Direction::Right, voxel_pos));
-            }
-            if down.is_solid() {
-                quads.push(Quad::from_direction(Direction::Up, voxel_pos));
-            }
-            if back.is_solid() {
-                quads.push(Quad::from_direction(Direction::Forward, voxel_pos));
-            }
+        if neighbour.is_face_visible(block) {
+            let layer = block_layers.get(*neighbour);
+            let quad = Quad {
+                emission: neighbour.emission(),
+                ..Quad::from_direction(*towards_voxel, voxel_pos, neighbour.color(), layer)
+            };
+            quads.push(*neighbour, quad);
         }
     }
 }
 
-fn construct_buffers(
-    device: &wgpu::Device,
-    vertices: Vec<VoxelVertex>,
-    indices: Vec<u32>,
-) -> (wgpu::Buffer, wgpu::Buffer) {
-    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("voxel_chunk_vertices"),
-        contents: bytemuck::cast_slice(&vertices),
-        usage: wgpu::BufferUsage::VERTEX,
-    });
-    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("voxel_chunk_indices"),
-        contents: bytemuck::cast_slice(&indices),
-        usage: wgpu::BufferUsage::INDEX,
-    });
-    (vertex_buffer, index_buffer)
+// faces of one chunk, split by the mesh they end up in
+#[derive(Default)]
+struct ChunkQuads {
+    opaque: Vec<Quad>,
+    translucent: Vec<Quad>,
+}
+
+impl ChunkQuads {
+    fn push(&mut self, block: BlockType, quad: Quad) {
+        match block.transparency() {
+            Transparency::Translucent => self.translucent.push(quad),
+            Transparency::Opaque | Transparency::Cutout => self.opaque.push(quad),
+            // is_face_visible never lets empty blocks have faces
+            Transparency::Empty => {}
+        }
+    }
 }
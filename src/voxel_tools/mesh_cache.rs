@@ -0,0 +1,309 @@
+use anyhow::{bail, Context, Result};
+use bytemuck::Zeroable;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+use super::{
+    block::BlockType,
+    chunks::Chunks,
+    mesh_builder::{ChunkMeshData, MESHER_VERSION},
+    rendering::voxel_vertex::VoxelVertex,
+};
+
+// MESH_CACHE_DIR=path turns the cache on, it is off by default
+pub const MESH_CACHE_ENV: &str = "MESH_CACHE_DIR";
+// start of every cache file, followed by the mesher version
+const MAGIC: &[u8; 4] = b"TMMC";
+// magic, mesher version, input hash and the four array lengths
+const HEADER_SIZE: usize = 4 + 4 + 8 + 4 * 4;
+
+// fnv-1a, unlike the std hashers guaranteed to give the same hash in every run and build
+// as long as it is only fed explicit bytes, derived Hash output depends on the platform
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// everything the mesh of a chunk is built from: its voxels, the voxels of the adjacent chunks
+// and the texture layers, any edit to them changes the hash and so invalidates the cached mesh
+pub fn mesh_input_hash(chunks: &Chunks, chunk_pos: cgmath::Vector3<i32>) -> u64 {
+    use cgmath::Vector3 as vec;
+    let mut hasher = StableHasher::default();
+    for offset in [
+        vec::<i32>::new(0, 0, 0),
+        -vec::<i32>::unit_x(),
+        vec::<i32>::unit_x(),
+        -vec::<i32>::unit_y(),
+        vec::<i32>::unit_y(),
+        -vec::<i32>::unit_z(),
+        vec::<i32>::unit_z(),
+    ]
+    .iter()
+    {
+        // a missing neighbour hashes differently from an empty one
+        match chunks.get_chunk(chunk_pos + offset) {
+            Some(chunk) => {
+                hasher.write_u8(1);
+                for voxel in chunk.voxels.iter() {
+                    hasher.write(&[voxel.density(), voxel.block_type() as u8]);
+                }
+            }
+            None => hasher.write_u8(0),
+        }
+    }
+    for block_type in BlockType::ALL.iter() {
+        hasher.write(&chunks.block_layers.get(*block_type).to_le_bytes());
+    }
+    hasher.finish()
+}
+
+// built chunk meshes stored as one file per chunk position, reused while the mesher version
+// and the input hash match
+pub struct MeshCache {
+    dir: PathBuf,
+    hits: u64,
+    misses: u64,
+}
+
+impl MeshCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("creating the mesh cache directory {:?}", dir))?;
+        log::info!("caching chunk meshes in {:?}", dir);
+        Ok(Self {
+            dir,
+            hits: 0,
+            misses: 0,
+        })
+    }
+
+    // None when the environment doesn't ask for a cache
+    pub fn from_env() -> Result<Option<Self>> {
+        match std::env::var_os(MESH_CACHE_ENV) {
+            Some(dir) => Self::new(dir).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn path(&self, chunk_pos: cgmath::Vector3<i32>) -> PathBuf {
+        self.dir.join(format!(
+            "{}_{}_{}.mesh",
+            chunk_pos.x, chunk_pos.y, chunk_pos.z
+        ))
+    }
+
+    // None if nothing is cached for the position or it was built from other data,
+    // unreadable files are treated the same and overwritten by the next store
+    pub fn load(&mut self, chunk_pos: cgmath::Vector3<i32>, hash: u64) -> Option<ChunkMeshData> {
+        let path = self.path(chunk_pos);
+        let mesh_data = match std::fs::read(&path) {
+            Ok(bytes) => decode(&bytes, hash).unwrap_or_else(|e| {
+                log::warn!("ignoring the cached mesh {:?}: {:?}", path, e);
+                None
+            }),
+            Err(_) => None,
+        };
+        match mesh_data {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        mesh_data
+    }
+
+    pub fn store(&self, chunk_pos: cgmath::Vector3<i32>, hash: u64, mesh_data: &ChunkMeshData) {
+        let path = self.path(chunk_pos);
+        // written next to the target and renamed, so a crash never leaves half a file
+        let temp_path = path.with_extension("tmp");
+        let result = std::fs::write(&temp_path, encode(hash, mesh_data))
+            .and_then(|_| std::fs::rename(&temp_path, &path));
+        if let Err(e) = result {
+            log::warn!("failed to cache the mesh {:?}: {:?}", path, e);
+        }
+    }
+
+    pub fn log_summary(&self) {
+        log::info!("mesh cache: {} hits, {} misses", self.hits, self.misses);
+    }
+}
+
+pub fn encode(hash: u64, mesh_data: &ChunkMeshData) -> Vec<u8> {
+    let arrays: [&[u8]; 4] = [
+        bytemuck::cast_slice(&mesh_data.vertices),
+        bytemuck::cast_slice(&mesh_data.indices),
+        bytemuck::cast_slice(&mesh_data.translucent_vertices),
+        bytemuck::cast_slice(&mesh_data.translucent_indices),
+    ];
+    let lengths = [
+        mesh_data.vertices.len(),
+        mesh_data.indices.len(),
+        mesh_data.translucent_vertices.len(),
+        mesh_data.translucent_indices.len(),
+    ];
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + arrays.iter().map(|a| a.len()).sum::<usize>());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&MESHER_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&hash.to_le_bytes());
+    for length in lengths.iter() {
+        bytes.extend_from_slice(&(*length as u32).to_le_bytes());
+    }
+    for array in arrays.iter() {
+        bytes.extend_from_slice(array);
+    }
+    bytes
+}
+
+// Ok(None) for a mesh of another mesher version or hash, errors for broken files
+pub fn decode(bytes: &[u8], hash: u64) -> Result<Option<ChunkMeshData>> {
+    if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+        bail!("not a mesh cache file");
+    }
+    let u32_at = |offset: usize| {
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&bytes[offset..offset + 4]);
+        u32::from_le_bytes(word)
+    };
+    let mut stored_hash = [0u8; 8];
+    stored_hash.copy_from_slice(&bytes[8..16]);
+    if u32_at(4) != MESHER_VERSION || u64::from_le_bytes(stored_hash) != hash {
+        return Ok(None);
+    }
+
+    let lengths = [u32_at(16), u32_at(20), u32_at(24), u32_at(28)];
+    let vertex_size = std::mem::size_of::<VoxelVertex>();
+    let expected = HEADER_SIZE
+        + (lengths[0] as usize + lengths[2] as usize) * vertex_size
+        + (lengths[1] as usize + lengths[3] as usize) * 4;
+    if bytes.len() != expected {
+        bail!("expected {} bytes, found {}", expected, bytes.len());
+    }
+
+    // the file bytes aren't aligned for the vertex type, so they are copied into place
+    let mut offset = HEADER_SIZE;
+    let mut read = |target: &mut [u8]| {
+        target.copy_from_slice(&bytes[offset..offset + target.len()]);
+        offset += target.len();
+    };
+    let mut mesh_data = ChunkMeshData {
+        vertices: vec![VoxelVertex::zeroed(); lengths[0] as usize],
+        indices: vec![0u32; lengths[1] as usize],
+        translucent_vertices: vec![VoxelVertex::zeroed(); lengths[2] as usize],
+        translucent_indices: vec![0u32; lengths[3] as usize],
+    };
+    read(bytemuck::cast_slice_mut(&mut mesh_data.vertices));
+    read(bytemuck::cast_slice_mut(&mut mesh_data.indices));
+    read(bytemuck::cast_slice_mut(
+        &mut mesh_data.translucent_vertices,
+    ));
+    read(bytemuck::cast_slice_mut(&mut mesh_data.translucent_indices));
+    Ok(Some(mesh_data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_tools::{chunk::Chunk, voxel::Voxel};
+    use cgmath::Vector3;
+
+    fn vertex(x: f32) -> VoxelVertex {
+        VoxelVertex {
+            position: [x, 1.0, 2.0],
+            normal: [0.0, 1.0, 0.0],
+            color_diffuse: [1.0, 0.5, 0.25, 1.0],
+            tex_coords: [0.0, 1.0, 3.0],
+            emission: 0.5,
+        }
+    }
+
+    fn mesh_data() -> ChunkMeshData {
+        ChunkMeshData {
+            vertices: vec![vertex(0.0), vertex(1.0), vertex(2.0)],
+            indices: vec![0, 1, 2],
+            translucent_vertices: vec![vertex(3.0)],
+            translucent_indices: vec![0, 0, 0, 0, 0, 0],
+        }
+    }
+
+    fn as_bytes(mesh_data: &ChunkMeshData) -> Vec<Vec<u8>> {
+        vec![
+            bytemuck::cast_slice(&mesh_data.vertices).to_vec(),
+            bytemuck::cast_slice(&mesh_data.indices).to_vec(),
+            bytemuck::cast_slice(&mesh_data.translucent_vertices).to_vec(),
+            bytemuck::cast_slice(&mesh_data.translucent_indices).to_vec(),
+        ]
+    }
+
+    #[test]
+    fn decode_returns_what_was_encoded() {
+        let decoded = decode(&encode(42, &mesh_data()), 42).unwrap().unwrap();
+        assert_eq!(as_bytes(&decoded), as_bytes(&mesh_data()));
+
+        let empty = decode(&encode(7, &ChunkMeshData::default()), 7)
+            .unwrap()
+            .unwrap();
+        assert_eq!(as_bytes(&empty), as_bytes(&ChunkMeshData::default()));
+    }
+
+    #[test]
+    fn meshes_built_from_other_data_are_ignored() {
+        let bytes = encode(42, &mesh_data());
+        assert!(decode(&bytes, 43).unwrap().is_none());
+
+        let mut other_version = bytes;
+        other_version[4..8].copy_from_slice(&(MESHER_VERSION + 1).to_le_bytes());
+        assert!(decode(&other_version, 42).unwrap().is_none());
+    }
+
+    #[test]
+    fn broken_files_are_errors() {
+        let bytes = encode(42, &mesh_data());
+        assert!(decode(&bytes[..bytes.len() - 1], 42).is_err());
+        assert!(decode(&bytes[..HEADER_SIZE - 1], 42).is_err());
+        assert!(decode(&[], 42).is_err());
+
+        let mut bad_magic = bytes;
+        bad_magic[0] = b'X';
+        assert!(decode(&bad_magic, 42).is_err());
+    }
+
+    #[test]
+    fn neighbour_voxels_change_the_hash() {
+        let origin = Vector3::new(0, 0, 0);
+        let mut chunks = Chunks::new();
+        let empty_hash = mesh_input_hash(&chunks, origin);
+
+        chunks.insert_chunk_data(origin, Chunk::new());
+        chunks.insert_chunk_data(Vector3::unit_x(), Chunk::new());
+        let loaded_hash = mesh_input_hash(&chunks, origin);
+        assert_ne!(loaded_hash, empty_hash);
+        assert_eq!(mesh_input_hash(&chunks, origin), loaded_hash);
+
+        let mut neighbour = Chunk::new();
+        neighbour.voxels[0] = Voxel::new_block(BlockType::Grass);
+        chunks.insert_chunk_data(Vector3::unit_x(), neighbour);
+        let edited_hash = mesh_input_hash(&chunks, origin);
+        assert_ne!(edited_hash, loaded_hash);
+
+        // chunks that don't touch the one being meshed don't matter
+        let mut far = Chunk::new();
+        far.voxels[0] = Voxel::new_block(BlockType::Grass);
+        chunks.insert_chunk_data(Vector3::new(2, 0, 0), far);
+        assert_eq!(mesh_input_hash(&chunks, origin), edited_hash);
+    }
+}
@@ -1,19 +1,26 @@
 use super::direction::Direction;
 use crate::color::Color;
 use cgmath::Vector3;
-use rand::Rng;
 
 pub struct Quad {
     pub color: Color,
     pub direction: Direction,
     // in world position
     pub corners: [Vector3<f32>; 4],
+    // layer in the block texture array
+    pub layer: u32,
+    pub emission: f32,
 }
 
 const HALF_SIZE: f32 = 0.5f32;
 
 impl Quad {
-    pub fn from_direction(direction: Direction, pos: Vector3<f32>) -> Self {
+    pub fn from_direction(
+        direction: Direction,
+        pos: Vector3<f32>,
+        color: Color,
+        layer: u32,
+    ) -> Self {
         let corners = match direction {
             Direction::Left => [
                 Vector3::new(pos.x - HALF_SIZE, pos.y - HALF_SIZE, pos.z - HALF_SIZE),
@@ -54,18 +61,26 @@ impl Quad {
             ],
         };
 
-        let green_range = 0.4f32;
         Self {
             corners,
-            //color: color::colors::GREEN,
-            //color: Color::new(0.7f32, (1.-green_range) + rand::thread_rng().gen_range(0f32..green_range), 0.3f32, 1.),
-            color: Color::new(
-                rand::thread_rng().gen_range(0f32..0.1f32),
-                (1. - green_range) + rand::thread_rng().gen_range(0f32..green_range),
-                rand::thread_rng().gen_range(0f32..0.1f32),
-                1.,
-            ),
+            color,
             direction,
+            layer,
+            emission: 0f32,
         }
     }
+
+    // u, v and texture layer of a corner
+    // uvs come from the world position on the face plane, one texture repeat per voxel,
+    // so a quad spanning several voxels tiles the texture instead of stretching it
+    pub fn tex_coords(&self, corner: usize) -> [f32; 3] {
+        // corners lie on half voxel positions, shift them onto voxel edges
+        let p = self.corners[corner] + Vector3::new(HALF_SIZE, HALF_SIZE, HALF_SIZE);
+        let (u, v) = match self.direction {
+            Direction::Left | Direction::Right => (p.z, -p.y),
+            Direction::Down | Direction::Up => (p.x, p.z),
+            Direction::Back | Direction::Forward => (p.x, -p.y),
+        };
+        [u, v, self.layer as f32]
+    }
 }
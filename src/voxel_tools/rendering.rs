@@ -1,3 +1,4 @@
+pub mod block_textures;
 pub mod voxel_pipeline;
 pub mod voxel_rendering;
 pub mod voxel_vertex;
@@ -0,0 +1,301 @@
+use anyhow::*;
+use std::path::Path;
+
+use crate::{
+    rendering::mipmaps,
+    texture::{self, TextureOptions},
+    voxel_tools::block::BlockType,
+};
+
+pub const BLOCK_TEXTURE_DIR: &str = "res/blocks";
+// plain white layer, blocks without a texture only show their vertex color
+pub const BLANK_LAYER: u32 = 0;
+
+// texture array layer of every block type, indexed by BlockType as u8
+#[derive(Copy, Clone, Debug)]
+pub struct BlockLayers([u32; BlockType::COUNT]);
+
+impl Default for BlockLayers {
+    fn default() -> Self {
+        Self([BLANK_LAYER; BlockType::COUNT])
+    }
+}
+
+impl BlockLayers {
+    pub fn get(&self, block_type: BlockType) -> u32 {
+        self.0[block_type as usize]
+    }
+}
+
+// block textures as layers of one rgba image, all scaled to the same tile size
+pub struct PackedBlockTextures {
+    pub tile_size: u32,
+    // tile_size * tile_size * 4 bytes per layer
+    pub pixels: Vec<u8>,
+    // name of every layer, the first is the blank layer
+    pub names: Vec<String>,
+}
+
+impl PackedBlockTextures {
+    pub fn layer_count(&self) -> u32 {
+        self.names.len() as u32
+    }
+
+    pub fn layer(&self, name: &str) -> Option<u32> {
+        self.names
+            .iter()
+            .position(|layer_name| layer_name == name)
+            .map(|index| index as u32)
+    }
+
+    // blocks whose texture is missing fall back to the blank layer
+    pub fn block_layers(&self) -> BlockLayers {
+        let mut layers = BlockLayers::default();
+        for block_type in BlockType::ALL.iter() {
+            if let Some(layer) = block_type.texture_name().and_then(|name| self.layer(name)) {
+                layers.0[*block_type as usize] = layer;
+            }
+        }
+        layers
+    }
+}
+
+// the largest image decides the tile size, smaller ones are scaled up
+// layers are sorted by name so the result doesn't depend on directory order
+pub fn pack_block_textures(mut images: Vec<(String, image::RgbaImage)>) -> PackedBlockTextures {
+    images.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let tile_size = images
+        .iter()
+        .map(|(_name, image)| image.width().max(image.height()))
+        .max()
+        .unwrap_or(1);
+    let layer_size = (tile_size * tile_size * 4) as usize;
+
+    let mut pixels = Vec::with_capacity(layer_size * (images.len() + 1));
+    let mut names = Vec::with_capacity(images.len() + 1);
+    pixels.resize(layer_size, 255u8);
+    names.push(String::from("blank"));
+    for (name, image) in images {
+        let image = if image.width() != tile_size || image.height() != tile_size {
+            image::imageops::resize(
+                &image,
+                tile_size,
+                tile_size,
+                image::imageops::FilterType::Nearest,
+            )
+        } else {
+            image
+        };
+        pixels.extend_from_slice(image.as_raw());
+        names.push(name);
+    }
+    PackedBlockTextures {
+        tile_size,
+        pixels,
+        names,
+    }
+}
+
+// every png in the directory, named after the file stem
+pub fn load_block_images<P: AsRef<Path>>(dir: P) -> Result<Vec<(String, image::RgbaImage)>> {
+    let mut images = Vec::new();
+    for entry in std::fs::read_dir(dir.as_ref())
+        .with_context(|| format!("reading block textures from {:?}", dir.as_ref()))?
+    {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("png") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .context("block texture without a name")?
+            .to_string();
+        log::debug!("loading block texture: {:?}", path);
+        let image = image::open(&path)
+            .with_context(|| format!("loading block texture {:?}", path))?
+            .to_rgba8();
+        images.push((name, image));
+    }
+    Ok(images)
+}
+
+// every block texture in one 2d texture array, sample with AddressMode::Repeat
+// so tiling uvs work across quads spanning several voxels
+pub struct BlockTextureArray {
+    pub texture: wgpu::Texture,
+    pub bind_group: wgpu::BindGroup,
+    pub layers: BlockLayers,
+}
+
+impl BlockTextureArray {
+    pub fn load<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        dir: P,
+        options: &TextureOptions,
+    ) -> Result<Self> {
+        let packed = pack_block_textures(load_block_images(dir)?);
+        Ok(Self::new(device, queue, layout, &packed, options))
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        packed: &PackedBlockTextures,
+        options: &TextureOptions,
+    ) -> Self {
+        let mip_level_count = if options.mipmaps {
+            mipmaps::mip_level_count(packed.tile_size, packed.tile_size)
+        } else {
+            1
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: packed.tile_size,
+                height: packed.tile_size,
+                depth_or_array_layers: packed.layer_count(),
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: options.color_space.format(),
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            label: Some("block_textures"),
+        });
+
+        // layers are mipmapped one by one so neighbouring tiles never bleed into each other
+        let layer_size = (packed.tile_size * packed.tile_size * 4) as usize;
+        for (layer, pixels) in packed.pixels.chunks(layer_size).enumerate() {
+            let image =
+                image::RgbaImage::from_raw(packed.tile_size, packed.tile_size, pixels.to_vec())
+                    .expect("packed layers are tile sized");
+            let levels = if options.mipmaps {
+                mipmaps::generate_mip_chain(&image, options.color_space)
+            } else {
+                vec![image]
+            };
+            for (mip_level, level) in levels.iter().enumerate() {
+                texture::write_mip_level(
+                    queue,
+                    &texture,
+                    mip_level as u32,
+                    level.as_raw(),
+                    packed.tile_size,
+                    packed.tile_size,
+                    layer as u32,
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("block_textures_view"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = options
+            .sampler
+            .create_sampler(device, Some("block_textures_sampler"));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("block_textures_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        Self {
+            texture,
+            bind_group,
+            layers: packed.block_layers(),
+        }
+    }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("block_textures_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(size: u32, value: u8) -> image::RgbaImage {
+        image::RgbaImage::from_pixel(size, size, image::Rgba([value, value, value, 255]))
+    }
+
+    fn layer_pixels(packed: &PackedBlockTextures, layer: u32) -> &[u8] {
+        let layer_size = (packed.tile_size * packed.tile_size * 4) as usize;
+        &packed.pixels[layer as usize * layer_size..(layer as usize + 1) * layer_size]
+    }
+
+    #[test]
+    fn layers_are_sorted_by_name_after_the_blank_layer() {
+        let packed = pack_block_textures(vec![
+            (String::from("water"), solid(2, 30)),
+            (String::from("grass"), solid(2, 10)),
+            (String::from("glass"), solid(2, 20)),
+        ]);
+        assert_eq!(packed.names, ["blank", "glass", "grass", "water"]);
+        assert_eq!(packed.layer_count(), 4);
+        assert_eq!(packed.pixels.len(), 4 * 2 * 2 * 4);
+        assert!(layer_pixels(&packed, BLANK_LAYER).iter().all(|v| *v == 255));
+        assert_eq!(layer_pixels(&packed, 1)[0], 20);
+        assert_eq!(layer_pixels(&packed, 2)[0], 10);
+        assert_eq!(layer_pixels(&packed, 3)[0], 30);
+    }
+
+    #[test]
+    fn smaller_images_are_scaled_up_to_the_tile_size() {
+        let packed = pack_block_textures(vec![
+            (String::from("glass"), solid(4, 20)),
+            (String::from("grass"), solid(1, 10)),
+        ]);
+        assert_eq!(packed.tile_size, 4);
+        assert_eq!(packed.pixels.len(), 3 * 4 * 4 * 4);
+        let grass = layer_pixels(&packed, packed.layer("grass").unwrap());
+        assert!(grass.chunks(4).all(|pixel| pixel == [10, 10, 10, 255]));
+    }
+
+    #[test]
+    fn blocks_without_a_texture_use_the_blank_layer() {
+        let packed = pack_block_textures(vec![(String::from("grass"), solid(2, 10))]);
+        let layers = packed.block_layers();
+        assert_eq!(layers.get(BlockType::Grass), 1);
+        // no image loaded
+        assert_eq!(layers.get(BlockType::Water), BLANK_LAYER);
+        // never drawn
+        assert_eq!(layers.get(BlockType::Air), BLANK_LAYER);
+    }
+}
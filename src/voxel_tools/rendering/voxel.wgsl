@@ -9,22 +9,28 @@ var<uniform> u_camera: CameraUniform;
 
 struct VertexOutput {
     [[builtin(position)]] builtin_position: vec4<f32>;
-    [[location(1)]] diffuse_color: vec3<f32>;
+    [[location(1)]] diffuse_color: vec4<f32>;
     [[location(2)]] normal: vec3<f32>;
     [[location(3)]] position: vec3<f32>;
+    [[location(4)]] tex_coords: vec3<f32>;
+    [[location(5)]] emission: f32;
 };
 
 [[stage(vertex)]]
 fn vs_main(
     [[location(0)]] position: vec3<f32>,
     [[location(1)]] normal: vec3<f32>,
-    [[location(2)]] diffuse_color: vec3<f32>,
+    [[location(2)]] diffuse_color: vec4<f32>,
+    [[location(3)]] tex_coords: vec3<f32>,
+    [[location(4)]] emission: f32,
 ) -> VertexOutput {
     var out: VertexOutput;
     out.normal = normal;
     let model_space = vec4<f32>(position, 1.0);
     out.position = model_space.xyz;
     out.diffuse_color = diffuse_color;
+    out.tex_coords = tex_coords;
+    out.emission = emission;
 
     out.builtin_position = u_camera.projection_view * model_space;
     return out;
@@ -35,32 +41,178 @@ fn vs_main(
 struct LightUniform {
     position: vec3<f32>;
     color: vec3<f32>;
+    sun_direction: vec3<f32>;
+    sun_color: vec3<f32>;
+    ambient_color: vec3<f32>;
 };
 
 [[group(1), binding(0)]]
 var<uniform> u_light: LightUniform;
 
-[[stage(fragment), early_depth_test]]
+[[group(2), binding(0)]]
+var t_blocks: texture_2d_array<f32>;
+[[group(2), binding(1)]]
+var s_blocks: sampler;
+
+[[block]]
+struct ShadowUniform {
+    cascades: [[stride(64)]] array<mat4x4<f32>, 3>;
+    texel_sizes: vec4<f32>;
+    // x: size of a texel in uv, y: normal offset in texels
+    params: vec4<f32>;
+};
+
+[[group(3), binding(0)]]
+var<uniform> u_shadow: ShadowUniform;
+[[group(3), binding(1)]]
+var t_shadow: texture_depth_2d_array;
+[[group(3), binding(2)]]
+var s_shadow: sampler_comparison;
+
+// 3x3 percentage closer filtering, 0 = in shadow, 1 = lit
+fn shadow_pcf(uv: vec2<f32>, cascade: i32, depth: f32) -> f32 {
+    var lit: f32 = 0.0;
+    var i: i32 = 0;
+    loop {
+        if (i >= 9) {
+            break;
+        }
+        let offset = vec2<f32>(f32(i % 3 - 1), f32(i / 3 - 1)) * u_shadow.params.x;
+        lit = lit + textureSampleCompare(t_shadow, s_shadow, uv + offset, cascade, depth);
+        continuing {
+            i = i + 1;
+        }
+    }
+    return lit / 9.0;
+}
+
+// the first cascade containing the position has the sharpest shadows
+fn sun_shadow(position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    var cascade: i32 = 0;
+    loop {
+        if (cascade >= 3) {
+            break;
+        }
+        let offset_position = position + normal * u_shadow.texel_sizes[cascade] * u_shadow.params.y;
+        let light_space = u_shadow.cascades[cascade] * vec4<f32>(offset_position, 1.0);
+        let ndc = light_space.xyz / light_space.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, ndc.y * -0.5 + 0.5);
+        if (all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0)) && ndc.z <= 1.0) {
+            return shadow_pcf(uv, cascade, ndc.z);
+        }
+        continuing {
+            cascade = cascade + 1;
+        }
+    }
+    // beyond the shadow distance
+    return 1.0;
+}
+
+// light reaching a surface, before its color is applied
+fn surface_light(position: vec3<f32>, normal: vec3<f32>, emission: f32) -> vec3<f32> {
+    let sun_strength = max(dot(normal, u_light.sun_direction), 0.0);
+    var sun_color: vec3<f32> = vec3<f32>(0.0);
+    // faces turned away from the sun are dark anyway, skip the shadow lookups
+    if (sun_strength > 0.0) {
+        sun_color = u_light.sun_color * sun_strength * sun_shadow(position, normal);
+    }
+
+    let view_dir = normalize(u_camera.position - position);
+    let point_color = point_lighting(position, normal, view_dir);
+
+    // emissive blocks stay bright without light and go past 1 into the bloom
+    return u_light.ambient_color + point_color + sun_color + vec3<f32>(emission);
+}
+
+fn block_color(tex_coords: vec3<f32>) -> vec4<f32> {
+    // the layer is interpolated as a float but is the same on every vertex of a face
+    let layer = i32(tex_coords.z + 0.5);
+    return textureSample(t_blocks, s_blocks, tex_coords.xy, layer);
+}
+
+struct SceneOutput {
+    [[location(0)]] color: vec4<f32>;
+    // the part of color lit by the ambient light, only kept by the opaque pipeline for ssao
+    [[location(1)]] ambient: vec4<f32>;
+};
+
+[[stage(fragment)]]
 fn fs_main(
     in: VertexOutput,
-) -> [[location(0)]] vec4<f32> {
-    let ambient_strength = 0.2;
-    let ambient_color = u_light.color * ambient_strength;
+) -> SceneOutput {
+    let texture_color = block_color(in.tex_coords);
+    // holes in cutout blocks like leaves
+    if (texture_color.a < 0.5) {
+        discard;
+    }
+    let surface_color = texture_color * in.diffuse_color;
+    let result = surface_light(in.position, normalize(in.normal), in.emission) * surface_color.xyz;
 
-    let normal = normalize(in.normal);
-    let light_dir = normalize(u_light.position - in.position);
-    let diffuse_strength = max(dot(normal, light_dir), 0.0);
-    let diffuse_color = u_light.color * diffuse_strength;
+    let view_distance = distance(in.position, u_camera.position);
+    var out: SceneOutput;
+    out.color = vec4<f32>(apply_fog(result, view_distance), surface_color.a);
+    // the fog covers the ambient light like the rest
+    let clear = 1.0 - fog_amount(view_distance);
+    out.ambient = vec4<f32>(u_light.ambient_color * surface_color.xyz * clear, surface_color.a);
+    return out;
+}
 
-    let view_dir = normalize(u_camera.position - in.position);
-    let half_dir = normalize(view_dir + light_dir);
+// debug views, each one is a pipeline variant with its own fragment entry point
 
-    let specular_strength = pow(max(dot(normal, half_dir), 0.0), 32.0);
-    let specular_color = specular_strength * u_light.color;
+// world space normal, -1..1 mapped to 0..1
+[[stage(fragment)]]
+fn fs_normals(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    if (block_color(in.tex_coords).a < 0.5) {
+        discard;
+    }
+    return vec4<f32>(normalize(in.normal) * 0.5 + vec3<f32>(0.5), 1.0);
+}
 
-    let surface_color = vec4<f32>(in.diffuse_color, 1.0);
+// a random bright color per chunk
+fn chunk_color(chunk: vec3<f32>) -> vec3<f32> {
+    let hue = fract(sin(dot(chunk, vec3<f32>(12.9898, 78.233, 37.719))) * 43758.5453);
+    let rgb = abs(fract(vec3<f32>(hue) + vec3<f32>(0.0, 0.6667, 0.3333)) * 6.0 - vec3<f32>(3.0)) - vec3<f32>(1.0);
+    return mix(vec3<f32>(1.0), clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(0.75));
+}
 
-    let result = (diffuse_color + ambient_color + specular_color) * surface_color.xyz;
+[[stage(fragment)]]
+fn fs_chunk_colors(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    if (block_color(in.tex_coords).a < 0.5) {
+        discard;
+    }
+    let normal = normalize(in.normal);
+    // faces lie on the min side of the voxel that emitted them, nudge into that voxel
+    let voxel = floor(in.position + vec3<f32>(0.5) + abs(normal) * 0.25);
+    // the chunk size, chunk::SIZE
+    let chunk = floor(voxel / 16.0);
+    // darker sides keep the shape readable
+    let shade = 0.6 + 0.4 * abs(normal.y);
+    return vec4<f32>(chunk_color(chunk) * shade, 1.0);
+}
+
+// blue (no light) over green to red (twice the full sun)
+fn heat_color(t: f32) -> vec3<f32> {
+    let x = clamp(t, 0.0, 1.0) * 4.0;
+    return clamp(
+        vec3<f32>(x - 2.0, 2.0 - abs(x - 2.0), 2.0 - x),
+        vec3<f32>(0.0),
+        vec3<f32>(1.0),
+    );
+}
 
-    return vec4<f32>(result, surface_color.a);
-}
\ No newline at end of file
+// all light reaching the surface, ignoring its color
+[[stage(fragment)]]
+fn fs_light_level(
+    in: VertexOutput,
+) -> [[location(0)]] vec4<f32> {
+    if (block_color(in.tex_coords).a < 0.5) {
+        discard;
+    }
+    let light = surface_light(in.position, normalize(in.normal), in.emission);
+    let luminance = dot(light, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return vec4<f32>(heat_color(luminance * 0.5), 1.0);
+}
@@ -1,6 +1,10 @@
 use crate::{
-    create_render_pipeline,
-    rendering::{render_utils, vertex_desc::VertexDesc},
+    point_lights,
+    rendering::{
+        fog,
+        render_utils::{self, PipelineOptions},
+        vertex_desc::VertexDesc,
+    },
     texture,
     voxel_tools::rendering::voxel_vertex::VoxelVertex,
 };
@@ -9,23 +13,33 @@ pub fn create_voxel_pipeline(
     device: &wgpu::Device,
     texture_format: wgpu::TextureFormat,
     light_bind_group_layout: &wgpu::BindGroupLayout,
+    block_textures_bind_group_layout: &wgpu::BindGroupLayout,
+    shadow_bind_group_layout: &wgpu::BindGroupLayout,
+    options: PipelineOptions,
 ) -> wgpu::RenderPipeline {
     let visibility = wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT;
     let camera_bind_group_layout =
         render_utils::create_bind_group_layout(&device, "camera_bind_layout", 0, visibility);
 
-    let shader_module = render_utils::create_shader_module(
-        &device,
-        include_str!("voxel.wgsl"),
-        "voxel_shader_module",
+    let source = format!(
+        "{}\n{}\n{}",
+        fog::shader_source(1),
+        point_lights::shader_source(1),
+        include_str!("voxel.wgsl")
     );
+    let shader_module = render_utils::create_shader_module(&device, &source, "voxel_shader_module");
 
-    let bind_group_layouts = &[&camera_bind_group_layout, &light_bind_group_layout];
+    let bind_group_layouts = &[
+        &camera_bind_group_layout,
+        light_bind_group_layout,
+        block_textures_bind_group_layout,
+        shadow_bind_group_layout,
+    ];
     let pipeline_layout =
         render_utils::create_pipeline_layout(&device, "voxel_pipeline", bind_group_layouts);
 
-    println!("creating pipeline");
-    let render_pipeline = create_render_pipeline(
+    log::debug!("creating voxel pipeline");
+    let render_pipeline = render_utils::create_render_pipeline_with_options(
         &device,
         &pipeline_layout,
         texture_format,
@@ -33,6 +47,62 @@ pub fn create_voxel_pipeline(
         &[VoxelVertex::desc()],
         shader_module,
         "voxel_pipeline",
+        options,
     );
     render_pipeline
 }
+
+// renders chunk depth from the sun into one shadow cascade
+pub fn create_voxel_shadow_pipeline(
+    device: &wgpu::Device,
+    cascade_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader_module = render_utils::create_shader_module(
+        device,
+        include_str!("voxel_shadow.wgsl"),
+        "voxel_shadow_shader_module",
+    );
+    let pipeline_layout = render_utils::create_pipeline_layout(
+        device,
+        "voxel_shadow_pipeline",
+        &[cascade_bind_group_layout],
+    );
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("voxel_shadow_pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader_module,
+            entry_point: "vs_main",
+            buffers: &[VoxelVertex::desc()],
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            // chunks only have faces towards open space, back faces of hills still block the sun
+            cull_mode: None,
+            clamp_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            // slope scaled bias keeps surfaces at grazing angles from shadowing themselves
+            bias: wgpu::DepthBiasState {
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
@@ -1,17 +1,18 @@
-use anyhow::*;
+use crate::rendering::{indirect_draw::IndirectDrawBuffer, mesh_buffer_pool::MeshBufferPool};
 
-pub fn draw_chunk<'a, 'b>(
+// draws every chunk mesh in the draw buffer, bind groups are only set once
+pub fn draw_chunks<'a>(
     render_pass: &mut wgpu::RenderPass<'a>,
-    num_indices: u32,
     camera_u: &'a wgpu::BindGroup,
     light_u: &'a wgpu::BindGroup,
-    vertex_buffer: &'a wgpu::Buffer,
-    index_buffer: &'a wgpu::Buffer,
-) -> Result<()> {
-    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-    render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-    render_pass.set_bind_group(0, &camera_u, &[]);
-    render_pass.set_bind_group(1, &light_u, &[]);
-    render_pass.draw_indexed(0..num_indices, 0, 0..1);
-    Ok(())
+    block_textures: &'a wgpu::BindGroup,
+    shadows: &'a wgpu::BindGroup,
+    draws: &'a IndirectDrawBuffer,
+    chunk_buffers: &'a MeshBufferPool,
+) {
+    render_pass.set_bind_group(0, camera_u, &[]);
+    render_pass.set_bind_group(1, light_u, &[]);
+    render_pass.set_bind_group(2, block_textures, &[]);
+    render_pass.set_bind_group(3, shadows, &[]);
+    draws.draw(render_pass, chunk_buffers);
 }
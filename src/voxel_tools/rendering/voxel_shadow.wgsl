@@ -0,0 +1,15 @@
+[[block]]
+struct CascadeUniform {
+    light_view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> u_cascade: CascadeUniform;
+
+// depth only, no fragment stage
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec3<f32>,
+) -> [[builtin(position)]] vec4<f32> {
+    return u_cascade.light_view_proj * vec4<f32>(position, 1.0);
+}
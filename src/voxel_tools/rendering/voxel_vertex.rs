@@ -5,7 +5,11 @@ use crate::rendering::vertex_desc::VertexDesc;
 pub struct VoxelVertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
-    pub color_diffuse: [f32; 3],
+    pub color_diffuse: [f32; 4],
+    // u, v and block texture array layer
+    pub tex_coords: [f32; 3],
+    // added on top of the lit color, 0 for blocks that don't glow
+    pub emission: f32,
 }
 
 impl VertexDesc for VoxelVertex {
@@ -27,12 +31,24 @@ impl VertexDesc for VoxelVertex {
                     offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
                 },
-                // diffuse color
+                // diffuse color, alpha below 1 only in translucent meshes
                 wgpu::VertexAttribute {
-                    format: wgpu::VertexFormat::Float32x3,
+                    format: wgpu::VertexFormat::Float32x4,
                     offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
                     shader_location: 2,
                 },
+                // texture coordinates
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                },
+                // emission
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32,
+                    offset: mem::size_of::<[f32; 13]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                },
             ],
         }
     }
@@ -0,0 +1,98 @@
+use super::{block::BlockType, generator::WorldGenerator, voxel::Voxel};
+
+// columns searched around the origin, in voxels
+pub const SPAWN_SEARCH_RADIUS: i32 = 64;
+// heights searched above and below the origin
+pub const SPAWN_SEARCH_HEIGHT: i32 = 48;
+// air voxels needed above the ground
+pub const SPAWN_CLEARANCE: i32 = 2;
+// camera above the ground surface
+pub const EYE_HEIGHT: f32 = 1.6;
+
+// the camera position closest to the origin with solid ground below and open air around the
+// head, searched ring by ring outwards and per column from the origin height up and down
+pub fn find_spawn(
+    generator: &WorldGenerator,
+    origin: cgmath::Vector3<i32>,
+) -> Option<cgmath::Vector3<f32>> {
+    let heights = search_heights();
+    for radius in 0..=SPAWN_SEARCH_RADIUS {
+        for (x, z) in ring(radius) {
+            let column = cgmath::Vector3::new(origin.x + x, origin.y, origin.z + z);
+            // the whole column once, every height below looks at its neighbours
+            let bottom = column.y - SPAWN_SEARCH_HEIGHT - 1;
+            let voxels = (bottom..=column.y + SPAWN_SEARCH_HEIGHT + SPAWN_CLEARANCE)
+                .map(|y| generator.voxel(cgmath::Vector3::new(column.x, y, column.z)))
+                .collect::<Vec<_>>();
+            for offset in heights.iter() {
+                let index = (SPAWN_SEARCH_HEIGHT + 1 + offset) as usize;
+                if is_spawnable(&voxels[index - 1..=index + SPAWN_CLEARANCE as usize - 1]) {
+                    // voxels are centered on whole coordinates, the ground surface is half a
+                    // voxel below the first air voxel
+                    let ground = (column.y + offset) as f32 - 0.5;
+                    return Some(cgmath::Vector3::new(
+                        column.x as f32,
+                        ground + EYE_HEIGHT,
+                        column.z as f32,
+                    ));
+                }
+            }
+        }
+    }
+    None
+}
+
+// the ground voxel followed by the voxels above it
+// water has no density now, but it must never count as ground even if it gets one
+fn is_spawnable(voxels: &[Voxel]) -> bool {
+    voxels[0].is_solid()
+        && voxels[0].block_type() != BlockType::Water
+        && voxels[1..]
+            .iter()
+            .all(|voxel| voxel.block_type() == BlockType::Air)
+}
+
+// 0, 1, -1, 2, -2, ... relative to the origin height
+fn search_heights() -> Vec<i32> {
+    let mut heights = vec![0];
+    for distance in 1..=SPAWN_SEARCH_HEIGHT {
+        heights.push(distance);
+        heights.push(-distance);
+    }
+    heights
+}
+
+// the columns at exactly this chessboard distance from the center
+fn ring(radius: i32) -> Vec<(i32, i32)> {
+    if radius == 0 {
+        return vec![(0, 0)];
+    }
+    let mut columns = Vec::with_capacity(8 * radius as usize);
+    for i in -radius..radius {
+        columns.push((i, -radius));
+        columns.push((radius, i));
+        columns.push((-i, radius));
+        columns.push((-radius, -i));
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawns_need_solid_ground_that_isnt_water() {
+        let air = Voxel::new_block(BlockType::Air);
+        let column = |ground| vec![Voxel::new_block(ground), air, air];
+        assert!(is_spawnable(&column(BlockType::Grass)));
+        assert!(!is_spawnable(&column(BlockType::Water)));
+        assert!(!is_spawnable(&column(BlockType::Air)));
+        let water_above = [
+            Voxel::new_block(BlockType::Grass),
+            Voxel::new_block(BlockType::Water),
+            air,
+        ];
+        assert!(!is_spawnable(&water_above));
+    }
+}
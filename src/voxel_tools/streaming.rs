@@ -0,0 +1,69 @@
+// how much chunk work (generation or meshing) is allowed in a single frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameBudget {
+    // process at most this many chunks per frame, deterministic
+    Count(usize),
+}
+
+pub const DEFAULT_DATA_BUDGET: FrameBudget = FrameBudget::Count(16);
+pub const DEFAULT_MESH_BUDGET: FrameBudget = FrameBudget::Count(4);
+
+impl FrameBudget {
+    // call once at the start of the work loop
+    pub fn start(self) -> BudgetTracker {
+        BudgetTracker {
+            budget: self,
+            spent: 0,
+        }
+    }
+}
+
+pub struct BudgetTracker {
+    budget: FrameBudget,
+    spent: usize,
+}
+
+impl BudgetTracker {
+    pub fn has_budget(&self) -> bool {
+        match self.budget {
+            FrameBudget::Count(max) => self.spent < max,
+        }
+    }
+
+    // one unit of work was done
+    pub fn spend(&mut self) {
+        self.spent += 1;
+    }
+}
+
+// radii in chunks around the camera chunk
+// unload radii are larger than load radii so chunks at the edge don't load and unload every
+// time the camera moves back and forth over a chunk boundary
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamingPolicy {
+    pub mesh_load_radius: i32,
+    pub mesh_unload_radius: i32,
+    pub data_load_radius: i32,
+    pub data_unload_radius: i32,
+}
+
+// how many chunks further away than the load radius a chunk is unloaded
+pub const UNLOAD_HYSTERESIS: i32 = 2;
+
+impl StreamingPolicy {
+    pub fn from_render_distance(render_distance: i32) -> Self {
+        let render_distance = render_distance.max(1);
+        // meshes need the voxel data of adjacent chunks, so data is loaded one chunk further
+        Self {
+            mesh_load_radius: render_distance,
+            mesh_unload_radius: render_distance + UNLOAD_HYSTERESIS,
+            data_load_radius: render_distance + 1,
+            data_unload_radius: render_distance + 1 + UNLOAD_HYSTERESIS,
+        }
+    }
+
+    // sphere check in chunk coordinates
+    pub fn in_radius(offset: cgmath::Vector3<i32>, radius: i32) -> bool {
+        offset.x * offset.x + offset.y * offset.y + offset.z * offset.z < radius * radius
+    }
+}
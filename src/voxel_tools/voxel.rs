@@ -1,6 +1,9 @@
+use super::block::BlockType;
+
 #[derive(Copy, Clone, Debug)]
 pub struct Voxel {
     density: u8,
+    block_type: BlockType,
 }
 
 // decorative argument type
@@ -8,7 +11,14 @@ pub struct Density(u8);
 
 impl Voxel {
     pub fn new(density: Density) -> Self {
-        Self { density: density.0 }
+        Self {
+            density: density.0,
+            block_type: if density.0 > 0 {
+                BlockType::Grass
+            } else {
+                BlockType::Air
+            },
+        }
     }
 
     pub fn new_empty() -> Self {
@@ -20,10 +30,30 @@ impl Voxel {
         Self::new(Density(255u8))
     }
 
+    // fluids like water have no density, everything else that isn't air is solid
+    pub fn new_block(block_type: BlockType) -> Self {
+        let density = match block_type {
+            BlockType::Air | BlockType::Water => 0u8,
+            _ => 255u8,
+        };
+        Self {
+            density,
+            block_type,
+        }
+    }
+
     pub fn is_solid(&self) -> bool {
         self.density > 0u8
     }
 
+    pub fn block_type(&self) -> BlockType {
+        self.block_type
+    }
+
+    pub fn density(&self) -> u8 {
+        self.density
+    }
+
     #[allow(dead_code)]
     pub fn density_fraction(&self) -> f32 {
         self.density as f32 / 255f32
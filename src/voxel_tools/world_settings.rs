@@ -0,0 +1,74 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use super::generator::{GeneratorKind, WorldGenerator, DEFAULT_SEED};
+
+// in the world directory, one key=value per line
+pub const WORLD_FILE: &str = "world.txt";
+// cached chunk meshes, below the world directory
+pub const MESH_CACHE_DIR: &str = "meshes";
+
+// what a world is generated from, saved in its directory so it is the same world next time
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WorldSettings {
+    pub seed: u32,
+    pub generator: GeneratorKind,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            generator: GeneratorKind::Caves,
+        }
+    }
+}
+
+impl WorldSettings {
+    pub fn generator(&self) -> WorldGenerator {
+        WorldGenerator::new(self.generator, self.seed)
+    }
+
+    fn path(dir: &Path) -> PathBuf {
+        dir.join(WORLD_FILE)
+    }
+
+    // None for a new world
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(&path).with_context(|| format!("reading {:?}", path))?;
+        Self::parse(&text)
+            .with_context(|| format!("reading {:?}", path))
+            .map(Some)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut settings = Self::default();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = match line.find('=') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => bail!("expected key=value, found {:?}", line),
+            };
+            match key {
+                "seed" => {
+                    settings.seed = value
+                        .parse()
+                        .with_context(|| format!("invalid seed {:?}", value))?
+                }
+                "generator" => settings.generator = GeneratorKind::parse(value)?,
+                _ => bail!("unknown key {:?}", key),
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {:?}", dir))?;
+        let path = Self::path(dir);
+        let text = format!("seed={}\ngenerator={}\n", self.seed, self.generator.name());
+        std::fs::write(&path, text).with_context(|| format!("writing {:?}", path))
+    }
+}